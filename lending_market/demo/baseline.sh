@@ -77,9 +77,18 @@ echo "CALL_FUNCTION
     \"LendingMarket\"
     \"instantiate\"
     Tuple(
-        10u8, 
+        20u8,
         Decimal(\"0.4\"),
-        Decimal(\"0.99\")
+        Decimal(\"0.99\"),
+        Enum<0u8>(),
+        Decimal(\"0.95\"),
+        Decimal(\"0\"),
+        Enum<0u8>(),
+        Decimal(\"0.9\"),
+        Decimal(\"10\"),
+        Decimal(\"0.05\"),
+        Decimal(\"0.5\"),
+        Decimal(\"0.8\")
     )
 ;" >> tx.rtm
 
@@ -1,6 +1,8 @@
 use crate::modules::{
-    cdp_data::*, cdp_health_checker::*, interest_strategy::*, liquidation_threshold::*,
-    market_config::*, operation_status::*, pool_config::*, pool_state::*, utils::*,
+    cdp_data::*, cdp_health_checker::*, cdp_index::*, interest_strategy::*, liquidation_threshold::*,
+    market_config::*, operation_status::*, pool_config::*, pool_context::*, pool_history::*,
+    pool_state::*, utils::*,
+    withdrawal_queue::*,
 };
 use crate::resources::*;
 use scrypto::prelude::*;
@@ -12,11 +14,12 @@ pub enum UpdateCDPInput {
     KeyImageURL(String),
     Name(String),
     Description(String),
+    LiquidationPreference(Vec<ResourceAddress>),
 }
 
 #[blueprint]
-#[types(ResourceAddress, CDPUpdatedEvenType, CDPLiquidable, CDPType, CollaterizedDebtPositionData, WrappedCDPData, PositionData, ExtendedCollateralPositionData, ExtendedLoanPositionData, CDPHealthChecker, InterestStrategyBreakPoints, InterestStrategy, UpdateLiquidationThresholdInput, LiquidationThreshold, UpdateMarketConfigInput, MarketConfig, OperatingStatus, PoolConfig, LendingPoolUpdatedEvent, MarketStatsPool, MarketStatsAllPools, LendingPoolState, WithdrawType, DepositType, PriceInfo)]
-#[events(CDPUpdatedEvent, LendingPoolUpdatedEvent, CDPLiquidableEvent)]
+#[types(ResourceAddress, CDPUpdatedEvenType, CDPLiquidable, CDPHealthData, LiquidationQuote, LiquidationQuoteRepayment, LiquidationQuoteSeizure, BatchLiquidationResult, CDPFilter, CDPPage, CDPType, CDPHealthBand, CDPNonFungibleData, CollaterizedDebtPositionData, CDPPositions, WrappedCDPData, PositionData, ExtendedCollateralPositionData, ExtendedLoanPositionData, CDPHealthChecker, PoolCDPIndex, InterestStrategyBreakPoints, InterestStrategy, UpdateLiquidationThresholdInput, LiquidationThreshold, UpdateMarketConfigInput, MarketConfig, OperatingStatus, PoolConfig, LendingPoolUpdatedEvent, MarketStatsPool, MarketStatsAllPools, AccountSummary, PoolSnapshot, PoolHistory, PoolRevenue, RevenueSource, ReservePolicy, ListingStatus, PoolUnitMigration, WithdrawalRequest, WithdrawalQueue, LendingPoolState, WithdrawType, DepositType, PriceInfo)]
+#[events(CDPUpdatedEvent, CDPPositionsUpdatedEvent, RepayEvent, LiquidationEvent, LendingPoolUpdatedEvent, CDPLiquidableEvent, ReserveCollectedEvent, AssetRolesChangedEvent, PoolPriceStatusChangedEvent, CDPAtRiskEvent)]
 mod lending_market {
 
     extern_blueprint!(
@@ -76,15 +79,27 @@ mod lending_market {
 
             update_price_feed => restrict_to: [admin];
             update_market_config => restrict_to: [admin];
+            update_cdp_key_image_url_template => restrict_to: [admin];
             update_pool_config => restrict_to: [admin];
             update_liquidation_threshold => restrict_to: [admin];
+            set_borrow_ltv => restrict_to: [admin];
+            update_borrow_ltv => restrict_to: [admin];
             update_interest_strategy => restrict_to: [admin];
+            update_reserve_policy => restrict_to: [admin];
+            freeze_lending_pool => restrict_to: [admin];
+            wind_down_lending_pool => restrict_to: [admin];
+            retire_lending_pool => restrict_to: [admin];
+            migrate_pool_price_feed => restrict_to: [admin];
+            replace_pool_component => restrict_to: [admin];
+            cover_shortfall => restrict_to: [admin];
 
             admin_update_operating_status => restrict_to: [admin];
 
             update_operating_status => restrict_to: [admin,moderator];
 
             update_pool_state => PUBLIC;
+            audit_listed_asset => PUBLIC;
+            confirm_asset_roles => restrict_to: [admin];
 
             /* Reserve Collector methods*/
 
@@ -100,44 +115,97 @@ mod lending_market {
 
             show_cdp => PUBLIC;
 
+            burn_cdp => PUBLIC;
+
+            migrate_cdp_positions => PUBLIC;
+
+            merge_cdps => PUBLIC;
+
+            split_cdp => PUBLIC;
+
             // Lending and Borrowing methods
 
             contribute => PUBLIC;
             redeem => PUBLIC;
+            redeem_partial => PUBLIC;
+            request_withdrawal => PUBLIC;
+            claim_withdrawal => PUBLIC;
+            cancel_withdrawal => PUBLIC;
+            migrate_pool_units => PUBLIC;
 
             add_collateral => PUBLIC;
             remove_collateral => PUBLIC;
+            update_collateral_usage => PUBLIC;
             borrow => PUBLIC;
             repay => PUBLIC;
 
             // Liquidation methods
             mint_liquidator_badge => restrict_to: [admin];
             list_liquidable_cdps => PUBLIC;
+            list_cdps => PUBLIC;
+            list_cdps_by_pool => PUBLIC;
             check_cdp_for_liquidation => PUBLIC;
+            quote_liquidation => PUBLIC;
             start_liquidation => restrict_to: [admin,liquidator];
+            start_liquidation_with_badge => restrict_to: [admin,liquidator];
             end_liquidation => restrict_to: [admin,liquidator];
             fast_liquidation => restrict_to: [admin,liquidator];
+            fast_liquidation_with_badge => restrict_to: [admin,liquidator];
+            batch_fast_liquidation => restrict_to: [admin,liquidator];
+            batch_fast_liquidation_with_badge => restrict_to: [admin,liquidator];
 
             // Statistics queries
             list_info_stats => PUBLIC;
+            get_account_summary => PUBLIC;
+            get_pool_history => PUBLIC;
+            get_pool_revenue => PUBLIC;
+
+            // Oracle methods
+            get_pool_unit_price => PUBLIC;
         }
 
     }
 
     macro_rules! save_cdp_macro {
-        ($self:expr,$cdp:expr) => {
-            $cdp.save_cdp(&$self.cdp_res_manager, $self.market_config.max_cdp_position)
-                .expect("Error saving CDP");
-        };
+        ($self:expr,$cdp:expr) => {{
+            save_cdp_macro!($self, $cdp, None)
+        }};
+        // The LTV ratio already computed by a health check is reused for the health band
+        ($self:expr,$cdp:expr,$loan_to_value_ratio:expr) => {{
+            let loan_to_value_ratio = $cdp
+                .update_health_band(
+                    $loan_to_value_ratio,
+                    &$self.pool_states,
+                    &$self.cdp_key_image_url_template,
+                    &$self.market_config,
+                )
+                .expect("Error updating CDP health band");
+            $cdp.save_cdp(
+                &$self.cdp_res_manager,
+                &$self.cdp_positions,
+                $self.market_config.max_cdp_position,
+                &$self.pool_cdp_indexes,
+            )
+            .expect("Error saving CDP");
+            loan_to_value_ratio
+        }};
     }
 
     macro_rules! emit_cdp_event {
-        ($cdp_id:expr,$event_type:expr) => {
+        ($cdp_id:expr,$event_type:expr,$positions:expr,$loan_to_value_ratio:expr) => {{
+            let cdp_id = $cdp_id;
+            let event_type = $event_type;
             Runtime::emit_event(CDPUpdatedEvent {
-                cdp_id: $cdp_id,
-                event_type: $event_type,
+                cdp_id: cdp_id.clone(),
+                event_type: event_type.clone(),
             });
-        };
+            Runtime::emit_event(CDPPositionsUpdatedEvent {
+                cdp_id,
+                event_type,
+                positions: $positions,
+                loan_to_value_ratio: $loan_to_value_ratio,
+            });
+        }};
     }
 
     /// Lending market component
@@ -154,6 +222,12 @@ mod lending_market {
         /// Counter of created liquidators
         liquidator_counter: u64,
 
+        /// Resource manager of the withdrawal requests
+        withdrawal_request_res_manager: ResourceManager,
+
+        /// Counter of created withdrawal requests
+        withdrawal_request_counter: u64,
+
         /// Current lending market component address
         market_component_address: ComponentAddress,
 
@@ -169,6 +243,15 @@ mod lending_market {
         /// Map the asset resource addresses to the pool states
         pool_states: KeyValueStore<ResourceAddress, LendingPoolState>,
 
+        /// Map the asset resource addresses to the index of the CDPs with positions in the pool
+        pool_cdp_indexes: KeyValueStore<ResourceAddress, PoolCDPIndex>,
+
+        /// Map the pool unit resource addresses of the replaced pool components to their migration
+        pool_unit_migrations: KeyValueStore<ResourceAddress, PoolUnitMigration>,
+
+        /// Map the CDP ids to the CDP positions
+        cdp_positions: KeyValueStore<NonFungibleLocalId, CDPPositions>,
+
         /// Resource manager of the transient token, like liquidation token
         transient_res_manager: ResourceManager,
 
@@ -180,6 +263,9 @@ mod lending_market {
 
         /// The market configuration
         market_config: MarketConfig,
+
+        /// Template used to render the default key image URL of the CDPs, see `render_cdp_key_image_url`
+        cdp_key_image_url_template: Option<String>,
     }
 
     impl LendingMarket {
@@ -229,7 +315,12 @@ mod lending_market {
                 create_transient_res_manager(admin_rule.clone(), component_rule.clone());
 
             // * Create liquidator badge manager * //
-            let liquidator_badge_manager = create_liquidator_badge_manager(admin_rule.clone(), component_rule);
+            let liquidator_badge_manager =
+                create_liquidator_badge_manager(admin_rule.clone(), component_rule.clone());
+
+            // * Create withdrawal request resource manager * //
+            let withdrawal_request_res_manager =
+                create_withdrawal_request_res_manager(admin_rule.clone(), component_rule);
 
             // *  Instantiate our component with the previously created resources and addresses * //
             Self {
@@ -238,14 +329,20 @@ mod lending_market {
                 admin_rule: admin_rule.clone(),
                 cdp_counter: 0,
                 liquidator_counter: 0,
+                withdrawal_request_res_manager,
+                withdrawal_request_counter: 0,
                 transient_res_manager,
                 liquidator_badge_manager,
                 pool_unit_refs: IndexMap::new(),
                 reverse_pool_unit_refs: IndexMap::new(),
                 pool_states: KeyValueStore::<ResourceAddress, LendingPoolState>::new_with_registered_type(),
+                pool_cdp_indexes: KeyValueStore::<ResourceAddress, PoolCDPIndex>::new_with_registered_type(),
+                pool_unit_migrations: KeyValueStore::<ResourceAddress, PoolUnitMigration>::new_with_registered_type(),
+                cdp_positions: KeyValueStore::<NonFungibleLocalId, CDPPositions>::new_with_registered_type(),
                 listed_assets: IndexSet::new(),
                 operating_status: OperatingStatus::new(),
                 market_config,
+                cdp_key_image_url_template: None,
             }
             .instantiate()
             .prepare_to_globalize(OwnerRole::None)
@@ -280,7 +377,8 @@ mod lending_market {
         /// - `pool_res_address`: The resource address of the asset exchanged in the pool
         /// - `pool_config`: The pool configuration
         /// - `interest_strategy_break_points`: The interest strategy break points
-        /// - `liquidation_threshold`: The liquidation threshold
+        /// - `liquidation_threshold`: The liquidation threshold. The max borrow LTV of the pool is
+        ///   derived from it with the market borrow LTV margin
         pub fn create_lending_pool(
             &mut self,
             price_feed_component: Global<AnyComponent>,
@@ -294,6 +392,11 @@ mod lending_market {
                 "The lending pool is already registered"
             );
 
+            assert!(
+                self.pool_states.get(&pool_res_address).is_none(),
+                "A retired lending pool cannot be registered again"
+            );
+
             let res_manager: ResourceManager = pool_res_address.into();
 
             let recaller_role = res_manager
@@ -314,28 +417,17 @@ mod lending_market {
                 .check()
                 .expect("Invalid liquidation threshold");
 
-            pool_config.check().expect("Invalid pool config");
-
-            let component_rule = rule!(require(global_caller(self.market_component_address)));
-
-            let (pool, pool_unit_res_address) = Blueprint::<SingleResourcePool>::instantiate(
-                pool_res_address,
-                OwnerRole::Fixed(component_rule.clone()),
-                self.admin_rule.clone(),
-                component_rule.clone(),
-                component_rule.clone(),
-                component_rule,
-            );
-
-            let pool_unit_res_manager = ResourceManager::from_address(pool_unit_res_address);
+            // The max borrow LTV is derived from the liquidation threshold, keeping the market margin
+            let borrow_ltv_margin = self.market_config.borrow_ltv_margin;
+            let borrow_ltv = if borrow_ltv_margin > Decimal::ZERO {
+                Some(liquidation_threshold.scale(Decimal::ONE - borrow_ltv_margin))
+            } else {
+                None
+            };
 
-            let pool_res_symbol: String = ResourceManager::from_address(pool_res_address)
-            .get_metadata("symbol").expect("Pool resource symbol not provided").unwrap_or_default();
+            pool_config.check().expect("Invalid pool config");
 
-            pool_unit_res_manager.set_metadata("name", format!("{pool_res_symbol} rtToken"));
-            pool_unit_res_manager.lock_metadata("name");
-            pool_unit_res_manager.set_metadata("symbol", format!("rt{pool_res_symbol}"));
-            pool_unit_res_manager.lock_metadata("symbol");
+            let (pool, pool_unit_res_address) = self._instantiate_pool(pool_res_address);
 
             let mut interest_strategy = InterestStrategy::new();
 
@@ -357,6 +449,7 @@ mod lending_market {
 
                 price_updated_at: Clock::current_time(TimePrecision::Second)
                     .seconds_since_unix_epoch,
+                price_stale: false,
 
                 total_loan: 0.into(),
                 total_deposit: 0.into(),
@@ -370,14 +463,24 @@ mod lending_market {
                 price_feed_comp: price_feed_component,
                 interest_strategy,
                 liquidation_threshold,
+                borrow_ltv,
                 pool_config,
                 operating_status: OperatingStatus::new(),
-                pool_utilization: 0.into()
+                pool_utilization: 0.into(),
+                history: PoolHistory::new(),
+                revenue: PoolRevenue::default(),
+                reserve_policy: ReservePolicy::default(),
+                reserve_pool_units: Vault::new(pool_unit_res_address),
+                listing_status: ListingStatus::Listed,
+                asset_roles: get_asset_roles(pool_res_address),
+                withdrawal_queue: WithdrawalQueue::new(pool_unit_res_address, pool_res_address),
             };
 
             //
             self.pool_states.insert(pool_res_address, pool_state);
 
+            self.pool_cdp_indexes.insert(pool_res_address, PoolCDPIndex::new());
+
             //
             self.reverse_pool_unit_refs
                 .insert(pool_unit_res_address, pool_res_address);
@@ -388,25 +491,37 @@ mod lending_market {
             self.listed_assets.insert(pool_res_address);
         }
 
-        /// Collect reserve retention from all pools
+        /// Collect reserve retention from all pools, or from a single pool
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool to collect the reserve from, `None` to collect from all pools
+        /// - `min_amount`: Minimum amount of asset to collect from each pool, pools with a lower
+        ///   reserve are skipped
         /// 
         /// *Output*
         /// - List of tuples having
         ///     - The price of the asset
         ///     - The asset coming from the reserve vault
-        pub fn collect_reserve(&mut self) -> Vec<(Decimal, Bucket)> {
-            let listed_assets = self.listed_assets.clone();
+        pub fn collect_reserve(
+            &mut self,
+            pool_res_address: Option<ResourceAddress>,
+            min_amount: Option<Decimal>,
+        ) -> Vec<(Decimal, Bucket)> {
+            let pool_res_addresses = match pool_res_address {
+                Some(pool_res_address) => vec![pool_res_address],
+                None => self.listed_assets.iter().copied().collect(),
+            };
 
-            listed_assets
+            pool_res_addresses
                 .iter()
-                .map(|pool_res_address| {
+                .filter_map(|pool_res_address| {
                     let mut pool_state = self._get_pool_state(pool_res_address, None, None);
 
                     let price = pool_state.price;
 
-                    let fee = pool_state.reserve.take_all();
-
-                    (price, fee)
+                    pool_state
+                        .collect_reserve(min_amount.unwrap_or(Decimal::ZERO))
+                        .map(|fee| (price, fee))
                 })
                 .collect()
         }
@@ -438,12 +553,98 @@ mod lending_market {
             pool_res_address: ResourceAddress,
             value: UpdateLiquidationThresholdInput,
         ) {
+            let borrow_ltv_margin = self.market_config.borrow_ltv_margin;
+
             let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
 
             pool_state
                 .liquidation_threshold
                 .update_liquidation_threshold(value)
                 .expect("Invalid liquidation threshold");
+
+            if let Some(borrow_ltv) = pool_state.borrow_ltv.as_ref() {
+                borrow_ltv
+                    .check_below(
+                        &pool_state.liquidation_threshold,
+                        borrow_ltv_margin,
+                        pool_res_address,
+                        pool_state.pool_config.asset_type,
+                    )
+                    .expect("Borrow LTV must be lower than the liquidation threshold");
+            }
+        }
+
+        /// Set the max borrow LTV of a pool, used instead of the liquidation threshold to check
+        /// risk-increasing actions
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address for which to set the borrow LTV
+        /// - `borrow_ltv`: The borrow LTV configuration
+        /// 
+        /// *Error*
+        /// - If the configuration is invalid
+        /// - If the configuration is not lower than the liquidation threshold by the market borrow LTV margin
+        pub fn set_borrow_ltv(
+            &mut self,
+            pool_res_address: ResourceAddress,
+            borrow_ltv: LiquidationThreshold,
+        ) {
+            let borrow_ltv_margin = self.market_config.borrow_ltv_margin;
+
+            let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+            borrow_ltv.check().expect("Invalid borrow LTV");
+
+            borrow_ltv
+                .check_below(
+                    &pool_state.liquidation_threshold,
+                    borrow_ltv_margin,
+                    pool_res_address,
+                    pool_state.pool_config.asset_type,
+                )
+                .expect("Borrow LTV must be lower than the liquidation threshold");
+
+            pool_state.borrow_ltv = Some(borrow_ltv);
+        }
+
+        /// Update the max borrow LTV of a pool
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address for which to change the borrow LTV
+        /// - `value`: Input of the borrow LTV update
+        /// 
+        /// *Error*
+        /// - If the borrow LTV is not set
+        /// - If the configuration is invalid
+        /// - If the configuration is not lower than the liquidation threshold by the market borrow LTV margin
+        pub fn update_borrow_ltv(
+            &mut self,
+            pool_res_address: ResourceAddress,
+            value: UpdateLiquidationThresholdInput,
+        ) {
+            let borrow_ltv_margin = self.market_config.borrow_ltv_margin;
+
+            let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+            let mut borrow_ltv = pool_state
+                .borrow_ltv
+                .clone()
+                .expect("Borrow LTV not set");
+
+            borrow_ltv
+                .update_liquidation_threshold(value)
+                .expect("Invalid borrow LTV");
+
+            borrow_ltv
+                .check_below(
+                    &pool_state.liquidation_threshold,
+                    borrow_ltv_margin,
+                    pool_res_address,
+                    pool_state.pool_config.asset_type,
+                )
+                .expect("Borrow LTV must be lower than the liquidation threshold");
+
+            pool_state.borrow_ltv = Some(borrow_ltv);
         }
 
         /// Update interest strategy of a pool
@@ -468,10 +669,53 @@ mod lending_market {
         /// 
         /// *Params*
         /// - `value`: Input of the market configuration update
+        /// 
+        /// *Error*
+        /// - If the configuration is invalid
+        /// - If a new borrow LTV margin is not kept by the borrow LTV of a pool
         pub fn update_market_config(&mut self, value: UpdateMarketConfigInput) {
+            let borrow_ltv_margin_updated = matches!(value, UpdateMarketConfigInput::BorrowLTVMargin(_));
+
             self.market_config
                 .update(value)
                 .expect("Invalid market config");
+
+            // The borrow LTV of every pool must keep the new margin. Pools without borrow LTV get
+            // one derived from their liquidation threshold, a zero margin leaves them without buffer
+            if borrow_ltv_margin_updated {
+                let borrow_ltv_margin = self.market_config.borrow_ltv_margin;
+
+                for pool_res_address in self.listed_assets.iter() {
+                    let mut pool_state = self.pool_states.get_mut(pool_res_address).unwrap();
+
+                    if let Some(borrow_ltv) = pool_state.borrow_ltv.as_ref() {
+                        borrow_ltv
+                            .check_below(
+                                &pool_state.liquidation_threshold,
+                                borrow_ltv_margin,
+                                *pool_res_address,
+                                pool_state.pool_config.asset_type,
+                            )
+                            .expect("Borrow LTV must be lower than the liquidation threshold");
+                    } else if borrow_ltv_margin > Decimal::ZERO {
+                        let borrow_ltv = pool_state
+                            .liquidation_threshold
+                            .scale(Decimal::ONE - borrow_ltv_margin);
+
+                        pool_state.borrow_ltv = Some(borrow_ltv);
+                    }
+                }
+            }
+        }
+
+        /// Update the template used to render the default key image URL of the CDPs.
+        /// The template can contain the `{cdp_id}` and `{health_band}` placeholders, so that
+        /// a renderer can display the risk of a position at a glance.
+        /// 
+        /// *Params*
+        /// - `template`: The URL template, or nothing to stop generating key image URLs
+        pub fn update_cdp_key_image_url_template(&mut self, template: Option<String>) {
+            self.cdp_key_image_url_template = template;
         }
 
         /// Update pool configuration
@@ -492,6 +736,280 @@ mod lending_market {
                 .expect("Invalid pool config");
         }
 
+        /// Freeze a pool as first step of its delisting: new contributions, collaterals and borrows
+        /// are not accepted anymore
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        pub fn freeze_lending_pool(&mut self, pool_res_address: ResourceAddress) {
+            let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+            assert!(
+                pool_state.listing_status == ListingStatus::Listed,
+                "Only a listed pool can be frozen"
+            );
+
+            for value in [
+                OperatingService::Contribute,
+                OperatingService::AddCollateral,
+                OperatingService::Borrow,
+            ] {
+                pool_state
+                    .operating_status
+                    .update(value, false, true)
+                    .expect("Error updating operating status");
+            }
+
+            pool_state.listing_status = ListingStatus::Frozen;
+        }
+
+        /// Start the wind-down of a frozen pool: the liquidation threshold of the asset is linearly
+        /// reduced to zero, pushing the borrowers to move their collaterals away
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        /// - `duration`: Duration of the wind-down, in seconds
+        pub fn wind_down_lending_pool(&mut self, pool_res_address: ResourceAddress, duration: i64) {
+            assert!(duration > 0, "Wind-down duration must be positive");
+
+            let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+            assert!(
+                pool_state.listing_status == ListingStatus::Frozen,
+                "Only a frozen pool can be wound down"
+            );
+
+            pool_state.listing_status = ListingStatus::WindingDown {
+                started_at: Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch,
+                duration,
+            };
+        }
+
+        /// Retire a wound down pool having no CDP positions left, removing it from the listed assets.
+        /// The pool state is kept so that the remaining pool units can still be redeemed
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        pub fn retire_lending_pool(&mut self, pool_res_address: ResourceAddress) {
+            assert!(
+                self.pool_cdp_indexes
+                    .get(&pool_res_address)
+                    .expect("Pool not found")
+                    .count == 0,
+                "The pool still has CDP positions"
+            );
+
+            {
+                let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+                assert!(pool_state.is_wound_down(), "The pool wind-down is not completed");
+
+                pool_state.withdraw_reserve();
+                pool_state.listing_status = ListingStatus::Retired;
+            }
+
+            self.listed_assets.shift_remove(&pool_res_address);
+        }
+
+        /// Move a pool to a new price oracle component. Unlike `update_price_feed`, the price of the
+        /// new oracle is checked against the last known price of the pool, and is applied at once
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        /// - `price_feed`: Global address of the new price oracle component
+        /// - `max_price_deviation`: Max relative difference between the new price and the last known price
+        pub fn migrate_pool_price_feed(
+            &mut self,
+            pool_res_address: ResourceAddress,
+            price_feed: Global<AnyComponent>,
+            max_price_deviation: Decimal,
+        ) {
+            assert!(
+                max_price_deviation >= Decimal::ZERO,
+                "Max price deviation must be non-negative"
+            );
+
+            let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+            let new_price = get_price(price_feed, pool_res_address)
+                .expect("Price not found")
+                .price;
+
+            let price_deviation = ((new_price - pool_state.price) / pool_state.price)
+                .checked_abs()
+                .unwrap();
+
+            assert!(
+                price_deviation <= max_price_deviation,
+                "New price {} deviates from the last known price {} by more than {}",
+                new_price, pool_state.price, max_price_deviation
+            );
+
+            pool_state.price_feed_comp = price_feed;
+
+            pool_state
+                .update_interest_and_price(Some((true, false)))
+                .expect("Error updating pool price");
+        }
+
+        /// Move the liquidity of a frozen or wound down pool to a new pool component. The pool must
+        /// have no CDP positions and no pending withdrawal request. The pool units of the replaced
+        /// component can be exchanged for the new ones with `migrate_pool_units`. The pool is listed
+        /// again with the frozen services left disabled, to be enabled by the admin
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        /// 
+        /// *Output*
+        /// The pool unit resource address of the new pool component
+        pub fn replace_pool_component(&mut self, pool_res_address: ResourceAddress) -> ResourceAddress {
+            assert!(
+                self.pool_cdp_indexes
+                    .get(&pool_res_address)
+                    .expect("Pool not found")
+                    .count == 0,
+                "The pool still has CDP positions"
+            );
+
+            let (new_pool, new_pool_unit_res_address) = self._instantiate_pool(pool_res_address);
+
+            let migration = {
+                let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+                assert!(
+                    matches!(
+                        pool_state.listing_status,
+                        ListingStatus::Frozen | ListingStatus::WindingDown { .. }
+                    ),
+                    "Only a frozen or wound down pool can be replaced"
+                );
+
+                assert!(
+                    pool_state.withdrawal_queue.locked_units.amount() == Decimal::ZERO,
+                    "The pool still has pending withdrawal requests"
+                );
+
+                pool_state.withdraw_reserve();
+
+                let (pooled_amount, external_liquidity_amount) = pool_state.pool.get_pooled_amount();
+
+                assert!(
+                    external_liquidity_amount == Decimal::ZERO,
+                    "The pool still has liquidity in use"
+                );
+
+                let replaced_unit_ratio = pool_state.pool.get_pool_unit_ratio();
+
+                let assets = pool_state.pool.protected_withdraw(
+                    pooled_amount,
+                    WithdrawType::LiquidityWithdrawal,
+                    WithdrawStrategy::Rounded(RoundingMode::ToZero),
+                );
+                let amount = assets.amount();
+
+                let pool_units = new_pool.contribute(assets);
+                let conversion_rate = new_pool.get_pool_unit_ratio() / replaced_unit_ratio;
+
+                pool_state.total_deposit = amount.into();
+                pool_state.total_deposit_unit = pool_units.amount().into();
+
+                pool_state.pool = new_pool;
+
+                let replaced_pool_units = std::mem::replace(
+                    &mut pool_state.collaterals,
+                    Vault::new(new_pool_unit_res_address),
+                );
+
+                let replaced_vaults = vec![
+                    std::mem::replace(
+                        &mut pool_state.reserve_pool_units,
+                        Vault::new(new_pool_unit_res_address),
+                    ),
+                    std::mem::replace(
+                        &mut pool_state.withdrawal_queue.locked_units,
+                        Vault::new(new_pool_unit_res_address),
+                    ),
+                ];
+
+                pool_state.deposit_reserve();
+
+                pool_state.listing_status = ListingStatus::Listed;
+
+                PoolUnitMigration {
+                    pool_res_address,
+                    conversion_rate,
+                    pool_units: Vault::with_bucket(pool_units),
+                    replaced_pool_units,
+                    replaced_vaults,
+                }
+            };
+
+            let replaced_pool_unit_res_address = *self
+                .pool_unit_refs
+                .get(&pool_res_address)
+                .expect("Pool unit not found");
+
+            self.pool_unit_migrations
+                .insert(replaced_pool_unit_res_address, migration);
+
+            self.reverse_pool_unit_refs
+                .shift_remove(&replaced_pool_unit_res_address);
+
+            self.reverse_pool_unit_refs
+                .insert(new_pool_unit_res_address, pool_res_address);
+
+            self.pool_unit_refs
+                .insert(pool_res_address, new_pool_unit_res_address);
+
+            new_pool_unit_res_address
+        }
+
+        /// Update the reserve policy of a pool. The reserve is deposited in the pool or withdrawn
+        /// from it according to the new policy
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address for which to change the reserve policy
+        /// - `min_buffer`: Amount of asset kept in the reserve by `collect_reserve`
+        /// - `auto_deposit`: Whether the reserve is deposited in the pool as pool units until collected
+        pub fn update_reserve_policy(
+            &mut self,
+            pool_res_address: ResourceAddress,
+            min_buffer: Decimal,
+            auto_deposit: bool,
+        ) {
+            assert!(min_buffer >= Decimal::ZERO, "Reserve buffer must not be negative");
+
+            let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+            pool_state.reserve_policy = ReservePolicy {
+                min_buffer,
+                auto_deposit,
+            };
+
+            if auto_deposit {
+                pool_state.deposit_reserve();
+            } else {
+                pool_state.withdraw_reserve();
+            }
+        }
+
+        /// Move reserve of a pool back into the pool liquidity, in order to cover bad debt or
+        /// rounding deficits
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        /// - `amount`: The amount of asset to move, capped to the reserve amount
+        /// 
+        /// *Output*
+        /// The amount moved into the pool liquidity
+        pub fn cover_shortfall(&mut self, pool_res_address: ResourceAddress, amount: Decimal) -> Decimal {
+            assert!(amount > Decimal::ZERO, "Amount must be positive");
+
+            let mut pool_state = self._get_pool_state(&pool_res_address, None, None);
+
+            pool_state.cover_shortfall(amount)
+        }
+
         /// Update pool state, recomputing price of the asset and accrued interest and allocating the collectible reserve
         /// 
         /// *Params*
@@ -512,6 +1030,46 @@ mod lending_market {
             pool_state.allocate_reserve();
         }
 
+        /// Re-read the risk-relevant roles of a listed asset (recaller, freezer, minter updater,
+        /// withdrawer and depositor). If any of them changed since the listing or the last confirmation,
+        /// borrow, add collateral, remove collateral and liquidation are disabled for the pool and an
+        /// `AssetRolesChangedEvent` is emitted. The change is reported until an admin confirms the new
+        /// roles with `confirm_asset_roles`. Meant to be called periodically by keeper bots
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        /// 
+        /// *Output*
+        /// The names of the changed roles, empty if none changed
+        pub fn audit_listed_asset(&mut self, pool_res_address: ResourceAddress) -> Vec<String> {
+            assert!(
+                self.listed_assets.contains(&pool_res_address),
+                "The lending pool is not listed"
+            );
+
+            self._get_pool_state_without_update(&pool_res_address, None)
+                .audit_asset_roles()
+                .expect("Error auditing asset roles")
+        }
+
+        /// Accept the current roles of a listed asset as the new baseline for `audit_listed_asset`.
+        /// The services disabled by the audit stay disabled until re-enabled with `admin_update_operating_status`
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        /// 
+        /// *Output*
+        /// The names of the roles that changed from the previous baseline
+        pub fn confirm_asset_roles(&mut self, pool_res_address: ResourceAddress) -> Vec<String> {
+            assert!(
+                self.listed_assets.contains(&pool_res_address),
+                "The lending pool is not listed"
+            );
+
+            self._get_pool_state_without_update(&pool_res_address, None)
+                .confirm_asset_roles()
+        }
+
         ///
         fn _update_operating_status(
             &mut self,
@@ -584,7 +1142,7 @@ mod lending_market {
                 let cdp_id = &NonFungibleLocalId::Integer(cdp_id.into());
                 // Logger::debug(format!("Search cdp {} exists= {}", cdp_id, self.cdp_res_manager.non_fungible_exists(cdp_id)));
                 if self.cdp_res_manager.non_fungible_exists(cdp_id) {
-                    let cdp_data = WrappedCDPData::new(&self.cdp_res_manager, cdp_id);
+                    let cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, cdp_id);
                     if !cdp_data.cdp_data.collaterals.is_empty() && !cdp_data.cdp_data.loans.is_empty() {
                         let mut cdp_health_checker = CDPHealthChecker::new_without_update(
                             &cdp_data,
@@ -606,53 +1164,106 @@ mod lending_market {
             results
         }
 
+        /// List the CDPs matching a filter, together with their up-to-date health data.
+        /// The method does not emit any event and is meant to be used as a read-only query
+        /// 
+        /// *Params*
+        /// - `filter`: The criteria the returned CDPs must match
+        /// - `cursor`: The first CDP id to scan, `None` to start from the first CDP
+        /// - `page_size`: The number of CDP ids to scan, matching or not
+        /// 
+        /// *Output*
+        /// The page of matching CDPs and the cursor of the next page
+        pub fn list_cdps(&self, filter: CDPFilter, cursor: Option<u64>, page_size: u64) -> CDPPage {
+            assert!(page_size > 0, "Page size must be greater than zero");
+
+            let start = cursor.unwrap_or(1).max(1);
+            let end = start.saturating_add(page_size).min(self.cdp_counter + 1);
+
+            let cdps = (start..end)
+                .filter_map(|cdp_id| self._get_cdp_health_data(&NonFungibleLocalId::Integer(cdp_id.into())))
+                .filter(|cdp_health_data| filter.matches(cdp_health_data))
+                .collect();
+
+            CDPPage {
+                cdps,
+                next_cursor: if end <= self.cdp_counter { Some(end) } else { None },
+                index_version: None,
+            }
+        }
+
+        /// List the CDPs with collaterals or loans in a pool, together with their up-to-date health data.
+        /// Allows to rescan only the CDPs affected by a price change.
+        /// Removing a CDP from the index moves the last indexed CDP into the freed slot, so a CDP can be
+        /// missed if the index changes between two pages. The page returns the version of the index: when
+        /// it differs from the version of the first page, the scan should be restarted.
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The resource address of the pool
+        /// - `cursor`: The position in the pool index to start from, `None` to start from the beginning
+        /// - `limit`: The maximum number of CDPs to return
+        /// 
+        /// *Output*
+        /// The page of CDPs and the cursor of the next page
+        pub fn list_cdps_by_pool(
+            &self,
+            pool_res_address: ResourceAddress,
+            cursor: Option<u64>,
+            limit: u64,
+        ) -> CDPPage {
+            let pool_cdp_index = self
+                .pool_cdp_indexes
+                .get(&pool_res_address)
+                .expect("Pool not found");
+
+            let (cdp_ids, next_cursor) = pool_cdp_index.list(cursor.unwrap_or(0), limit);
+
+            let cdps = cdp_ids
+                .iter()
+                .filter_map(|cdp_id| self._get_cdp_health_data(cdp_id))
+                .collect();
+
+            CDPPage {
+                cdps,
+                next_cursor,
+                index_version: Some(pool_cdp_index.version),
+            }
+        }
+
         /// Create a CDP
         /// 
         /// *Params*
-        /// - `name`: (UNUSED)
-        /// - `description`: (UNUSED)
-        /// - `key_image_url`: (UNUSED)
+        /// - `name`: Optional name of the CDP
+        /// - `description`: Optional description of the CDP
+        /// - `key_image_url`: Optional image of the CDP, rendered from the market template if not provided
         /// - `deposits``: The assets to put as collateral
         /// 
         /// *Output*
         /// - An NFT identifying the newly created CDP
         pub fn create_cdp(
             &mut self,
-            _name: Option<String>,
-            _description: Option<String>,
-            _key_image_url: Option<String>,
+            name: Option<String>,
+            description: Option<String>,
+            key_image_url: Option<String>,
             deposits: Vec<Bucket>,
         ) -> Bucket {
             if deposits.is_empty() {
                 panic!("INVALID_INPUT: creation of a CDP without deposits is not allowed")
             }
             
-            let cdp_id = NonFungibleLocalId::Integer(self._get_new_cdp_id().into());
+            let (cdp_id, cdp) = self._mint_cdp(name, description, key_image_url);
 
-            let now = Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch;
+            if !deposits.is_empty() {
+                let (positions, loan_to_value_ratio) =
+                    self._add_collateral_internal(cdp_id.clone(), deposits);
 
-            let data = CollaterizedDebtPositionData {
-                name: "".into(),
-                description: "".into(),
-                key_image_url: "".into(),
-                cdp_type: CDPType::Standard,
-                collaterals: IndexMap::new(),
-                loans: IndexMap::new(),
-                minted_at: now,
-                updated_at: now,
-                liquidable: None,
-            };
-
-            let cdp = self.cdp_res_manager.mint_non_fungible(&cdp_id, data);
-
-            if !deposits.is_empty() {
-                self._add_collateral_internal(cdp_id, deposits);
+                emit_cdp_event!(cdp_id, CDPUpdatedEvenType::AddCollateral, positions, loan_to_value_ratio);
             }
 
             cdp
         }
 
-        /// Update a CDP
+        /// Update the display data or the liquidation preference of a CDP
         /// 
         /// *Params*
         /// - `cdp_proof`: Proof of ownership of the CDP
@@ -679,13 +1290,24 @@ mod lending_market {
                         description,
                     );
                 }
-            }
+                UpdateCDPInput::LiquidationPreference(liquidation_preference) => {
+                    for pool_res_address in liquidation_preference.iter() {
+                        assert!(
+                            self.listed_assets.contains(pool_res_address),
+                            "INVALID_INPUT: {:?} is not a listed asset",
+                            pool_res_address
+                        );
+                    }
 
-            self.cdp_res_manager.update_non_fungible_data(
-                &cdp_id,
-                "updated_at",
-                Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch,
-            );
+                    let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+                    cdp_data
+                        .set_liquidation_preference(liquidation_preference)
+                        .expect("Invalid liquidation preference");
+
+                    save_cdp_macro!(self, cdp_data);
+                }
+            }
         }
 
         /// Shows a CDP
@@ -700,13 +1322,253 @@ mod lending_market {
         pub fn show_cdp(&self, cdp_id: u64) -> Option<WrappedCDPData> {
             let cdp_id = &NonFungibleLocalId::Integer(cdp_id.into());
             if self.cdp_res_manager.non_fungible_exists(cdp_id) {
-                let cdp_data = WrappedCDPData::new(&self.cdp_res_manager, cdp_id);
+                let cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, cdp_id);
                 Some(cdp_data)
             } else {
                 None
             }
         }
 
+        /// Burn one or more empty CDPs, i.e. CDPs having no collaterals and no loans
+        /// 
+        /// *Params*
+        /// - `cdp`: The CDPs to burn
+        pub fn burn_cdp(&mut self, cdp: Bucket) {
+            assert!(
+                cdp.resource_address() == self.cdp_res_manager.address(),
+                "INVALID_INPUT: the bucket does not contain CDPs"
+            );
+
+            for cdp_id in cdp.as_non_fungible().non_fungible_local_ids() {
+                let cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+                assert!(
+                    cdp_data.is_empty(),
+                    "CDP {} still has collaterals or loans",
+                    cdp_id
+                );
+
+                self.cdp_positions.remove(&cdp_id);
+
+                emit_cdp_event!(cdp_id, CDPUpdatedEvenType::Burn, vec![], Decimal::ZERO);
+            }
+
+            self.cdp_res_manager.burn(cdp);
+        }
+
+        /// Move the positions of CDPs minted before the positions were stored in the market
+        /// from their NFT data into the market storage. CDPs already migrated are skipped.
+        /// Not migrated CDPs are also migrated on their next update
+        /// 
+        /// *Params*
+        /// - `cdp_ids`: The ids of the CDPs to migrate
+        /// 
+        /// *Output*
+        /// The number of migrated CDPs
+        pub fn migrate_cdp_positions(&mut self, cdp_ids: Vec<NonFungibleLocalId>) -> u64 {
+            cdp_ids.iter().fold(0, |migrated, cdp_id| {
+                let cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, cdp_id);
+
+                if cdp_data.migrate_positions(
+                    &self.cdp_res_manager,
+                    &self.cdp_positions,
+                    &self.pool_cdp_indexes,
+                ) {
+                    migrated + 1
+                } else {
+                    migrated
+                }
+            })
+        }
+
+        /// Merge several CDPs into a single one. Collaterals and loans of the merged CDPs
+        /// are moved into the target CDP, which must be healthy after the merge and must not
+        /// exceed the configured max amount of positions. The merged CDPs are burnt, hence they
+        /// must be passed as a bucket rather than as proofs.
+        /// 
+        /// *Params*
+        /// - `cdp_proof`: Proof of ownership of the CDP receiving the positions
+        /// - `cdps`: The CDPs to merge into the target one
+        pub fn merge_cdps(&mut self, cdp_proof: Proof, cdps: Bucket) {
+            self._check_operating_status(OperatingService::AddCollateral);
+
+            let cdp_id = self._validate_cdp_proof(cdp_proof);
+
+            assert!(
+                cdps.resource_address() == self.cdp_res_manager.address(),
+                "INVALID_INPUT: the bucket does not contain CDPs"
+            );
+
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+            let mut pool_context = PoolContext::new();
+
+            let mut positions = Vec::new();
+
+            for merged_cdp_id in cdps.as_non_fungible().non_fungible_local_ids() {
+                assert!(
+                    merged_cdp_id != cdp_id,
+                    "INVALID_INPUT: a CDP cannot be merged into itself"
+                );
+
+                let merged_cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &merged_cdp_id);
+
+                cdp_data
+                    .merge(&merged_cdp_data)
+                    .expect("Error merging CDP");
+
+                merged_cdp_data.remove_from_pool_cdp_indexes(&self.pool_cdp_indexes);
+
+                self.cdp_positions.remove(&merged_cdp_id);
+
+                let merged_positions = self._get_position_deltas(&merged_cdp_data, false);
+
+                positions.extend(self._get_position_deltas(&merged_cdp_data, true));
+
+                emit_cdp_event!(
+                    merged_cdp_id,
+                    CDPUpdatedEvenType::Burn,
+                    merged_positions,
+                    Decimal::ZERO
+                );
+            }
+
+            let checked_loan_to_value_ratio = CDPHealthChecker::new(
+                &cdp_data,
+                &mut self.pool_states,
+                &mut pool_context,
+            )
+            .check_cdp()
+            .expect("Error checking CDP");
+
+            let loan_to_value_ratio =
+                save_cdp_macro!(self, cdp_data, Some(checked_loan_to_value_ratio));
+
+            self.cdp_res_manager.burn(cdps);
+
+            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::Merge, positions, loan_to_value_ratio);
+        }
+
+        /// Split a CDP, moving the selected positions into a newly minted CDP.
+        /// Both CDPs must be healthy after the split and must not exceed the configured max
+        /// amount of positions.
+        /// 
+        /// *Params*
+        /// - `cdp_proof`: Proof of ownership of the CDP to split
+        /// - `collaterals`: List of tuples indicating
+        ///   - The resource address
+        ///   - The amount of collateral units to move
+        /// - `loans`: List of tuples indicating
+        ///   - The resource address
+        ///   - The amount of loan units to move
+        /// 
+        /// *Output*
+        /// - An NFT identifying the newly created CDP
+        pub fn split_cdp(
+            &mut self,
+            cdp_proof: Proof,
+            collaterals: Vec<(ResourceAddress, Decimal)>,
+            loans: Vec<(ResourceAddress, Decimal)>,
+        ) -> Bucket {
+            self._check_operating_status(OperatingService::RemoveCollateral);
+
+            let cdp_id = self._validate_cdp_proof(cdp_proof);
+
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+            let (new_cdp_id, new_cdp) = self._mint_cdp(None, None, None);
+
+            let mut new_cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &new_cdp_id);
+
+            let mut pool_context = PoolContext::new();
+
+            for (pool_res_address, unit_amount) in collaterals {
+                let units: PreciseDecimal = unit_amount.into();
+
+                assert!(
+                    units > PreciseDecimal::ZERO
+                        && units <= cdp_data.get_collateral_units(pool_res_address),
+                    "INVALID_INPUT: collateral units to move must be positive and at most the CDP units"
+                );
+
+                let use_as_collateral = cdp_data.is_used_as_collateral(pool_res_address);
+
+                cdp_data
+                    .update_collateral(pool_res_address, -units)
+                    .expect("Error updating collateral for CDP");
+
+                new_cdp_data
+                    .update_collateral(pool_res_address, units)
+                    .expect("Error updating collateral for CDP");
+
+                if !use_as_collateral {
+                    new_cdp_data
+                        .set_use_as_collateral(pool_res_address, false)
+                        .expect("Error updating collateral usage for CDP");
+                }
+            }
+
+            for (pool_res_address, unit_amount) in loans {
+                let units: PreciseDecimal = unit_amount.into();
+
+                assert!(
+                    units > PreciseDecimal::ZERO
+                        && units <= cdp_data.get_loan_units(pool_res_address),
+                    "INVALID_INPUT: loan units to move must be positive and at most the CDP units"
+                );
+
+                cdp_data
+                    .update_loan(pool_res_address, -units)
+                    .expect("Error updating loan");
+
+                new_cdp_data
+                    .update_loan(pool_res_address, units)
+                    .expect("Error updating loan");
+            }
+
+            assert!(
+                !new_cdp_data.is_empty(),
+                "INVALID_INPUT: no position to move into the new CDP"
+            );
+
+            let checked_loan_to_value_ratio = CDPHealthChecker::new(
+                &cdp_data,
+                &mut self.pool_states,
+                &mut pool_context,
+            )
+            .check_cdp()
+            .expect("Error checking CDP");
+
+            let checked_new_loan_to_value_ratio = CDPHealthChecker::new(
+                &new_cdp_data,
+                &mut self.pool_states,
+                &mut pool_context,
+            )
+            .check_cdp()
+            .expect("Error checking new CDP");
+
+            let loan_to_value_ratio =
+                save_cdp_macro!(self, cdp_data, Some(checked_loan_to_value_ratio));
+            let new_loan_to_value_ratio =
+                save_cdp_macro!(self, new_cdp_data, Some(checked_new_loan_to_value_ratio));
+
+            emit_cdp_event!(
+                cdp_id,
+                CDPUpdatedEvenType::Split,
+                self._get_position_deltas(&new_cdp_data, false),
+                loan_to_value_ratio
+            );
+
+            emit_cdp_event!(
+                new_cdp_id,
+                CDPUpdatedEvenType::Split,
+                self._get_position_deltas(&new_cdp_data, true),
+                new_loan_to_value_ratio
+            );
+
+            new_cdp
+        }
+
         /* Lending and Borrowing methods */
 
         /// Contribute assets to the lending market
@@ -749,6 +1611,164 @@ mod lending_market {
                 .redeem_proxy(pool_units, false)
         }
 
+        /// Redeem as much assets as the available liquidity of the pool allows
+        /// 
+        /// *Params*
+        /// - `pool_units`: Pool units to return
+        /// 
+        /// *Output*
+        /// - Assets equivalent of the redeemed pool units
+        /// - Pool units which could not be redeemed
+        pub fn redeem_partial(&mut self, pool_units: Bucket) -> (Bucket, Bucket) {
+            self._check_operating_status(OperatingService::Redeem);
+
+            let pool_res_address = *self
+                .reverse_pool_unit_refs
+                .get(&pool_units.resource_address())
+                .expect("Pool unit not found");
+
+            self._get_pool_state(&pool_res_address, Some(OperatingService::Redeem), None)
+                .redeem_partial_proxy(pool_units)
+        }
+
+        /// Lock pool units in the withdrawal queue of the pool. Queued requests are paid in
+        /// first-come-first-served order from the incoming repayments. The value of the locked
+        /// pool units must be at least the market minimum withdrawal request value
+        /// 
+        /// *Params*
+        /// - `pool_units`: Pool units to redeem
+        /// 
+        /// *Output*
+        /// - The withdrawal request, used to claim the redeemed assets
+        pub fn request_withdrawal(&mut self, pool_units: Bucket) -> Bucket {
+            self._check_operating_status(OperatingService::Redeem);
+
+            let pool_res_address = *self
+                .reverse_pool_unit_refs
+                .get(&pool_units.resource_address())
+                .expect("Pool unit not found");
+
+            self.withdrawal_request_counter += 1;
+            let request_id = self.withdrawal_request_counter;
+
+            let request_data = WithdrawalRequestData {
+                pool_res_address,
+                pool_units: pool_units.amount(),
+                requested_at: Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch,
+            };
+
+            let min_withdrawal_request_value = self.market_config.min_withdrawal_request_value;
+
+            {
+                let mut pool_state =
+                    self._get_pool_state(&pool_res_address, Some(OperatingService::Redeem), None);
+
+                let request_value = (pool_units.amount() / pool_state.pool.get_pool_unit_ratio())
+                    .checked_truncate(RoundingMode::ToNearestMidpointToEven)
+                    .unwrap()
+                    * pool_state.price;
+
+                assert!(
+                    request_value >= min_withdrawal_request_value,
+                    "INVALID_INPUT: withdrawal request value {} is lower than the minimum {}",
+                    request_value,
+                    min_withdrawal_request_value
+                );
+
+                pool_state
+                    .withdrawal_queue
+                    .push(request_id, pool_units)
+                    .expect("Error queuing withdrawal request");
+
+                pool_state.process_withdrawal_queue();
+            }
+
+            self.withdrawal_request_res_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(request_id),
+                request_data,
+            )
+        }
+
+        /// Claim the assets redeemed for a withdrawal request. The request is burnt once fully paid
+        /// 
+        /// *Params*
+        /// - `withdrawal_request`: The withdrawal request
+        /// 
+        /// *Output*
+        /// - The redeemed assets
+        /// - The withdrawal request if not fully paid yet
+        pub fn claim_withdrawal(&mut self, withdrawal_request: Bucket) -> (Bucket, Option<Bucket>) {
+            let (request_id, request_data) = self._validate_withdrawal_request(&withdrawal_request);
+
+            let (assets, fully_paid) = self
+                ._get_pool_state_without_update(&request_data.pool_res_address, None)
+                .withdrawal_queue
+                .claim(request_id)
+                .expect("Error claiming withdrawal request");
+
+            if fully_paid {
+                self.withdrawal_request_res_manager.burn(withdrawal_request);
+                (assets, None)
+            } else {
+                (assets, Some(withdrawal_request))
+            }
+        }
+
+        /// Cancel a withdrawal request, burning it
+        /// 
+        /// *Params*
+        /// - `withdrawal_request`: The withdrawal request
+        /// 
+        /// *Output*
+        /// - The assets already redeemed for the request
+        /// - The pool units not redeemed yet
+        pub fn cancel_withdrawal(&mut self, withdrawal_request: Bucket) -> (Bucket, Bucket) {
+            let (request_id, request_data) = self._validate_withdrawal_request(&withdrawal_request);
+
+            let result = self
+                ._get_pool_state_without_update(&request_data.pool_res_address, None)
+                .withdrawal_queue
+                .cancel(request_id)
+                .expect("Error cancelling withdrawal request");
+
+            self.withdrawal_request_res_manager.burn(withdrawal_request);
+
+            result
+        }
+
+        /// Exchange the pool units of a replaced pool component for the pool units of the current one,
+        /// see `replace_pool_component`
+        /// 
+        /// *Params*
+        /// - `pool_units`: The pool units of the replaced pool component
+        /// 
+        /// *Output*
+        /// - The pool units of the current pool component
+        pub fn migrate_pool_units(&mut self, mut pool_units: Bucket) -> Bucket {
+            assert!(
+                self.pool_unit_migrations
+                    .get(&pool_units.resource_address())
+                    .is_some(),
+                "INVALID_INPUT: No migration found for the provided pool units"
+            );
+
+            // A pool component can be replaced several times, the units are migrated up to the current one
+            while let Some(mut migration) =
+                self.pool_unit_migrations.get_mut(&pool_units.resource_address())
+            {
+                let amount = (pool_units.amount() * migration.conversion_rate)
+                    .checked_truncate(RoundingMode::ToZero)
+                    .unwrap()
+                    .min(migration.pool_units.amount());
+
+                migration.replaced_pool_units.put(pool_units);
+
+                pool_units = migration.pool_units.take(amount);
+            }
+
+            pool_units
+        }
+
         /// Add collateral 
         /// 
         /// *Params*
@@ -757,9 +1777,10 @@ mod lending_market {
         pub fn add_collateral(&mut self, cdp_proof: Proof, deposits: Vec<Bucket>) {
             let cdp_id = self._validate_cdp_proof(cdp_proof);
 
-            self._add_collateral_internal(cdp_id.clone(), deposits);
+            let (positions, loan_to_value_ratio) =
+                self._add_collateral_internal(cdp_id.clone(), deposits);
 
-            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::AddCollateral);
+            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::AddCollateral, positions, loan_to_value_ratio);
         }
 
         /// Remove collateral 
@@ -782,15 +1803,17 @@ mod lending_market {
 
             let cdp_id = self._validate_cdp_proof(cdp_proof);
 
-            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &cdp_id);
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
 
-            let withdrawals = withdraw_details.into_iter().fold(
-                Vec::new(),
-                |mut withdrawals, (pool_res_address, unit_amount, keep_deposit_unit)| {
-                    let mut pool_state = self._get_pool_state(
+            let mut pool_context = PoolContext::new();
+
+            let (withdrawals, positions) = withdraw_details.into_iter().fold(
+                (Vec::new(), Vec::new()),
+                |(mut withdrawals, mut positions), (pool_res_address, unit_amount, keep_deposit_unit)| {
+                    let mut pool_state = self._get_pool_state_in_context(
                         &pool_res_address,
                         Some(OperatingService::RemoveCollateral),
-                        None,
+                        &mut pool_context,
                     );
 
                     let current_deposit_units = cdp_data.get_collateral_units(pool_res_address);
@@ -801,6 +1824,8 @@ mod lending_market {
                         .update_collateral(pool_res_address, -withdraw_collateral_units)
                         .expect("Error updating collateral for CDP");
 
+                    positions.push(CDPPositionDelta::collateral(&pool_state, -withdraw_collateral_units));
+
                     let deposit_units = pool_state
                         .remove_pool_units_from_collateral(withdraw_collateral_units)
                         .expect("Error redeeming pool units from collateral");
@@ -813,24 +1838,77 @@ mod lending_market {
 
                     withdrawals.push(returned_assets);
 
-                    withdrawals
+                    (withdrawals, positions)
                 },
             );
 
-            CDPHealthChecker::new(
+            let checked_loan_to_value_ratio = CDPHealthChecker::new(
                 &cdp_data,
                 &mut self.pool_states,
+                &mut pool_context,
             )
             .check_cdp()
             .expect("Error checking CDP");
 
-            save_cdp_macro!(self, cdp_data);
+            let loan_to_value_ratio =
+                save_cdp_macro!(self, cdp_data, Some(checked_loan_to_value_ratio));
 
-            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::RemoveCollateral);
+            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::RemoveCollateral, positions, loan_to_value_ratio);
 
             withdrawals
         }
 
+        /// Enable or disable a deposited asset as collateral. A disabled asset keeps earning
+        /// supply yield but neither counts toward the borrowing power nor can be seized in
+        /// liquidation. The CDP must be healthy after the asset is disabled
+        /// 
+        /// *Params*
+        /// - `cdp_proof`: Proof of ownership of the CDP
+        /// - `pool_res_address`: The resource address of the deposited asset
+        /// - `use_as_collateral`: Whether the asset is used as collateral
+        pub fn update_collateral_usage(
+            &mut self,
+            cdp_proof: Proof,
+            pool_res_address: ResourceAddress,
+            use_as_collateral: bool,
+        ) {
+            let cdp_id = self._validate_cdp_proof(cdp_proof);
+
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+            if use_as_collateral {
+                self._check_operating_status(OperatingService::AddCollateral);
+
+                self._get_pool_state_without_update(
+                    &pool_res_address,
+                    Some(OperatingService::AddCollateral),
+                );
+            }
+
+            cdp_data
+                .set_use_as_collateral(pool_res_address, use_as_collateral)
+                .expect("Error updating collateral usage for CDP");
+
+            // The LTV ratio is only checked when a collateral is disabled
+            let checked_loan_to_value_ratio = if !use_as_collateral {
+                Some(
+                    CDPHealthChecker::new(
+                        &cdp_data,
+                        &mut self.pool_states,
+                        &mut PoolContext::new(),
+                    )
+                    .check_cdp()
+                    .expect("Error checking CDP"),
+                )
+            } else {
+                None
+            };
+
+            let loan_to_value_ratio = save_cdp_macro!(self, cdp_data, checked_loan_to_value_ratio);
+
+            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::CollateralUsage, vec![], loan_to_value_ratio);
+        }
+
         /// Borrow assets from the market
         /// 
         /// *Params*
@@ -847,16 +1925,18 @@ mod lending_market {
 
             let cdp_id = self._validate_cdp_proof(cdp_proof);
 
-            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &cdp_id);
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+            let mut pool_context = PoolContext::new();
 
-            let loans =
+            let (loans, positions) =
                 borrows
                     .into_iter()
-                    .fold(Vec::new(), |mut loans, (pool_res_address, amount)| {
-                        let mut pool_state = self._get_pool_state(
+                    .fold((Vec::new(), Vec::new()), |(mut loans, mut positions), (pool_res_address, amount)| {
+                        let mut pool_state = self._get_pool_state_in_context(
                             &pool_res_address,
                             Some(OperatingService::Borrow),
-                            None,
+                            &mut pool_context,
                         );
 
                         let (borrowed_assets, delta_loan_units) = pool_state
@@ -867,21 +1947,28 @@ mod lending_market {
                             .update_loan(pool_res_address, delta_loan_units.into())
                             .expect("Error updating loan");
 
+                        positions.push(
+                            CDPPositionDelta::loan(&pool_state, delta_loan_units.into())
+                                .expect("Error computing loan delta"),
+                        );
+
                         loans.push(borrowed_assets);
 
-                        loans
+                        (loans, positions)
                     });
 
-            CDPHealthChecker::new(
+            let checked_loan_to_value_ratio = CDPHealthChecker::new(
                 &cdp_data,
                 &mut self.pool_states,
+                &mut pool_context,
             )
             .check_cdp()
             .expect("Error checking CDP");
 
-            save_cdp_macro!(self, cdp_data);
+            let loan_to_value_ratio =
+                save_cdp_macro!(self, cdp_data, Some(checked_loan_to_value_ratio));
 
-            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::Borrow);
+            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::Borrow, positions, loan_to_value_ratio);
 
             loans
         }
@@ -906,20 +1993,28 @@ mod lending_market {
 
             let cdp_id = self._validate_cdp_proof(cdp_proof);
 
-            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &cdp_id);
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
 
             if cdp_data.cdp_data.collaterals.is_empty() {
                 panic!("Position was liquidated");
             }
 
-            let (remainders, payment_value) = self._repay_internal(
-                &mut cdp_data,
-                payments,
-                None,
-                false,
-            );
+            let mut remainders = payments;
+
+            let (payment_value, repayments) = self
+                ._repay_internal(&mut cdp_data, &mut remainders, None, None, false)
+                .expect("Error repaying the loans");
+
+            let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
 
-            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::Repay);
+            emit_cdp_event!(cdp_id.clone(), CDPUpdatedEvenType::Repay, repayments.clone(), loan_to_value_ratio);
+
+            Runtime::emit_event(RepayEvent {
+                cdp_id,
+                repayments,
+                total_payment_value: payment_value,
+                loan_to_value_ratio,
+            });
 
             (remainders, payment_value)
         }
@@ -943,48 +2038,36 @@ mod lending_market {
             requested_collaterals: Vec<ResourceAddress>,
             total_payment_value: Option<Decimal>,
         ) -> (Vec<Bucket>, Bucket) {
-            self._check_operating_status(OperatingService::Liquidation);
-
-            if let Some(total_payment_value) = total_payment_value {
-                assert!(
-                    total_payment_value >= 0.into(),
-                    "INVALID_INPUT: Total payment value must be non-negative"
-                );
-            }
-
-            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &cdp_id);
+            self._start_liquidation(cdp_id, requested_collaterals, total_payment_value, None)
+        }
 
-            let is_within_minute = Clock::current_time_is_strictly_before(Instant::new(cdp_data.cdp_data.updated_at).add_minutes(1).unwrap(), TimePrecision::Second);
-            if !is_within_minute {
-                panic!("cdp info is too old.");
-            }
-            let self_closable_loan_value = match cdp_data.cdp_data.liquidable {
-                Some(self_closable_loan_value) => self_closable_loan_value,
-                None => panic!("The cdp is not liquidable.")
-            };
-
-            let temp_total_payment_value = total_payment_value
-                .unwrap_or(self_closable_loan_value)
-                .min(self_closable_loan_value);
-
-            let (returned_collaterals, total_payement_value) = self
-                ._remove_collateral_for_liquidation(
-                    &mut cdp_data,
-                    requested_collaterals,
-                    temp_total_payment_value,
-                    false,
-                );
-
-            let liquidation_term =
-                self.transient_res_manager
-                    .mint_ruid_non_fungible(TransientResData {
-                        data: TransientResDataType::LiquidationTerm(LiquidationTerm {
-                            cdp_id,
-                            payement_value: total_payement_value,
-                        }),
-                    });
+        /// Same as `start_liquidation`, identifying the liquidator in the `LiquidationEvent`
+        /// 
+        /// *Params*
+        /// - `cdp_id`: Id of the CDP to liquidate
+        /// - `requested_collaterals`: The collaterals to liquidate
+        /// - `total_payment_value`: The value to repay in order to bring the CDP back into 
+        ///   an healthy state
+        /// - `liquidator_badge_proof`: Proof of the liquidator badge
+        /// 
+        /// *Output*
+        /// - The liquidated colaterals
+        /// - The NFT to terminate the transaction
+        pub fn start_liquidation_with_badge(
+            &mut self,
+            cdp_id: NonFungibleLocalId,
+            requested_collaterals: Vec<ResourceAddress>,
+            total_payment_value: Option<Decimal>,
+            liquidator_badge_proof: Proof,
+        ) -> (Vec<Bucket>, Bucket) {
+            let liquidator_badge_id = self._validate_liquidator_badge_proof(liquidator_badge_proof);
 
-            (returned_collaterals, liquidation_term)
+            self._start_liquidation(
+                cdp_id,
+                requested_collaterals,
+                total_payment_value,
+                Some(liquidator_badge_id),
+            )
         }
 
         /// Terminates a liquidation started in the same transaction as `start_liquidation`
@@ -1014,14 +2097,19 @@ mod lending_market {
 
             let cdp_id = liquidation_term_data.cdp_id;
 
-            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &cdp_id);
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
 
-            let (remainders, total_payment_value) = self._repay_internal(
-                &mut cdp_data,
-                payments,
-                Some(liquidation_term_data.payement_value),
-                true,
-            );
+            let mut remainders = payments;
+
+            let (total_payment_value, repayments) = self
+                ._repay_internal(
+                    &mut cdp_data,
+                    &mut remainders,
+                    Some(liquidation_term_data.payement_value),
+                    None,
+                    true,
+                )
+                .expect("Error repaying the loans");
 
             assert!(
                 (total_payment_value - liquidation_term_data.payement_value).checked_abs().unwrap() < ZERO_EPSILON,
@@ -1039,9 +2127,16 @@ mod lending_market {
 
             cdp_data.on_liquidation().expect("perform cdp liquidation tasks");
 
-            save_cdp_macro!(self, cdp_data);
+            let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
 
-            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::Liquidate);
+            self._emit_liquidation_events(
+                cdp_id,
+                liquidation_term_data.liquidator_badge_id,
+                repayments,
+                liquidation_term_data.seized_collaterals,
+                total_payment_value,
+                loan_to_value_ratio,
+            );
 
             (remainders, total_payment_value)
         }
@@ -1061,6 +2156,9 @@ mod lending_market {
         /// call to start liquidation and will determine the exact liquidable amount.
         /// If more than a minute is elapsed between the call to `check_cdp_for_liquidation` and 
         /// the call to `start_liquidation`, the information is considered obsolete.
+        /// When a liquidation grace period is configured, a CDP with a health factor between
+        /// the hard liquidation health factor and 1 is first marked at risk, and can only be
+        /// liquidated once the grace period is over.
         /// 
         /// *Params*
         /// - `cdp_id`: The id of the CDP to check
@@ -1068,14 +2166,30 @@ mod lending_market {
         /// *Output*
         /// - Whether the CDP is liquidable or not
         pub fn check_cdp_for_liquidation(&mut self, cdp_id: NonFungibleLocalId) -> bool {
-            let mut cdp_data: WrappedCDPData = WrappedCDPData::new(&self.cdp_res_manager, &cdp_id);
+            let mut cdp_data: WrappedCDPData = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
 
             let mut cdp_health_checker = CDPHealthChecker::new(
                 &cdp_data,
                 &mut self.pool_states,
+                &mut PoolContext::new(),
             );
 
-            let can_liquidate = cdp_health_checker.can_liquidate().is_ok();
+            cdp_health_checker
+                .check_price_validity()
+                .expect("Liquidation check not allowed");
+
+            let can_liquidate = cdp_health_checker.can_liquidate().is_ok()
+                && !self._apply_liquidation_grace_period(
+                    &mut cdp_data,
+                    cdp_health_checker.total_loan_to_value_ratio,
+                );
+
+            if can_liquidate {
+                cdp_health_checker
+                    .update_liquidable_value(&self.market_config)
+                    .expect("Error computing the liquidable value");
+            }
+
             match cdp_data.cdp_data.liquidable {
                 Some(_) => {
                     if can_liquidate {
@@ -1094,6 +2208,181 @@ mod lending_market {
             can_liquidate
         }
 
+        /// Quotes the liquidation of a CDP, computed from the current pool states without
+        /// updating them. The quote replays the repayment and the seizure steps of the
+        /// liquidation, so that liquidators can build the exact liquidation manifest
+        /// 
+        /// *Params*
+        /// - `cdp_id`: The id of the CDP to liquidate
+        /// - `repay_assets`: The loans to repay, in order
+        /// - `seize_order`: The collaterals to seize, in order
+        /// 
+        /// *Output*
+        /// The liquidation quote
+        pub fn quote_liquidation(
+            &self,
+            cdp_id: NonFungibleLocalId,
+            repay_assets: Vec<ResourceAddress>,
+            seize_order: Vec<ResourceAddress>,
+        ) -> LiquidationQuote {
+            assert!(
+                self.cdp_res_manager.non_fungible_exists(&cdp_id),
+                "INVALID_INPUT: CDP {} not found",
+                cdp_id
+            );
+
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+            let mut cdp_health_checker =
+                CDPHealthChecker::new_without_update(&cdp_data, &self.pool_states);
+
+            cdp_health_checker
+                .update_health_check_data()
+                .expect(&format!("Error updating health check data for cdp {}", cdp_id));
+
+            cdp_health_checker
+                .check_price_validity()
+                .expect("Liquidation not allowed");
+
+            let loan_to_value_ratio = cdp_health_checker.total_loan_to_value_ratio;
+
+            assert!(
+                !self._is_in_liquidation_grace_period(&cdp_data, loan_to_value_ratio),
+                "Liquidation not allowed: the CDP is in its liquidation grace period"
+            );
+
+            // Repayment of the loans, limited by the loan close factor when no target health
+            // factor is set, and in total by the liquidable value of the CDP as the liquidation does
+            let target_health_liquidation = self.market_config.target_health_factor.is_some();
+            let mut remaining_liquidable_value = cdp_data.cdp_data.liquidable.unwrap_or(dec!(0));
+
+            let mut repayments: Vec<LiquidationQuoteRepayment> = Vec::new();
+            let mut total_payment_value = Decimal::ZERO;
+
+            for pool_res_address in repay_assets {
+                let pool_state = self
+                    .pool_states
+                    .get(&pool_res_address)
+                    .expect("INVALID_INPUT: asset not listed");
+
+                let loan_unit_ratio = pool_state
+                    .get_loan_unit_ratio()
+                    .expect("Error getting loan unit ratio for provided resource");
+
+                let (_, pool_borrowed_amount) = pool_state.pool.get_pooled_amount();
+
+                let mut max_loan_amount = cdp_data.get_loan_units(pool_res_address) / loan_unit_ratio;
+
+                if !target_health_liquidation && !pool_state.is_wound_down() {
+                    max_loan_amount *= pool_state.pool_config.loan_close_factor;
+                }
+
+                let mut max_loan_value = (max_loan_amount * pool_state.price)
+                    .min((pool_borrowed_amount * pool_state.price).into())
+                    .checked_truncate(RoundingMode::ToNearestMidpointToEven)
+                    .unwrap();
+
+                max_loan_value = max_loan_value.min(remaining_liquidable_value);
+                remaining_liquidable_value -= max_loan_value;
+
+                let repay_amount = max_loan_value / pool_state.price;
+
+                cdp_data
+                    .update_loan(pool_res_address, -(repay_amount * loan_unit_ratio))
+                    .expect("Error updating loan");
+
+                repayments.push(LiquidationQuoteRepayment {
+                    pool_res_address,
+                    amount: repay_amount,
+                    value: max_loan_value,
+                });
+
+                total_payment_value += max_loan_value;
+            }
+
+            // Seizure of the collaterals, with the liquidation bonus and the protocol fee
+            let mut seizures: Vec<LiquidationQuoteSeizure> = Vec::new();
+            let mut total_seized_value = Decimal::ZERO;
+            let mut protocol_fee_value = Decimal::ZERO;
+            let mut remaining_value = total_payment_value;
+
+            for pool_res_address in seize_order {
+                if remaining_value == Decimal::ZERO {
+                    break;
+                }
+
+                if !cdp_data.cdp_data.collaterals.contains_key(&pool_res_address)
+                    || !cdp_data.is_used_as_collateral(pool_res_address)
+                {
+                    continue;
+                }
+
+                cdp_data
+                    .check_liquidation_preference(pool_res_address)
+                    .expect("Invalid collateral seizure order");
+
+                let pool_state = self
+                    .pool_states
+                    .get(&pool_res_address)
+                    .expect("INVALID_INPUT: asset not listed");
+
+                let bonus_rate = dec!(1) + pool_state.pool_config.liquidation_bonus_rate;
+
+                let unit_ratio = pool_state.pool.get_pool_unit_ratio();
+
+                let max_collateral_amount = (cdp_data.get_collateral_units(pool_res_address) / unit_ratio)
+                    .checked_truncate(RoundingMode::ToNearestMidpointToEven)
+                    .unwrap();
+
+                let collateral_value =
+                    (max_collateral_amount * pool_state.price).min(bonus_rate * remaining_value);
+
+                remaining_value = (remaining_value - collateral_value / bonus_rate).max(Decimal::ZERO);
+
+                let collateral_amount = collateral_value / pool_state.price;
+
+                cdp_data
+                    .update_collateral(pool_res_address, -(collateral_amount * unit_ratio))
+                    .expect("Error updating collateral for CDP");
+
+                let protocol_fee_amount =
+                    collateral_amount * pool_state.pool_config.protocol_liquidation_fee_rate;
+
+                let amount = collateral_amount - protocol_fee_amount;
+
+                total_seized_value += amount * pool_state.price;
+                protocol_fee_value += protocol_fee_amount * pool_state.price;
+
+                seizures.push(LiquidationQuoteSeizure {
+                    pool_res_address,
+                    amount,
+                    value: amount * pool_state.price,
+                    bonus_value: collateral_value - collateral_value / bonus_rate,
+                    protocol_fee_amount,
+                });
+            }
+
+            let mut post_liquidation_health_checker =
+                CDPHealthChecker::new_without_update(&cdp_data, &self.pool_states);
+
+            post_liquidation_health_checker
+                .update_health_check_data()
+                .expect(&format!("Error updating health check data for cdp {}", cdp_id));
+
+            LiquidationQuote {
+                cdp_id,
+                repayments,
+                seizures,
+                total_payment_value,
+                total_seized_value,
+                protocol_fee_value,
+                uncovered_payment_value: remaining_value,
+                loan_to_value_ratio,
+                post_liquidation_loan_to_value_ratio: post_liquidation_health_checker
+                    .total_loan_to_value_ratio,
+            }
+        }
+
         /// Allows to liquidate a CDP in a single call. This requires to anticipate the payments
         /// of the loans, since collaterals will be returned at the end
         /// 
@@ -1112,44 +2401,93 @@ mod lending_market {
             payments: Vec<Bucket>,
             requested_collaterals: Vec<ResourceAddress>,
         ) -> (Vec<Bucket>, Vec<Bucket>, Decimal) {
-            self._check_operating_status(OperatingService::Liquidation);
-
-            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &cdp_id);
-
-            let is_within_minute = Clock::current_time_is_strictly_before(Instant::new(cdp_data.cdp_data.updated_at).add_minutes(1).unwrap(), TimePrecision::Second);
-            if cdp_data.cdp_data.liquidable.is_some() && !is_within_minute {
-                panic!("cdp info is too old");
-            } else if cdp_data.cdp_data.liquidable.is_none() && !is_within_minute {
-                panic!("The cdp is not liquidable and cdp info is too old.")
-            } else if cdp_data.cdp_data.liquidable.is_none() {
-                panic!("The cdp is not liquidable.")
-            }
-
-            let (remainders, total_payment_value) =
-                self._repay_internal(&mut cdp_data, payments, None, true);
+            self._fast_liquidation(cdp_id, payments, requested_collaterals, None)
+        }
 
-            let (returned_collaterals, _total_payement_value) = self
-                ._remove_collateral_for_liquidation(
-                    &mut cdp_data,
-                    requested_collaterals,
-                    total_payment_value,
-                    true
-                );
+        /// Same as `fast_liquidation`, identifying the liquidator in the `LiquidationEvent`
+        /// 
+        /// *Params*
+        /// - `cdp_id`: The id of the CDP to liquidate
+        /// - `payments`: The payments for the loans
+        /// - `requested_collaterals`: The collaterals to return
+        /// - `liquidator_badge_proof`: Proof of the liquidator badge
+        /// 
+        /// *Output*
+        /// - Payments remainders
+        /// - Collaterals
+        /// - Total payment value
+        pub fn fast_liquidation_with_badge(
+            &mut self,
+            cdp_id: NonFungibleLocalId,
+            payments: Vec<Bucket>,
+            requested_collaterals: Vec<ResourceAddress>,
+            liquidator_badge_proof: Proof,
+        ) -> (Vec<Bucket>, Vec<Bucket>, Decimal) {
+            let liquidator_badge_id = self._validate_liquidator_badge_proof(liquidator_badge_proof);
 
-            cdp_data.on_liquidation().expect("perform cdp liquidation tasks");
+            self._fast_liquidation(
+                cdp_id,
+                payments,
+                requested_collaterals,
+                Some(liquidator_badge_id),
+            )
+        }
 
-            save_cdp_macro!(self, cdp_data);
+        /// Liquidates several CDPs in a single call, in the listed order. The health of each CDP
+        /// is checked again with pool states refreshed once for the whole batch, so that CDPs
+        /// that are not liquidable anymore are skipped instead of making the call fail.
+        /// CDPs that cannot be processed, like unknown CDPs, CDPs with a stale price or whose
+        /// requested collaterals cannot be seized, are skipped as well and the error is reported
+        /// in their result. Payments are shared among the CDPs: the remainders of a CDP repayment
+        /// are used for the following ones
+        /// 
+        /// *Params*
+        /// - `cdps`: List of tuples indicating
+        ///   - The id of the CDP to liquidate
+        ///   - The collaterals to return for the CDP
+        /// - `payments`: The payments for the loans of all the CDPs
+        /// 
+        /// *Output*
+        /// - Payments remainders
+        /// - Collaterals, one bucket per resource
+        /// - The outcome of the liquidation of each CDP
+        pub fn batch_fast_liquidation(
+            &mut self,
+            cdps: Vec<(NonFungibleLocalId, Vec<ResourceAddress>)>,
+            payments: Vec<Bucket>,
+        ) -> (Vec<Bucket>, Vec<Bucket>, Vec<BatchLiquidationResult>) {
+            self._batch_fast_liquidation(cdps, payments, None)
+        }
 
-            emit_cdp_event!(cdp_id, CDPUpdatedEvenType::Liquidate);
+        /// Same as `batch_fast_liquidation`, identifying the liquidator in the `LiquidationEvent`
+        /// of each liquidated CDP
+        /// 
+        /// *Params*
+        /// - `cdps`: List of tuples indicating
+        ///   - The id of the CDP to liquidate
+        ///   - The collaterals to return for the CDP
+        /// - `payments`: The payments for the loans of all the CDPs
+        /// - `liquidator_badge_proof`: Proof of the liquidator badge
+        /// 
+        /// *Output*
+        /// - Payments remainders
+        /// - Collaterals, one bucket per resource
+        /// - The outcome of the liquidation of each CDP
+        pub fn batch_fast_liquidation_with_badge(
+            &mut self,
+            cdps: Vec<(NonFungibleLocalId, Vec<ResourceAddress>)>,
+            payments: Vec<Bucket>,
+            liquidator_badge_proof: Proof,
+        ) -> (Vec<Bucket>, Vec<Bucket>, Vec<BatchLiquidationResult>) {
+            let liquidator_badge_id = self._validate_liquidator_badge_proof(liquidator_badge_proof);
 
-            (remainders, returned_collaterals, total_payment_value)
+            self._batch_fast_liquidation(cdps, payments, Some(liquidator_badge_id))
         }
 
         /*  PUBLIC QUERIES   */
 
         /// Getter of the statistics of all the pools in the market
         pub fn list_info_stats(&self) -> MarketStatsAllPools {
-            let second_per_year = 31536000;
             let mut total_supply_all_pools = Decimal::zero();
             let mut total_borrow_all_pools = Decimal::zero();
 
@@ -1159,21 +2497,8 @@ mod lending_market {
                 .map(|asset| {
                     let pool_state_ref = self.pool_states.get(&asset).unwrap();
                     let pool_state = pool_state_ref;
-                    let utilization_rate = pool_state.get_pool_utilization();
-
-                    let deposit_rate =
-                        utilization_rate *
-                        pool_state.interest_rate *
-                        (1 - pool_state.pool_config.protocol_interest_fee_rate);
 
-                    let supply_apy_term: PreciseDecimal = PreciseDecimal::ONE + deposit_rate / second_per_year;
-                    let supply_apy =
-                        supply_apy_term.checked_powi(second_per_year).unwrap() - dec!(1);
-
-                    let borrow_apy_term: PreciseDecimal =
-                        pdec!(1) + pool_state.interest_rate / second_per_year;
-                    let borrow_apy =
-                        borrow_apy_term.checked_powi(second_per_year).unwrap() - dec!(1);
+                    let (supply_apy, borrow_apy) = pool_state.get_supply_and_borrow_apy();
 
                     let pooled_amount = pool_state.pool.get_pooled_amount();
                     let available_liquidity = pooled_amount.0;
@@ -1202,7 +2527,7 @@ mod lending_market {
                         borrow_limit: pool_state.pool_config.borrow_limit,
                         utilization_limit: pool_state.pool_config.utilization_limit,
                         optimal_usage: pool_state.pool_config.optimal_usage,
-                        ltv_limit: pool_state.liquidation_threshold.default_value,
+                        ltv_limit: pool_state.get_liquidation_threshold().default_value,
                     }
                 })
                 .collect::<Vec<MarketStatsPool>>();
@@ -1215,14 +2540,169 @@ mod lending_market {
             market_total_stats
         }
 
+        /// Getter of a consolidated view of the positions of an account, computed from the current
+        /// pool states without updating them
+        /// 
+        /// *Params*
+        /// - `cdp_ids`: The CDPs held by the account
+        /// - `pool_unit_holdings`: List of tuples indicating
+        ///   - The pool unit resource address
+        ///   - The amount of pool units held outside of the CDPs
+        /// 
+        /// *Output*
+        /// The account summary
+        pub fn get_account_summary(
+            &self,
+            cdp_ids: Vec<NonFungibleLocalId>,
+            pool_unit_holdings: Vec<(ResourceAddress, Decimal)>,
+        ) -> AccountSummary {
+            let mut free_supplied_value = Decimal::ZERO;
+            let mut collateral_value = Decimal::ZERO;
+            let mut total_borrowed_value = Decimal::ZERO;
+            let mut supply_earnings = PreciseDecimal::ZERO;
+            let mut borrow_costs = PreciseDecimal::ZERO;
+            let mut max_loan_to_value_ratio = Decimal::ZERO;
+
+            for (pool_unit_res_address, pool_unit_amount) in pool_unit_holdings {
+                let pool_res_address = *self
+                    .reverse_pool_unit_refs
+                    .get(&pool_unit_res_address)
+                    .expect("INVALID_INPUT: not a pool unit of the market");
+
+                let pool_state = self.pool_states.get(&pool_res_address).unwrap();
+
+                let amount = (pool_unit_amount / pool_state.pool.get_pool_unit_ratio())
+                    .checked_truncate(RoundingMode::ToNearestMidpointToEven)
+                    .unwrap();
+                let value = amount * pool_state.price;
+
+                free_supplied_value += value;
+                supply_earnings += pool_state.get_supply_and_borrow_apy().0 * value;
+            }
+
+            for cdp_id in cdp_ids {
+                assert!(
+                    self.cdp_res_manager.non_fungible_exists(&cdp_id),
+                    "INVALID_INPUT: CDP {} not found",
+                    cdp_id
+                );
+
+                let cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+                let mut cdp_health_checker =
+                    CDPHealthChecker::new_without_update(&cdp_data, &self.pool_states);
+
+                cdp_health_checker
+                    .update_health_check_data()
+                    .expect(&format!("Error updating health check data for cdp {}", cdp_id));
+
+                for (pool_res_address, position) in cdp_health_checker.collateral_positions.iter() {
+                    let pool_state = self.pool_states.get(pool_res_address).unwrap();
+                    collateral_value += position.data.value;
+                    supply_earnings += pool_state.get_supply_and_borrow_apy().0 * position.data.value;
+                }
+
+                for (pool_res_address, position) in cdp_health_checker.loan_positions.iter() {
+                    let pool_state = self.pool_states.get(pool_res_address).unwrap();
+                    total_borrowed_value += position.data.value;
+                    borrow_costs += pool_state.get_supply_and_borrow_apy().1 * position.data.value;
+                }
+
+                max_loan_to_value_ratio =
+                    max_loan_to_value_ratio.max(cdp_health_checker.total_loan_to_value_ratio);
+            }
+
+            let total_supplied_value = free_supplied_value + collateral_value;
+            let net_supplied_value = total_supplied_value - total_borrowed_value;
+
+            let weighted_apy = |earnings: PreciseDecimal, value: Decimal| {
+                if value > Decimal::ZERO { earnings / value } else { PreciseDecimal::ZERO }
+            };
+
+            AccountSummary {
+                free_supplied_value,
+                collateral_value,
+                total_supplied_value,
+                total_borrowed_value,
+                supply_apy: weighted_apy(supply_earnings, total_supplied_value),
+                borrow_apy: weighted_apy(borrow_costs, total_borrowed_value),
+                net_apy: weighted_apy(supply_earnings - borrow_costs, net_supplied_value),
+                max_loan_to_value_ratio,
+                health_band: CDPHealthBand::from_ltv(max_loan_to_value_ratio, &self.market_config),
+            }
+        }
+
+        /// Getter of the snapshots of a pool, recorded at each interest update and kept for
+        /// `HISTORY_MAX_BUCKETS` buckets of `HISTORY_BUCKET_PERIOD` seconds
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        /// - `from`: The start timestamp, inclusive
+        /// - `to`: The end timestamp, inclusive
+        /// 
+        /// *Output*
+        /// The snapshots, ordered by timestamp
+        pub fn get_pool_history(
+            &self,
+            pool_res_address: ResourceAddress,
+            from: i64,
+            to: i64,
+        ) -> Vec<PoolSnapshot> {
+            let pool_state = self.pool_states.get(&pool_res_address).expect("Pool not found");
+
+            pool_state.history.get(from, to)
+        }
+
+        /// Getter of the cumulative protocol revenue of a pool, by source. The revenue still to
+        /// collect is the difference between the total revenue and the collected revenue
+        /// 
+        /// *Params*
+        /// - `pool_res_address`: The pool resource address
+        pub fn get_pool_revenue(&self, pool_res_address: ResourceAddress) -> PoolRevenue {
+            self.pool_states
+                .get(&pool_res_address)
+                .expect("Pool not found")
+                .revenue
+                .clone()
+        }
+
+        /// Getter of the price of a pool unit (rtToken), in the same shape as the price feed `get_price`
+        /// method, allowing pool units to be priced by any component consuming the oracle interface.
+        /// The price is computed from the stored pool state, the caller decides whether its
+        /// timestamp is recent enough
+        /// 
+        /// *Params*
+        /// - `pool_unit_res_address`: The pool unit resource address
+        /// 
+        /// *Output*
+        /// The `PriceInfo` of the pool unit, `None` if the resource is not a pool unit of the market
+        pub fn get_pool_unit_price(&self, pool_unit_res_address: ResourceAddress) -> Option<PriceInfo> {
+            let pool_res_address = *self.reverse_pool_unit_refs.get(&pool_unit_res_address)?;
+
+            let price_info = self
+                .pool_states
+                .get(&pool_res_address)
+                .expect("Pool not found")
+                .get_pool_unit_price()
+                .expect("Error getting pool unit price");
+
+            Some(price_info)
+        }
+
         /*  PRIVATE UTILITY METHODS */
 
-        fn _add_collateral_internal(&mut self, cdp_id: NonFungibleLocalId, deposits: Vec<Bucket>) {
+        fn _add_collateral_internal(
+            &mut self,
+            cdp_id: NonFungibleLocalId,
+            deposits: Vec<Bucket>,
+        ) -> (Vec<CDPPositionDelta>, Decimal) {
             self._check_operating_status(OperatingService::AddCollateral);
 
-            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &cdp_id);
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
 
-            deposits.into_iter().fold((), |_, assets| {
+            let mut pool_context = PoolContext::new();
+
+            let positions = deposits.into_iter().fold(Vec::new(), |mut positions, assets| {
                 let res_address = assets.resource_address();
 
                 let value = self.pool_unit_refs.get(&res_address);
@@ -1236,10 +2716,10 @@ mod lending_market {
                     )
                 };
 
-                let mut pool_state = self._get_pool_state(
+                let mut pool_state = self._get_pool_state_in_context(
                     &pool_res_address,
                     Some(OperatingService::AddCollateral),
-                    None,
+                    &mut pool_context,
                 );
 
                 let deposit_units = if res_address == pool_unit_res_address {
@@ -1254,25 +2734,347 @@ mod lending_market {
                     .update_collateral(pool_res_address, deposit_units.amount().into())
                     .expect("Error updating collateral for CDP");
 
+                positions.push(CDPPositionDelta::collateral(
+                    &pool_state,
+                    deposit_units.amount().into(),
+                ));
+
                 pool_state
                     .add_pool_units_as_collateral(deposit_units)
                     .expect("Error adding pool units as collateral");
+
+                positions
             });
 
-            save_cdp_macro!(self, cdp_data);
+            let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
+
+            (positions, loan_to_value_ratio)
         }
 
-        fn _remove_collateral_for_liquidation(
+        fn _start_liquidation(
             &mut self,
-            cdp_data: &mut WrappedCDPData,
+            cdp_id: NonFungibleLocalId,
             requested_collaterals: Vec<ResourceAddress>,
-            requested_collaterals_value: Decimal,
-            check_requested_collaterals: bool
-        ) -> (Vec<Bucket>, Decimal) {
-            let mut returned_collaterals: Vec<Bucket> = Vec::new();
-            let mut returned_collaterals_value = dec!(0);
-
-            let mut temp_requested_value = requested_collaterals_value;
+            total_payment_value: Option<Decimal>,
+            liquidator_badge_id: Option<NonFungibleLocalId>,
+        ) -> (Vec<Bucket>, Bucket) {
+            self._check_operating_status(OperatingService::Liquidation);
+
+            if let Some(total_payment_value) = total_payment_value {
+                assert!(
+                    total_payment_value >= 0.into(),
+                    "INVALID_INPUT: Total payment value must be non-negative"
+                );
+            }
+
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+            self._check_cdp_price_validity(&cdp_data)
+                .expect("Liquidation not allowed");
+
+            let is_within_minute = Clock::current_time_is_strictly_before(Instant::new(cdp_data.cdp_data.updated_at).add_minutes(1).unwrap(), TimePrecision::Second);
+            if !is_within_minute {
+                panic!("cdp info is too old.");
+            }
+            let self_closable_loan_value = match cdp_data.cdp_data.liquidable {
+                Some(self_closable_loan_value) => self_closable_loan_value,
+                None => panic!("The cdp is not liquidable.")
+            };
+
+            let temp_total_payment_value = total_payment_value
+                .unwrap_or(self_closable_loan_value)
+                .min(self_closable_loan_value);
+
+            let (returned_collaterals, total_payement_value, seized_collaterals) = self
+                ._remove_collateral_for_liquidation(
+                    &mut cdp_data,
+                    requested_collaterals,
+                    temp_total_payment_value,
+                    false,
+                )
+                .expect("Error seizing the collaterals");
+
+            let liquidation_term =
+                self.transient_res_manager
+                    .mint_ruid_non_fungible(TransientResData {
+                        data: TransientResDataType::LiquidationTerm(LiquidationTerm {
+                            cdp_id,
+                            payement_value: total_payement_value,
+                            liquidator_badge_id,
+                            seized_collaterals,
+                        }),
+                    });
+
+            (returned_collaterals, liquidation_term)
+        }
+
+        fn _fast_liquidation(
+            &mut self,
+            cdp_id: NonFungibleLocalId,
+            payments: Vec<Bucket>,
+            requested_collaterals: Vec<ResourceAddress>,
+            liquidator_badge_id: Option<NonFungibleLocalId>,
+        ) -> (Vec<Bucket>, Vec<Bucket>, Decimal) {
+            self._check_operating_status(OperatingService::Liquidation);
+
+            let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+            self._check_cdp_price_validity(&cdp_data)
+                .expect("Liquidation not allowed");
+
+            let is_within_minute = Clock::current_time_is_strictly_before(Instant::new(cdp_data.cdp_data.updated_at).add_minutes(1).unwrap(), TimePrecision::Second);
+            if cdp_data.cdp_data.liquidable.is_some() && !is_within_minute {
+                panic!("cdp info is too old");
+            } else if cdp_data.cdp_data.liquidable.is_none() && !is_within_minute {
+                panic!("The cdp is not liquidable and cdp info is too old.")
+            } else if cdp_data.cdp_data.liquidable.is_none() {
+                panic!("The cdp is not liquidable.")
+            }
+
+            let mut remainders = payments;
+
+            let (total_payment_value, repayments) = self
+                ._repay_internal(&mut cdp_data, &mut remainders, None, None, true)
+                .expect("Error repaying the loans");
+
+            let (returned_collaterals, _total_payement_value, seized_collaterals) = self
+                ._remove_collateral_for_liquidation(
+                    &mut cdp_data,
+                    requested_collaterals,
+                    total_payment_value,
+                    true
+                )
+                .expect("Error seizing the collaterals");
+
+            cdp_data.on_liquidation().expect("perform cdp liquidation tasks");
+
+            let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
+
+            self._emit_liquidation_events(
+                cdp_id,
+                liquidator_badge_id,
+                repayments,
+                seized_collaterals,
+                total_payment_value,
+                loan_to_value_ratio,
+            );
+
+            (remainders, returned_collaterals, total_payment_value)
+        }
+
+        fn _batch_fast_liquidation(
+            &mut self,
+            cdps: Vec<(NonFungibleLocalId, Vec<ResourceAddress>)>,
+            payments: Vec<Bucket>,
+            liquidator_badge_id: Option<NonFungibleLocalId>,
+        ) -> (Vec<Bucket>, Vec<Bucket>, Vec<BatchLiquidationResult>) {
+            self._check_operating_status(OperatingService::Liquidation);
+
+            let mut pool_context = PoolContext::new();
+
+            let mut remaining_payments: IndexMap<ResourceAddress, Bucket> = IndexMap::new();
+            for payment in payments {
+                Self::_put_in_bucket_map(&mut remaining_payments, payment);
+            }
+
+            let mut returned_collaterals: IndexMap<ResourceAddress, Bucket> = IndexMap::new();
+            let mut results: Vec<BatchLiquidationResult> = Vec::new();
+
+            for (cdp_id, requested_collaterals) in cdps {
+                if !self.cdp_res_manager.non_fungible_exists(&cdp_id) {
+                    results.push(BatchLiquidationResult::skipped(
+                        cdp_id.clone(),
+                        Decimal::ZERO,
+                        Some(format!("CDP {} not found", cdp_id)),
+                    ));
+
+                    continue;
+                }
+
+                let mut cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, &cdp_id);
+
+                let health_check = CDPHealthChecker::try_new(
+                    &cdp_data,
+                    &mut self.pool_states,
+                    &mut pool_context,
+                )
+                .and_then(|cdp_health_checker| {
+                    cdp_health_checker.check_price_validity()?;
+                    Ok(cdp_health_checker)
+                });
+
+                let mut cdp_health_checker = match health_check {
+                    Ok(cdp_health_checker) => cdp_health_checker,
+                    Err(error) => {
+                        results.push(BatchLiquidationResult::skipped(cdp_id, Decimal::ZERO, Some(error)));
+
+                        continue;
+                    }
+                };
+
+                let can_liquidate = cdp_health_checker.can_liquidate().is_ok()
+                    && !self._apply_liquidation_grace_period(
+                        &mut cdp_data,
+                        cdp_health_checker.total_loan_to_value_ratio,
+                    );
+
+                // The liquidable value, the loan pools and the requested collaterals are checked
+                // before any repayment, a failing CDP is skipped with its error
+                let seizable_value = if can_liquidate {
+                    cdp_health_checker
+                        .update_liquidable_value(&self.market_config)
+                        .and_then(|_| {
+                            self._check_batch_liquidation(
+                                &cdp_data,
+                                &requested_collaterals,
+                                &remaining_payments,
+                            )
+                        })
+                } else {
+                    Ok(Decimal::ZERO)
+                };
+
+                cdp_data.cdp_data.liquidable = if can_liquidate {
+                    Some(cdp_health_checker.self_closable_loan_value)
+                } else {
+                    None
+                };
+
+                let seizable_value = match seizable_value {
+                    Ok(seizable_value) => seizable_value,
+                    Err(error) => {
+                        let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
+
+                        results.push(BatchLiquidationResult::skipped(cdp_id, loan_to_value_ratio, Some(error)));
+
+                        continue;
+                    }
+                };
+
+                let cdp_payments: Vec<Bucket> = if can_liquidate {
+                    cdp_data
+                        .cdp_data
+                        .loans
+                        .keys()
+                        .filter_map(|pool_res_address| remaining_payments.swap_remove(pool_res_address))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                if cdp_payments.is_empty() {
+                    let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
+
+                    results.push(BatchLiquidationResult::skipped(cdp_id, loan_to_value_ratio, None));
+
+                    continue;
+                }
+
+                // The repayment is capped by the value of the requested collaterals, so that the
+                // seizure cannot run short of collaterals
+                let mut cdp_payments = cdp_payments;
+
+                let repayment = self._repay_internal(
+                    &mut cdp_data,
+                    &mut cdp_payments,
+                    None,
+                    Some(seizable_value),
+                    true,
+                );
+
+                for remainder in cdp_payments {
+                    Self::_put_in_bucket_map(&mut remaining_payments, remainder);
+                }
+
+                // A failing repayment has not updated the CDP nor the pools
+                let (total_payment_value, repayments) = match repayment {
+                    Ok(repayment) => repayment,
+                    Err(error) => {
+                        let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
+
+                        results.push(BatchLiquidationResult::skipped(cdp_id, loan_to_value_ratio, Some(error)));
+
+                        continue;
+                    }
+                };
+
+                let seizure = self._remove_collateral_for_liquidation(
+                    &mut cdp_data,
+                    requested_collaterals,
+                    total_payment_value,
+                    false
+                );
+
+                // A failing seizure has not updated the collaterals, the repaid loans are saved
+                // so that the CDP stays consistent with the pools
+                let (collaterals, _total_payement_value, seized_collaterals) = match seizure {
+                    Ok(seizure) => seizure,
+                    Err(error) => {
+                        let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
+
+                        emit_cdp_event!(cdp_id.clone(), CDPUpdatedEvenType::Repay, repayments, loan_to_value_ratio);
+
+                        results.push(BatchLiquidationResult {
+                            cdp_id,
+                            liquidated: false,
+                            total_payment_value,
+                            loan_to_value_ratio,
+                            error: Some(error),
+                        });
+
+                        continue;
+                    }
+                };
+
+                for collateral in collaterals {
+                    Self::_put_in_bucket_map(&mut returned_collaterals, collateral);
+                }
+
+                cdp_data.on_liquidation().expect("perform cdp liquidation tasks");
+
+                let loan_to_value_ratio = save_cdp_macro!(self, cdp_data);
+
+                self._emit_liquidation_events(
+                    cdp_id.clone(),
+                    liquidator_badge_id.clone(),
+                    repayments,
+                    seized_collaterals,
+                    total_payment_value,
+                    loan_to_value_ratio,
+                );
+
+                results.push(BatchLiquidationResult {
+                    cdp_id,
+                    liquidated: true,
+                    total_payment_value,
+                    loan_to_value_ratio,
+                    error: None,
+                });
+            }
+
+            (
+                remaining_payments.into_values().collect(),
+                returned_collaterals.into_values().collect(),
+                results,
+            )
+        }
+
+        fn _remove_collateral_for_liquidation(
+            &mut self,
+            cdp_data: &mut WrappedCDPData,
+            requested_collaterals: Vec<ResourceAddress>,
+            requested_collaterals_value: Decimal,
+            check_requested_collaterals: bool
+        ) -> Result<(Vec<Bucket>, Decimal, Vec<SeizedCollateral>), String> {
+            // All the requested collaterals are checked before any seizure, so that an error
+            // leaves the CDP and the pools unchanged
+            self._get_seizable_value(cdp_data, &requested_collaterals)?;
+
+            let mut returned_collaterals: Vec<Bucket> = Vec::new();
+            let mut returned_collaterals_value = dec!(0);
+            let mut seized_collaterals: Vec<SeizedCollateral> = Vec::new();
+
+            let mut temp_requested_value = requested_collaterals_value;
 
             for pool_res_address in requested_collaterals {
                 // Make sure that that each requested collateral will have a bucket in the worktop
@@ -1281,10 +3083,12 @@ mod lending_market {
                     break;
                 }
 
-                let mut pool_state =  self._get_pool_state_without_update(
-                    &pool_res_address,
-                    Some(OperatingService::Liquidation)
-                );
+                // Deposited assets not used as collateral cannot be seized
+                if !cdp_data.is_used_as_collateral(pool_res_address) {
+                    continue;
+                }
+
+                let mut pool_state = self._get_pool_state_without_update(&pool_res_address, None);
 
                 let bonus_rate = dec!(1) + pool_state.pool_config.liquidation_bonus_rate;
 
@@ -1310,13 +3114,11 @@ mod lending_market {
 
                 let collateral_units = (max_collateral_value / pool_state.price) * unit_ratio;
 
-                cdp_data
-                    .update_collateral(pool_res_address, -collateral_units)
-                    .expect("Error updating collateral for CDP");
+                cdp_data.update_collateral(pool_res_address, -collateral_units)?;
 
-                let pool_unit = pool_state
-                    .remove_pool_units_from_collateral(collateral_units)
-                    .expect("Error redeeming pool units from collateral");
+                let position = CDPPositionDelta::collateral(&pool_state, -collateral_units);
+
+                let pool_unit = pool_state.remove_pool_units_from_collateral(collateral_units)?;
 
                 let mut collaterals = pool_state.redeem_proxy(pool_unit, true);
                 let collateral_amount = collaterals.amount();
@@ -1325,58 +3127,195 @@ mod lending_market {
                 
                 returned_collaterals_value = returned_collaterals_value * (dec!(1) - protocol_fee_amount/collateral_amount);
 
-                pool_state.reserve.put(collaterals.take_advanced(
+                let protocol_fee = collaterals.take_advanced(
                     protocol_fee_amount,
                     WithdrawStrategy::Rounded(RoundingMode::ToNearestMidpointToEven),
-                ));
+                );
+
+                pool_state.revenue.record(RevenueSource::Liquidation, protocol_fee.amount());
+
+                pool_state.reserve.put(protocol_fee);
+                pool_state.deposit_reserve();
+
+                seized_collaterals.push(SeizedCollateral {
+                    position,
+                    bonus_value: max_collateral_value - max_collateral_value / bonus_rate,
+                    protocol_fee_amount,
+                });
 
                 returned_collaterals.push(collaterals);
             }
 
-            if check_requested_collaterals {
-                assert!(
-                    temp_requested_value == dec!(0),
+            if check_requested_collaterals && temp_requested_value != dec!(0) {
+                return Err(format!(
                     "Insufficient collateral value, {} remaining",
                     temp_requested_value
-                );
+                ));
             }
             save_cdp_macro!(self, cdp_data);
 
-            (returned_collaterals, returned_collaterals_value)
+            Ok((returned_collaterals, returned_collaterals_value, seized_collaterals))
+        }
+
+        /// Check a CDP of a batch liquidation before any repayment: the loan pools having a
+        /// payment must allow the liquidation, and the requested collaterals must be seizable
+        ///
+        /// *Output*
+        /// The value of the loans the requested collaterals can repay
+        fn _check_batch_liquidation(
+            &self,
+            cdp_data: &WrappedCDPData,
+            requested_collaterals: &[ResourceAddress],
+            payments: &IndexMap<ResourceAddress, Bucket>,
+        ) -> Result<Decimal, String> {
+            for pool_res_address in cdp_data.cdp_data.loans.keys() {
+                if !payments.contains_key(pool_res_address) {
+                    continue;
+                }
+
+                let pool_state = self
+                    .pool_states
+                    .get(pool_res_address)
+                    .ok_or(format!("Pool {:?} not found", pool_res_address))?;
+
+                pool_state.check_operating_status(OperatingService::Liquidation)?;
+
+                pool_state.check_price_validity()?;
+            }
+
+            let seizable_value = self._get_seizable_value(cdp_data, requested_collaterals)?;
+
+            if seizable_value == Decimal::ZERO {
+                return Err("The requested collaterals cannot repay any loan".to_string());
+            }
+
+            Ok(seizable_value)
+        }
+
+        /// Check that no collateral or loan pool of a CDP has a stale price, so that neither the
+        /// repaid loans nor the seized collaterals of a liquidation are valued with an obsolete price
+        ///
+        /// *Error*
+        /// - If the price of a pool of the CDP is stale
+        fn _check_cdp_price_validity(&self, cdp_data: &WrappedCDPData) -> Result<(), String> {
+            for pool_res_address in cdp_data
+                .cdp_data
+                .collaterals
+                .keys()
+                .chain(cdp_data.cdp_data.loans.keys())
+            {
+                self.pool_states
+                    .get(pool_res_address)
+                    .ok_or(format!("Pool {:?} not found", pool_res_address))?
+                    .check_price_validity()?;
+            }
+
+            Ok(())
+        }
+
+        /// Value of the loans the requested collaterals can repay once the liquidation bonus is
+        /// deducted, without updating any state
+        ///
+        /// *Error*
+        /// - If a requested collateral cannot be seized: liquidation preference, operating
+        /// status of its pool or stale price
+        fn _get_seizable_value(
+            &self,
+            cdp_data: &WrappedCDPData,
+            requested_collaterals: &[ResourceAddress],
+        ) -> Result<Decimal, String> {
+            let mut seizable_value = Decimal::ZERO;
+
+            for pool_res_address in requested_collaterals {
+                if !cdp_data.is_used_as_collateral(*pool_res_address) {
+                    continue;
+                }
+
+                cdp_data.check_liquidation_preference(*pool_res_address)?;
+
+                let pool_state = self
+                    .pool_states
+                    .get(pool_res_address)
+                    .ok_or(format!("Pool {:?} not found", pool_res_address))?;
+
+                pool_state.check_operating_status(OperatingService::Liquidation)?;
+
+                pool_state.check_price_validity()?;
+
+                let bonus_rate = dec!(1) + pool_state.pool_config.liquidation_bonus_rate;
+
+                let collateral_amount = (cdp_data.get_collateral_units(*pool_res_address)
+                    / pool_state.pool.get_pool_unit_ratio())
+                .checked_truncate(RoundingMode::ToZero)
+                .ok_or("Error computing the collateral amount")?;
+
+                seizable_value += collateral_amount * pool_state.price / bonus_rate;
+            }
+
+            Ok(seizable_value)
         }
 
+        /// Repay the loans of a CDP with the provided payments, the repaid amounts are taken from
+        /// the payments which are left to the caller with their remaining amounts
+        ///
+        /// *Output*
+        /// - The total value repaid
+        /// - The repaid loan positions
+        ///
+        /// *Error*
+        /// - If a payment pool does not allow the repayment or the liquidation, or has a stale
+        /// price for a liquidation. These checks are done before any repayment, so that a failing
+        /// CDP of a batch liquidation can be skipped without losing the payments
+        /// - If the expected payment value is not repaid
         fn _repay_internal(
             &mut self,
             cdp_data: &mut WrappedCDPData,
-            payments: Vec<Bucket>,
+            payments: &mut [Bucket],
             payment_value: Option<Decimal>,
+            max_payment_value: Option<Decimal>,
             for_liquidation: bool
-        ) -> (Vec<Bucket>, Decimal) {
+        ) -> Result<(Decimal, Vec<CDPPositionDelta>), String> {
+            for payment in payments.iter() {
+                let pool_state = self
+                    .pool_states
+                    .get(&payment.resource_address())
+                    .ok_or(format!("Pool {:?} not found", payment.resource_address()))?;
+
+                // ! Liquidation
+                if for_liquidation {
+                    pool_state.check_operating_status(OperatingService::Liquidation)?;
+
+                    pool_state.check_price_validity()?;
+
+                // ! Repay
+                } else {
+                    pool_state.check_operating_status(OperatingService::Repay)?;
+                }
+            }
+
             let mut expected_payment_value = payment_value.unwrap_or(dec!(0));
+            let mut remaining_max_payment_value = max_payment_value.unwrap_or(Decimal::MAX);
+
+            let mut total_payment_value = Decimal::zero();
+            let mut repayments = Vec::new();
+            let mut pool_context = PoolContext::new();
+
+            // ! Liquidation: with a target health factor, the total repayment is capped by the
+            // liquidable value of the CDP instead of the loan close factor of each loan
+            let target_health_liquidation =
+                for_liquidation && self.market_config.target_health_factor.is_some();
+            let mut remaining_liquidable_value = cdp_data.cdp_data.liquidable.unwrap_or(dec!(0));
 
-            let (mut remainders, mut total_payment_value) = (Vec::new(), Decimal::zero());
-            for mut payment in payments {
+            for payment in payments.iter_mut() {
                 let pool_res_address = payment.resource_address();
 
                 let mut pool_state = if for_liquidation {
                     self._get_pool_state_without_update(&pool_res_address, None)
                 } else {
-                    self._get_pool_state(&pool_res_address, None, None)
+                    self._get_pool_state_in_context(&pool_res_address, None, &mut pool_context)
                 };
 
-                // ! Liquidation
-                if for_liquidation {
-                    pool_state
-                        .check_operating_status(OperatingService::Liquidation)
-                        .expect("Liquidation is not enabled for the pool");
-
-                // ! Repay
-                } else {
-                    pool_state
-                        .check_operating_status(OperatingService::Repay)
-                        .expect("Borrow is not enabled for the pool");
-                }
-
+                // The pools are updated from here, an error can only be reverted with the transaction
                 let loan_unit_ratio = pool_state
                     .get_loan_unit_ratio()
                     .expect("Error getting loan unit ratio for provided resource");
@@ -1387,8 +3326,8 @@ mod lending_market {
 
                 let mut max_loan_amount = position_loan_units / loan_unit_ratio;
 
-                // ! Liquidation
-                if for_liquidation {
+                // ! Liquidation: the loans of wound down pools can be fully repaid
+                if for_liquidation && !target_health_liquidation && !pool_state.is_wound_down() {
                     max_loan_amount *= pool_state.pool_config.loan_close_factor;
                 }
 
@@ -1399,6 +3338,19 @@ mod lending_market {
                     .checked_truncate(RoundingMode::ToNearestMidpointToEven)
                     .unwrap();
 
+                // ! Liquidation
+                if target_health_liquidation {
+                    max_loan_value = max_loan_value.min(remaining_liquidable_value);
+                    remaining_liquidable_value -= max_loan_value;
+                }
+
+                // ! Liquidation: the repayment is capped, unlike the expected payment value
+                // which must be fully repaid
+                if max_payment_value.is_some() {
+                    max_loan_value = max_loan_value.min(remaining_max_payment_value);
+                    remaining_max_payment_value -= max_loan_value;
+                }
+
                 // ! Liquidation
                 if payment_value.is_some() {
                     max_loan_value = max_loan_value.min(expected_payment_value.into());
@@ -1425,26 +3377,26 @@ mod lending_market {
                     .update_loan(pool_res_address, -delta_loan_unit)
                     .expect("Error updating loan");
 
-                remainders.push(payment);
+                repayments.push(
+                    CDPPositionDelta::loan(&pool_state, -delta_loan_unit)
+                        .expect("Error computing loan delta"),
+                );
 
                 total_payment_value += max_loan_value;
             };
 
             if let Some(value) = payment_value {
-                assert!(
-                    expected_payment_value < ZERO_EPSILON.max(total_payment_value * (1 - self.market_config.liquidation_dex_swap_rate)),
-                    "Insufficient payment value, {} required, {} total, {} remaining to pay",
-                    value,
-                    total_payment_value,
-                    expected_payment_value
-                );
-            }
-
-            if !for_liquidation {
-                save_cdp_macro!(self, cdp_data);
+                if expected_payment_value >= ZERO_EPSILON.max(total_payment_value * (1 - self.market_config.liquidation_dex_swap_rate)) {
+                    return Err(format!(
+                        "Insufficient payment value, {} required, {} total, {} remaining to pay",
+                        value,
+                        total_payment_value,
+                        expected_payment_value
+                    ));
+                }
             }
 
-            (remainders, total_payment_value)
+            Ok((total_payment_value, repayments))
         }
 
         fn _get_pool_state_without_update(
@@ -1484,6 +3436,265 @@ mod lending_market {
             pool_state
         }
 
+        fn _get_pool_state_in_context(
+            &mut self,
+            pool_res_address: &ResourceAddress,
+            operating_service: Option<OperatingService>,
+            pool_context: &mut PoolContext,
+        ) -> KeyValueEntryRefMut<'_, LendingPoolState> {
+            let mut pool_state =
+                self._get_pool_state_without_update(pool_res_address, operating_service);
+
+            pool_context
+                .refresh(&mut pool_state, None)
+                .expect("Error updating pool state");
+
+            pool_state
+        }
+
+        fn _get_position_deltas(
+            &self,
+            cdp_data: &WrappedCDPData,
+            positive: bool,
+        ) -> Vec<CDPPositionDelta> {
+            let sign = |units: PreciseDecimal| if positive { units } else { -units };
+
+            let collaterals = cdp_data.cdp_data.collaterals.iter().map(|(pool_res_address, units)| {
+                let pool_state = self.pool_states.get(pool_res_address).unwrap();
+                CDPPositionDelta::collateral(&pool_state, sign(*units))
+            });
+
+            let loans = cdp_data.cdp_data.loans.iter().map(|(pool_res_address, units)| {
+                let pool_state = self.pool_states.get(pool_res_address).unwrap();
+                CDPPositionDelta::loan(&pool_state, sign(*units)).expect("Error computing loan delta")
+            });
+
+            collaterals.chain(loans).collect()
+        }
+
+        fn _get_cdp_health_data(&self, cdp_id: &NonFungibleLocalId) -> Option<CDPHealthData> {
+            if !self.cdp_res_manager.non_fungible_exists(cdp_id) {
+                return None;
+            }
+
+            let cdp_data = WrappedCDPData::new(&self.cdp_res_manager, &self.cdp_positions, cdp_id);
+
+            let mut cdp_health_checker =
+                CDPHealthChecker::new_without_update(&cdp_data, &self.pool_states);
+
+            cdp_health_checker
+                .update_health_check_data()
+                .expect(&format!("Error updating health check data for cdp {}", cdp_id));
+
+            let total_collateral_value = cdp_health_checker
+                .collateral_positions
+                .values()
+                .fold(Decimal::ZERO, |total, position| total + position.data.value);
+
+            let loan_to_value_ratio = cdp_health_checker.total_loan_to_value_ratio;
+
+            Some(CDPHealthData {
+                cdp_id: cdp_data.cdp_id,
+                cdp_data: cdp_data.cdp_data,
+                total_collateral_value,
+                total_loan_value: cdp_health_checker.total_loan_value,
+                loan_to_value_ratio,
+                health_band: CDPHealthBand::from_ltv(loan_to_value_ratio, &self.market_config),
+                liquidable: !cdp_health_checker.loan_positions.is_empty()
+                    && (loan_to_value_ratio > Decimal::ONE
+                        || cdp_health_checker.get_wound_down_loan_value() > Decimal::ZERO),
+            })
+        }
+
+        /// Whether the liquidation of a CDP is deferred by the liquidation grace period, without
+        /// updating any state. A CDP without liquidation deadline would enter the grace period
+        ///
+        /// *Params*
+        /// - `cdp_data`: The CDP to check
+        /// - `loan_to_value_ratio`: The current LTV ratio of the CDP
+        fn _is_in_liquidation_grace_period(
+            &self,
+            cdp_data: &WrappedCDPData,
+            loan_to_value_ratio: Decimal,
+        ) -> bool {
+            if self.market_config.liquidation_grace_period.is_none() {
+                return false;
+            }
+
+            // A healthy CDP is only liquidable for its loans in wound down pools, whose
+            // wind-down already gave the borrower time to repay
+            if loan_to_value_ratio <= Decimal::ONE {
+                return false;
+            }
+
+            // Under the hard liquidation health factor, the liquidation is immediate
+            let hard_liquidation_health_factor = self.market_config.hard_liquidation_health_factor;
+            if hard_liquidation_health_factor > Decimal::ZERO
+                && loan_to_value_ratio > Decimal::ONE / hard_liquidation_health_factor
+            {
+                return false;
+            }
+
+            match cdp_data.liquidation_deadline {
+                Some(liquidation_deadline) => {
+                    Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch
+                        < liquidation_deadline
+                }
+                None => true,
+            }
+        }
+
+        /// Same as `_is_in_liquidation_grace_period`, starting the grace period of a CDP which
+        /// enters it
+        fn _apply_liquidation_grace_period(
+            &self,
+            cdp_data: &mut WrappedCDPData,
+            loan_to_value_ratio: Decimal,
+        ) -> bool {
+            if !self._is_in_liquidation_grace_period(cdp_data, loan_to_value_ratio) {
+                return false;
+            }
+
+            if let (None, Some(liquidation_grace_period)) = (
+                cdp_data.liquidation_deadline,
+                self.market_config.liquidation_grace_period,
+            ) {
+                let liquidation_deadline =
+                    Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch
+                        + liquidation_grace_period;
+
+                cdp_data.set_liquidation_deadline(Some(liquidation_deadline));
+
+                Runtime::emit_event(CDPAtRiskEvent {
+                    cdp_id: cdp_data.cdp_id.clone(),
+                    loan_to_value_ratio,
+                    liquidation_deadline,
+                });
+            }
+
+            true
+        }
+
+        fn _instantiate_pool(
+            &self,
+            pool_res_address: ResourceAddress,
+        ) -> (Global<SingleResourcePool>, ResourceAddress) {
+            let component_rule = rule!(require(global_caller(self.market_component_address)));
+
+            let (pool, pool_unit_res_address) = Blueprint::<SingleResourcePool>::instantiate(
+                pool_res_address,
+                OwnerRole::Fixed(component_rule.clone()),
+                self.admin_rule.clone(),
+                component_rule.clone(),
+                component_rule.clone(),
+                component_rule,
+            );
+
+            let pool_unit_res_manager = ResourceManager::from_address(pool_unit_res_address);
+
+            let pool_res_symbol: String = ResourceManager::from_address(pool_res_address)
+            .get_metadata("symbol").expect("Pool resource symbol not provided").unwrap_or_default();
+
+            pool_unit_res_manager.set_metadata("name", format!("{pool_res_symbol} rtToken"));
+            pool_unit_res_manager.lock_metadata("name");
+            pool_unit_res_manager.set_metadata("symbol", format!("rt{pool_res_symbol}"));
+            pool_unit_res_manager.lock_metadata("symbol");
+
+            (pool, pool_unit_res_address)
+        }
+
+        fn _put_in_bucket_map(buckets: &mut IndexMap<ResourceAddress, Bucket>, bucket: Bucket) {
+            match buckets.get_mut(&bucket.resource_address()) {
+                Some(existing_bucket) => existing_bucket.put(bucket),
+                None => {
+                    buckets.insert(bucket.resource_address(), bucket);
+                }
+            }
+        }
+
+        fn _validate_liquidator_badge_proof(&self, liquidator_badge_proof: Proof) -> NonFungibleLocalId {
+            liquidator_badge_proof
+                .check(self.liquidator_badge_manager.address())
+                .as_non_fungible()
+                .non_fungible_local_id()
+        }
+
+        fn _emit_liquidation_events(
+            &self,
+            cdp_id: NonFungibleLocalId,
+            liquidator_badge_id: Option<NonFungibleLocalId>,
+            repayments: Vec<CDPPositionDelta>,
+            seized_collaterals: Vec<SeizedCollateral>,
+            total_payment_value: Decimal,
+            loan_to_value_ratio: Decimal,
+        ) {
+            let (bonus_value, protocol_fee_value) = seized_collaterals.iter().fold(
+                (Decimal::ZERO, Decimal::ZERO),
+                |(bonus_value, protocol_fee_value), seized| {
+                    (
+                        bonus_value + seized.bonus_value,
+                        protocol_fee_value + seized.protocol_fee_amount * seized.position.price,
+                    )
+                },
+            );
+
+            let mut positions = repayments.clone();
+            positions.extend(seized_collaterals.iter().map(|seized| seized.position.clone()));
+
+            emit_cdp_event!(
+                cdp_id.clone(),
+                CDPUpdatedEvenType::Liquidate,
+                positions,
+                loan_to_value_ratio
+            );
+
+            Runtime::emit_event(LiquidationEvent {
+                cdp_id,
+                liquidator_badge_id,
+                repayments,
+                seized_collaterals,
+                total_payment_value,
+                bonus_value,
+                protocol_fee_value,
+                loan_to_value_ratio,
+            });
+        }
+
+        fn _mint_cdp(
+            &mut self,
+            name: Option<String>,
+            description: Option<String>,
+            key_image_url: Option<String>,
+        ) -> (NonFungibleLocalId, Bucket) {
+            let cdp_id = NonFungibleLocalId::Integer(self._get_new_cdp_id().into());
+
+            let now = Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch;
+
+            let key_image_url = key_image_url.unwrap_or_else(|| {
+                self.cdp_key_image_url_template
+                    .as_ref()
+                    .map(|template| {
+                        render_cdp_key_image_url(template, &cdp_id, &CDPHealthBand::Healthy)
+                    })
+                    .unwrap_or_default()
+            });
+
+            let data = CDPNonFungibleData {
+                name: name.unwrap_or_default(),
+                description: description.unwrap_or_default(),
+                key_image_url,
+                cdp_type: CDPType::Standard,
+                minted_at: now,
+                health_band: CDPHealthBand::Healthy,
+                collaterals: IndexMap::new(),
+                loans: IndexMap::new(),
+            };
+
+            let cdp = self.cdp_res_manager.mint_non_fungible(&cdp_id, data);
+
+            (cdp_id, cdp)
+        }
+
         fn _get_new_cdp_id(&mut self) -> u64 {
             self.cdp_counter += 1;
             self.cdp_counter
@@ -1499,6 +3710,27 @@ mod lending_market {
             validated_cdp.as_non_fungible().non_fungible_local_id()
         }
 
+        fn _validate_withdrawal_request(
+            &self,
+            withdrawal_request: &Bucket,
+        ) -> (u64, WithdrawalRequestData) {
+            assert!(
+                withdrawal_request.resource_address() == self.withdrawal_request_res_manager.address(),
+                "Invalid withdrawal request"
+            );
+
+            let non_fungible = withdrawal_request
+                .as_non_fungible()
+                .non_fungible::<WithdrawalRequestData>();
+
+            let request_id = match non_fungible.local_id() {
+                NonFungibleLocalId::Integer(request_id) => request_id.value(),
+                _ => panic!("Invalid withdrawal request id"),
+            };
+
+            (request_id, non_fungible.data())
+        }
+
         fn _check_operating_status(&self, value: OperatingService) {
             assert!(
                 self.operating_status.check(value.clone()),
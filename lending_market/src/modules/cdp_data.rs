@@ -1,9 +1,12 @@
 use scrypto::prelude::*;
 
-use super::cdp_health_checker::ZERO_EPSILON;
+use super::cdp_health_checker::{CDPHealthChecker, ZERO_EPSILON};
+use super::cdp_index::PoolCDPIndex;
+use super::market_config::MarketConfig;
+use super::pool_state::LendingPoolState;
 
 /// Type of the event launched in case of CDP update
-#[derive(ScryptoSbor)]
+#[derive(ScryptoSbor, Clone)]
 pub enum CDPUpdatedEvenType {
     /// Signals collateral was added
     AddCollateral,
@@ -14,7 +17,70 @@ pub enum CDPUpdatedEvenType {
     /// Signals repay of a borrowed amount happened
     Repay,
     /// Signals CDP liquidation
-    Liquidate
+    Liquidate,
+    /// Signals other CDPs were merged into this CDP
+    Merge,
+    /// Signals positions were moved out of this CDP into a new one
+    Split,
+    /// Signals the CDP was burnt
+    Burn,
+    /// Signals a collateral was enabled or disabled as collateral
+    CollateralUsage
+}
+
+/// Change of a CDP position, carried by the CDP events so that indexers do not need to read the NFT again
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct CDPPositionDelta {
+    /// The pool resource address of the position
+    pub pool_res_address: ResourceAddress,
+    /// Signed amount of units added to (positive) or removed from (negative) the position
+    pub delta_units: PreciseDecimal,
+    /// Signed amount of asset equivalent to the delta units
+    pub delta_amount: Decimal,
+    /// The asset price used for the operation
+    pub price: Decimal,
+}
+
+impl CDPPositionDelta {
+    /// Build the delta of a collateral position
+    /// 
+    /// *Params*
+    /// - `pool_state`: The state of the pool the collateral belongs to
+    /// - `delta_units`: Signed amount of collateral units
+    pub fn collateral(pool_state: &LendingPoolState, delta_units: PreciseDecimal) -> CDPPositionDelta {
+        let unit_ratio = pool_state.pool.get_pool_unit_ratio();
+        Self::new(pool_state, delta_units, unit_ratio)
+    }
+
+    /// Build the delta of a loan position
+    /// 
+    /// *Params*
+    /// - `pool_state`: The state of the pool the loan belongs to
+    /// - `delta_units`: Signed amount of loan units
+    /// 
+    /// *Error*
+    /// - If the loan unit ratio cannot be computed
+    pub fn loan(pool_state: &LendingPoolState, delta_units: PreciseDecimal) -> Result<CDPPositionDelta, String> {
+        let unit_ratio = pool_state.get_loan_unit_ratio()?;
+        Ok(Self::new(pool_state, delta_units, unit_ratio))
+    }
+
+    fn new(pool_state: &LendingPoolState, delta_units: PreciseDecimal, unit_ratio: PreciseDecimal) -> CDPPositionDelta {
+        let delta_amount = if unit_ratio == PreciseDecimal::ZERO {
+            Decimal::ZERO
+        } else {
+            (delta_units / unit_ratio)
+                .checked_truncate(RoundingMode::ToNearestMidpointToEven)
+                .unwrap()
+        };
+
+        CDPPositionDelta {
+            pool_res_address: pool_state.pool_res_address,
+            delta_units,
+            delta_amount,
+            price: pool_state.price,
+        }
+    }
 }
 
 /// Event launched in case of CDP update
@@ -26,6 +92,164 @@ pub struct CDPUpdatedEvent {
     pub event_type: CDPUpdatedEvenType,
 }
 
+/// Event launched alongside `CDPUpdatedEvent` with the details of the update. `CDPUpdatedEvent`
+/// keeps its original schema so that existing indexers are not broken
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct CDPPositionsUpdatedEvent {
+    /// id of the updated CDP
+    pub cdp_id: NonFungibleLocalId,
+    /// type of the event
+    pub event_type: CDPUpdatedEvenType,
+    /// The changes applied to the CDP positions
+    pub positions: Vec<CDPPositionDelta>,
+    /// The LTV ratio of the CDP after the operation
+    pub loan_to_value_ratio: Decimal,
+}
+
+/// Event launched when loans of a CDP are repaid by its owner
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct RepayEvent {
+    /// id of the CDP
+    pub cdp_id: NonFungibleLocalId,
+    /// The repaid loan positions
+    pub repayments: Vec<CDPPositionDelta>,
+    /// The total value repaid
+    pub total_payment_value: Decimal,
+    /// The LTV ratio of the CDP after the repayment
+    pub loan_to_value_ratio: Decimal,
+}
+
+/// Collateral taken out of a CDP during a liquidation
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct SeizedCollateral {
+    /// The change of the collateral position
+    pub position: CDPPositionDelta,
+    /// The value given to the liquidator on top of the repaid value
+    pub bonus_value: Decimal,
+    /// The amount of asset retained by the protocol as liquidation fee
+    pub protocol_fee_amount: Decimal,
+}
+
+/// Event launched when a CDP is liquidated
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct LiquidationEvent {
+    /// id of the liquidated CDP
+    pub cdp_id: NonFungibleLocalId,
+    /// id of the liquidator badge, if provided
+    pub liquidator_badge_id: Option<NonFungibleLocalId>,
+    /// The repaid loan positions
+    pub repayments: Vec<CDPPositionDelta>,
+    /// The seized collaterals
+    pub seized_collaterals: Vec<SeizedCollateral>,
+    /// The total value repaid by the liquidator
+    pub total_payment_value: Decimal,
+    /// The total bonus value paid to the liquidator
+    pub bonus_value: Decimal,
+    /// The total value retained by the protocol as liquidation fee
+    pub protocol_fee_value: Decimal,
+    /// The LTV ratio of the CDP after the liquidation
+    pub loan_to_value_ratio: Decimal,
+}
+
+/// Loan repayment estimated by a liquidation quote
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct LiquidationQuoteRepayment {
+    /// The resource address of the loan
+    pub pool_res_address: ResourceAddress,
+    /// The max amount repayable, after the loan close factor or within the liquidable value
+    /// of the CDP when a target health factor is set
+    pub amount: Decimal,
+    /// The value of the repayable amount
+    pub value: Decimal,
+}
+
+/// Collateral seizure estimated by a liquidation quote
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct LiquidationQuoteSeizure {
+    /// The resource address of the collateral
+    pub pool_res_address: ResourceAddress,
+    /// The amount received by the liquidator, protocol fee deducted
+    pub amount: Decimal,
+    /// The value received by the liquidator, protocol fee deducted
+    pub value: Decimal,
+    /// The value given to the liquidator on top of the repaid value
+    pub bonus_value: Decimal,
+    /// The amount of asset retained by the protocol as liquidation fee
+    pub protocol_fee_amount: Decimal,
+}
+
+/// Estimation of the outcome of a liquidation, computed from the current pool states
+/// without updating them
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct LiquidationQuote {
+    /// id of the CDP
+    pub cdp_id: NonFungibleLocalId,
+    /// The repayable loans, in the requested order
+    pub repayments: Vec<LiquidationQuoteRepayment>,
+    /// The seizable collaterals, in the requested order
+    pub seizures: Vec<LiquidationQuoteSeizure>,
+    /// The total value to repay
+    pub total_payment_value: Decimal,
+    /// The total value received by the liquidator, protocol fee deducted
+    pub total_seized_value: Decimal,
+    /// The total value retained by the protocol as liquidation fee
+    pub protocol_fee_value: Decimal,
+    /// The repaid value not covered by the requested collaterals
+    pub uncovered_payment_value: Decimal,
+    /// The LTV ratio of the CDP before the liquidation
+    pub loan_to_value_ratio: Decimal,
+    /// The LTV ratio of the CDP after the liquidation
+    pub post_liquidation_loan_to_value_ratio: Decimal,
+}
+
+/// Outcome of the liquidation of a CDP within a batch liquidation
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct BatchLiquidationResult {
+    /// id of the CDP
+    pub cdp_id: NonFungibleLocalId,
+    /// Whether the CDP was liquidated. CDPs that are not liquidable anymore, or whose loans
+    /// are not covered by any payment, are skipped
+    pub liquidated: bool,
+    /// The total value repaid for the CDP
+    pub total_payment_value: Decimal,
+    /// The LTV ratio of the CDP after the liquidation, zero if the health check failed
+    pub loan_to_value_ratio: Decimal,
+    /// The reason why the CDP was not liquidated, if it could not be processed
+    pub error: Option<String>,
+}
+impl BatchLiquidationResult {
+    /// Result of a CDP skipped by the batch liquidation
+    /// 
+    /// *Params*
+    /// - `cdp_id`: The id of the CDP
+    /// - `loan_to_value_ratio`: The LTV ratio of the CDP
+    /// - `error`: The reason why the CDP could not be processed, if any
+    pub fn skipped(
+        cdp_id: NonFungibleLocalId,
+        loan_to_value_ratio: Decimal,
+        error: Option<String>,
+    ) -> BatchLiquidationResult {
+        BatchLiquidationResult {
+            cdp_id,
+            liquidated: false,
+            total_payment_value: Decimal::ZERO,
+            loan_to_value_ratio,
+            error,
+        }
+    }
+}
+
+/// Event launched when a CDP enters the liquidation grace period
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct CDPAtRiskEvent {
+    /// id of the CDP at risk
+    pub cdp_id: NonFungibleLocalId,
+    /// The LTV ratio of the CDP
+    pub loan_to_value_ratio: Decimal,
+    /// Timestamp from which the CDP can be liquidated, unless it is back to health
+    pub liquidation_deadline: i64,
+}
+
 /// Model of a liquidable CDP
 #[derive(ScryptoSbor, Clone, Debug)]
 pub struct CDPLiquidable {
@@ -42,6 +266,80 @@ pub struct CDPLiquidableEvent {
     pub cdps: Vec<CDPLiquidable>,
 }
 
+/// Up-to-date health data of a CDP, as returned by the CDP queries
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct CDPHealthData {
+    /// id of the CDP
+    pub cdp_id: NonFungibleLocalId,
+    /// Data of the CDP
+    pub cdp_data: CollaterizedDebtPositionData,
+    /// Total value of the collaterals in the CDP
+    pub total_collateral_value: Decimal,
+    /// Total value of the loans in the CDP
+    pub total_loan_value: Decimal,
+    /// LTV ratio of the CDP. The health factor is its inverse
+    pub loan_to_value_ratio: Decimal,
+    /// Health band of the CDP, derived from the LTV ratio
+    pub health_band: CDPHealthBand,
+    /// True if the CDP can be liquidated
+    pub liquidable: bool,
+}
+
+/// Filter applied by the CDP queries. Criteria set to `None` are ignored
+#[derive(ScryptoSbor, Clone, Debug, Default)]
+pub struct CDPFilter {
+    /// Minimum LTV ratio, inclusive
+    pub min_loan_to_value_ratio: Option<Decimal>,
+    /// Maximum LTV ratio, inclusive
+    pub max_loan_to_value_ratio: Option<Decimal>,
+    /// Resource that must be used as collateral in the CDP
+    pub collateral: Option<ResourceAddress>,
+    /// Resource that must be borrowed in the CDP
+    pub loan: Option<ResourceAddress>,
+    /// Minimum total loan value, inclusive
+    pub min_loan_value: Option<Decimal>,
+    /// Required value of the liquidable flag
+    pub liquidable: Option<bool>,
+}
+
+impl CDPFilter {
+    /// Check if the CDP health data matches all the criteria of the filter
+    /// 
+    /// *Params*
+    /// - `cdp_health_data`: The health data of the CDP to check
+    pub fn matches(&self, cdp_health_data: &CDPHealthData) -> bool {
+        self.min_loan_to_value_ratio
+            .map_or(true, |min| cdp_health_data.loan_to_value_ratio >= min)
+            && self
+                .max_loan_to_value_ratio
+                .map_or(true, |max| cdp_health_data.loan_to_value_ratio <= max)
+            && self.collateral.map_or(true, |res_address| {
+                cdp_health_data.cdp_data.collaterals.contains_key(&res_address)
+            })
+            && self.loan.map_or(true, |res_address| {
+                cdp_health_data.cdp_data.loans.contains_key(&res_address)
+            })
+            && self
+                .min_loan_value
+                .map_or(true, |min| cdp_health_data.total_loan_value >= min)
+            && self
+                .liquidable
+                .map_or(true, |liquidable| cdp_health_data.liquidable == liquidable)
+    }
+}
+
+/// Page of CDPs returned by the paginated CDP queries
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct CDPPage {
+    /// CDPs of the page matching the filter
+    pub cdps: Vec<CDPHealthData>,
+    /// Cursor to use to query the next page, `None` if there are no more CDPs to scan
+    pub next_cursor: Option<u64>,
+    /// Version of the pool index the page was read from, `None` if the query does not use an index.
+    /// A different version between two pages means that CDPs were moved in the index during the scan
+    pub index_version: Option<u64>,
+}
+
 /// Type of CDP
 #[derive(ScryptoSbor, Clone, PartialEq, Debug)]
 pub enum CDPType {
@@ -49,9 +347,77 @@ pub enum CDPType {
     Standard
 }
 
-/// Data describing the CDP
+/// Risk band of a CDP, derived from its LTV ratio so that wallets can show the risk of a position at a glance
+#[derive(ScryptoSbor, Clone, PartialEq, Debug)]
+pub enum CDPHealthBand {
+    /// LTV ratio lower than the moderate health band LTV of the market, or no loans at all
+    Healthy,
+    /// LTV ratio between the moderate and the risky health band LTVs of the market
+    Moderate,
+    /// LTV ratio between the risky health band LTV of the market and 1, close to liquidation
+    Risky,
+    /// LTV ratio greater than 1, the CDP can be liquidated
+    Liquidable,
+}
+
+impl CDPHealthBand {
+    /// Get the health band corresponding to a LTV ratio
+    /// 
+    /// *Params*
+    /// - `ltv`: The LTV ratio as computed by the `CDPHealthChecker`
+    /// - `market_config`: The market configuration holding the health band LTVs
+    pub fn from_ltv(ltv: Decimal, market_config: &MarketConfig) -> CDPHealthBand {
+        if ltv < market_config.moderate_health_band_ltv {
+            CDPHealthBand::Healthy
+        } else if ltv < market_config.risky_health_band_ltv {
+            CDPHealthBand::Moderate
+        } else if ltv <= Decimal::ONE {
+            CDPHealthBand::Risky
+        } else {
+            CDPHealthBand::Liquidable
+        }
+    }
+
+    /// Textual representation of the health band, used to render the key image URL
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CDPHealthBand::Healthy => "healthy",
+            CDPHealthBand::Moderate => "moderate",
+            CDPHealthBand::Risky => "risky",
+            CDPHealthBand::Liquidable => "liquidable",
+        }
+    }
+}
+
+/// Render the key image URL of a CDP out of a template, replacing the `{cdp_id}` and `{health_band}` placeholders
+/// 
+/// *Params*
+/// - `template`: The URL template, like `https://example.com/cdp/{cdp_id}.svg?band={health_band}`
+/// - `cdp_id`: The id of the CDP
+/// - `health_band`: The current health band of the CDP
+/// 
+/// *Output*
+/// The rendered URL
+pub fn render_cdp_key_image_url(
+    template: &str,
+    cdp_id: &NonFungibleLocalId,
+    health_band: &CDPHealthBand,
+) -> String {
+    let cdp_id = match cdp_id {
+        NonFungibleLocalId::Integer(cdp_id) => cdp_id.value().to_string(),
+        _ => cdp_id.to_string(),
+    };
+
+    template
+        .replace("{cdp_id}", &cdp_id)
+        .replace("{health_band}", health_band.as_str())
+}
+
+/// Non fungible data of the CDP. Only immutable and display fields are stored on the NFT,
+/// the positions are stored in the market `CDPPositions`. The positions of the CDPs minted
+/// before are kept in the legacy fields until they are migrated
 #[derive(ScryptoSbor, NonFungibleData, Clone, Debug)]
-pub struct CollaterizedDebtPositionData {
+pub struct CDPNonFungibleData {
     /// Image to display when exploring Radix transactions
     #[mutable]
     pub key_image_url: String,
@@ -67,29 +433,101 @@ pub struct CollaterizedDebtPositionData {
     /// Immutable timestamp of CDP minting
     pub minted_at: i64,
 
-    /// Timestamp of CDP update
+    /// Type of the CDP
+    #[mutable]
+    pub cdp_type: CDPType,
+
+    /// Risk band of the CDP, updated each time the CDP is saved
+    #[mutable]
+    pub health_band: CDPHealthBand,
+
+    /// Legacy collateral units, only set on the CDPs not migrated yet
+    #[mutable]
+    pub collaterals: IndexMap<ResourceAddress, PreciseDecimal>,
+
+    /// Legacy loan units, only set on the CDPs not migrated yet
     #[mutable]
+    pub loans: IndexMap<ResourceAddress, PreciseDecimal>,
+}
+
+impl CDPNonFungibleData {
+    /// Positions stored in the NFT data of a CDP not migrated yet
+    /// 
+    /// *Output*
+    /// The legacy positions, `None` if the NFT data holds no position
+    pub fn legacy_positions(&self) -> Option<CDPPositions> {
+        if self.collaterals.is_empty() && self.loans.is_empty() {
+            return None;
+        }
+
+        Some(CDPPositions {
+            collaterals: self.collaterals.clone(),
+            loans: self.loans.clone(),
+            updated_at: self.minted_at,
+            ..Default::default()
+        })
+    }
+}
+
+/// Data describing the CDP, combining its non fungible data and its positions
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct CollaterizedDebtPositionData {
+    /// Image to display when exploring Radix transactions
+    pub key_image_url: String,
+
+    /// Name of the CDP
+    pub name: String,
+
+    /// Textual description of the CDP
+    pub description: String,
+
+    /// Timestamp of CDP minting
+    pub minted_at: i64,
+
+    /// Timestamp of CDP positions update
     pub updated_at: i64,
 
     /// Type of the CDP
-    #[mutable]
     pub cdp_type: CDPType,
 
     /// Map of collateral values, having the asset as key and the unit amount as value.
     /// Here, `PreciseDecimal` helps in keeping precision in computations
     /// even if the actual amount will require to be expressed as `Decimal`
-    #[mutable]
     pub collaterals: IndexMap<ResourceAddress, PreciseDecimal>,
 
     /// Map of loaned values, having the asset as key and the unit amount as value.
     /// Here, `PreciseDecimal` helps in keeping precision in computation
     /// even if the actual amount will require to be expressed as `Decimal`
-    #[mutable]
     pub loans: IndexMap<ResourceAddress, PreciseDecimal>,
 
     /// The maximum amount of liquidable value for this collateralized debt position
-    #[mutable]
-    pub liquidable: Option<Decimal>
+    pub liquidable: Option<Decimal>,
+
+    /// Risk band of the CDP, updated each time the CDP is saved
+    pub health_band: CDPHealthBand,
+}
+
+/// Positions of a CDP, stored in the market component rather than in the NFT data so that
+/// updates do not rewrite the CDP non fungible data
+#[derive(ScryptoSbor, Clone, Debug, Default)]
+pub struct CDPPositions {
+    /// Map of collateral values, having the asset as key and the unit amount as value
+    pub collaterals: IndexMap<ResourceAddress, PreciseDecimal>,
+    /// Map of loaned values, having the asset as key and the unit amount as value
+    pub loans: IndexMap<ResourceAddress, PreciseDecimal>,
+    /// Deposited assets not used as collateral. They keep earning supply yield but neither
+    /// count toward the borrowing power nor can be seized in liquidation
+    pub disabled_collaterals: IndexSet<ResourceAddress>,
+    /// Owner-defined order in which the collaterals are seized in liquidation. Listed collaterals
+    /// are seized first, in order, then the other ones
+    pub liquidation_preference: Vec<ResourceAddress>,
+    /// The maximum amount of liquidable value for this collateralized debt position
+    pub liquidable: Option<Decimal>,
+    /// Timestamp from which a CDP at risk can be liquidated, set when the CDP enters the
+    /// liquidation grace period
+    pub liquidation_deadline: Option<i64>,
+    /// Timestamp of the last positions update
+    pub updated_at: i64,
 }
 
 /// Wrapper of the `CollaterizedDebtPositionData` that keeps trace of the modifications,
@@ -104,24 +542,75 @@ pub struct WrappedCDPData {
     pub collateral_updated: bool,
     /// Indicator of an update in the loaned values
     pub loan_updated: bool,
+    /// Indicator of an update in the health band and in the rendered key image URL
+    pub display_updated: bool,
+    /// Pools in which the CDP positions were updated, used to maintain the pool CDP indexes
+    pub updated_pools: IndexSet<ResourceAddress>,
+    /// Indicator of positions loaded from the NFT data of a CDP not migrated yet
+    pub legacy_positions: bool,
+    /// Deposited assets not used as collateral
+    pub disabled_collaterals: IndexSet<ResourceAddress>,
+    /// Order in which the collaterals are seized in liquidation
+    pub liquidation_preference: Vec<ResourceAddress>,
+    /// Timestamp from which the CDP at risk can be liquidated
+    pub liquidation_deadline: Option<i64>,
 }
 
 impl WrappedCDPData {
-    /// Constructor
+    /// Constructor. Positions are loaded from the market storage, or from the NFT data if the
+    /// CDP is not migrated yet. A CDP never saved has none
     /// 
     /// *Params*
     /// - `res_manager``: The CDP resource manager
+    /// - `cdp_positions`: Reference to the on-chain key-value storage where key is the CDP id
+    ///                 and value is the CDP positions
     /// - `cdp_id`: The id to use for the newly created CDP
     /// 
     /// *Output*
     /// A new `WrappedCDPData` 
-    pub fn new(res_manager: &ResourceManager, cdp_id: &NonFungibleLocalId) -> WrappedCDPData {
-        let cdp_data = res_manager.get_non_fungible_data(cdp_id);
+    pub fn new(
+        res_manager: &ResourceManager,
+        cdp_positions: &KeyValueStore<NonFungibleLocalId, CDPPositions>,
+        cdp_id: &NonFungibleLocalId,
+    ) -> WrappedCDPData {
+        let nft_data: CDPNonFungibleData = res_manager.get_non_fungible_data(cdp_id);
+
+        let stored_positions = cdp_positions.get(cdp_id).map(|positions| positions.clone());
+
+        let legacy_positions = match stored_positions {
+            Some(_) => None,
+            None => nft_data.legacy_positions(),
+        };
+
+        let is_legacy = legacy_positions.is_some();
+
+        let positions = stored_positions.or(legacy_positions).unwrap_or_else(|| CDPPositions {
+            updated_at: nft_data.minted_at,
+            ..Default::default()
+        });
+
         WrappedCDPData {
             cdp_id: cdp_id.clone(),
-            cdp_data,
+            cdp_data: CollaterizedDebtPositionData {
+                key_image_url: nft_data.key_image_url,
+                name: nft_data.name,
+                description: nft_data.description,
+                minted_at: nft_data.minted_at,
+                updated_at: positions.updated_at,
+                cdp_type: nft_data.cdp_type,
+                collaterals: positions.collaterals,
+                loans: positions.loans,
+                liquidable: positions.liquidable,
+                health_band: nft_data.health_band,
+            },
             collateral_updated: false,
-            loan_updated: false
+            loan_updated: false,
+            display_updated: false,
+            updated_pools: IndexSet::new(),
+            legacy_positions: is_legacy,
+            disabled_collaterals: positions.disabled_collaterals,
+            liquidation_preference: positions.liquidation_preference,
+            liquidation_deadline: positions.liquidation_deadline,
         }
     }
 
@@ -162,10 +651,50 @@ impl WrappedCDPData {
         units: PreciseDecimal,
     ) -> Result<(), String> {
         let result = Self::update_map(&mut self.cdp_data.collaterals, res_address, units);
+        if !self.cdp_data.collaterals.contains_key(&res_address) {
+            self.disabled_collaterals.swap_remove(&res_address);
+        }
         self.collateral_updated = true;
+        self.updated_pools.insert(res_address);
         result
     }
 
+    /// Whether a deposited asset is used as collateral
+    /// 
+    /// *Params*
+    /// - `res_address`: The resource address of the collateral
+    pub fn is_used_as_collateral(&self, res_address: ResourceAddress) -> bool {
+        !self.disabled_collaterals.contains(&res_address)
+    }
+
+    /// Enable or disable a deposited asset as collateral
+    /// 
+    /// *Params*
+    /// - `res_address`: The resource address of the collateral
+    /// - `use_as_collateral`: Whether the asset is used as collateral
+    /// 
+    /// *Error*
+    /// - If the CDP has no such collateral
+    pub fn set_use_as_collateral(
+        &mut self,
+        res_address: ResourceAddress,
+        use_as_collateral: bool,
+    ) -> Result<(), String> {
+        if !self.cdp_data.collaterals.contains_key(&res_address) {
+            return Err(format!("Collateral {:?} not found in the CDP", res_address));
+        }
+
+        if use_as_collateral {
+            self.disabled_collaterals.swap_remove(&res_address);
+        } else {
+            self.disabled_collaterals.insert(res_address);
+        }
+
+        self.collateral_updated = true;
+
+        Ok(())
+    }
+
     /// Update the CDP loaned values
     /// 
     /// *Params*
@@ -181,9 +710,186 @@ impl WrappedCDPData {
     ) -> Result<(), String> {
         let result = Self::update_map(&mut self.cdp_data.loans, res_address, units);
         self.loan_updated = true;
+        self.updated_pools.insert(res_address);
         result
     }
 
+    /// Set the order in which the collaterals are seized in liquidation
+    /// 
+    /// *Params*
+    /// - `liquidation_preference`: The collaterals to seize first, in order
+    /// 
+    /// *Error*
+    /// - If a collateral is listed more than once
+    pub fn set_liquidation_preference(
+        &mut self,
+        liquidation_preference: Vec<ResourceAddress>,
+    ) -> Result<(), String> {
+        let unique: IndexSet<ResourceAddress> = liquidation_preference.iter().copied().collect();
+
+        if unique.len() != liquidation_preference.len() {
+            return Err("Duplicate collateral in the liquidation preference".into());
+        }
+
+        self.liquidation_preference = liquidation_preference;
+        self.collateral_updated = true;
+
+        Ok(())
+    }
+
+    /// Check that a collateral can be seized according to the liquidation preference, i.e. that
+    /// all the collaterals ranked before it are exhausted
+    /// 
+    /// *Params*
+    /// - `res_address`: The resource address of the collateral to seize
+    /// 
+    /// *Error*
+    /// - If a collateral ranked before is not exhausted
+    pub fn check_liquidation_preference(&self, res_address: ResourceAddress) -> Result<(), String> {
+        for preferred in self.liquidation_preference.iter() {
+            if *preferred == res_address {
+                break;
+            }
+
+            if self.cdp_data.collaterals.contains_key(preferred)
+                && self.is_used_as_collateral(*preferred)
+            {
+                return Err(format!(
+                    "Collateral {:?} must be seized before {:?}",
+                    preferred, res_address
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set the timestamp from which the CDP at risk can be liquidated, or clear it
+    /// 
+    /// *Params*
+    /// - `liquidation_deadline`: The liquidation deadline, nothing if the CDP is not at risk
+    pub fn set_liquidation_deadline(&mut self, liquidation_deadline: Option<i64>) {
+        if self.liquidation_deadline != liquidation_deadline {
+            self.liquidation_deadline = liquidation_deadline;
+            self.collateral_updated = true;
+        }
+    }
+
+    /// Remove the CDP from the index of every pool where it has positions.
+    /// Used when the CDP is burnt without being saved, like after a merge
+    /// 
+    /// *Params*
+    /// - `pool_cdp_indexes`: Reference to the on-chain key-value storage where key is asset resource
+    ///                 address and value is the index of the CDPs with positions in the pool
+    pub fn remove_from_pool_cdp_indexes(
+        &self,
+        pool_cdp_indexes: &KeyValueStore<ResourceAddress, PoolCDPIndex>,
+    ) {
+        for pool_res_address in self
+            .cdp_data
+            .collaterals
+            .keys()
+            .chain(self.cdp_data.loans.keys())
+        {
+            if let Some(mut pool_cdp_index) = pool_cdp_indexes.get_mut(pool_res_address) {
+                pool_cdp_index.remove(&self.cdp_id);
+            }
+        }
+    }
+
+    /// Whether the CDP has neither collaterals nor loans
+    pub fn is_empty(&self) -> bool {
+        self.cdp_data.collaterals.is_empty() && self.cdp_data.loans.is_empty()
+    }
+
+    /// Add all the collateral and loan units of another CDP to this CDP.
+    /// Pool vaults are not affected since the units stay locked in the same pools.
+    /// 
+    /// *Params*
+    /// - `other`: The CDP data to merge
+    /// 
+    /// *Error*
+    /// - If update of the internal state fails
+    pub fn merge(&mut self, other: &WrappedCDPData) -> Result<(), String> {
+        for (res_address, units) in other.cdp_data.collaterals.iter() {
+            // The usage of an asset already deposited in this CDP is kept
+            let disabled = !self.cdp_data.collaterals.contains_key(res_address)
+                && !other.is_used_as_collateral(*res_address);
+
+            self.update_collateral(*res_address, *units)?;
+
+            if disabled {
+                self.disabled_collaterals.insert(*res_address);
+            }
+        }
+
+        for (res_address, units) in other.cdp_data.loans.iter() {
+            self.update_loan(*res_address, *units)?;
+        }
+
+        Ok(())
+    }
+
+    /// Recompute the health band of the CDP and, if the key image URL was generated out of
+    /// the market template, render it again with the new health band. A CDP back to health,
+    /// after a repayment or a collateral top-up, is no longer at risk.
+    /// 
+    /// *Params*
+    /// - `loan_to_value_ratio`: The LTV ratio of the CDP if already computed by a health check,
+    ///                 else it is computed from the pool states
+    /// - `pool_states`: Reference to the on-chain key-value storage where key is asset resource 
+    ///                 address and value is the current pool state
+    /// - `key_image_url_template`: The market template for the key image URL, if any
+    /// - `market_config`: The market configuration holding the health band LTVs
+    /// 
+    /// *Output*
+    /// - The LTV ratio of the CDP
+    /// 
+    /// *Error*
+    /// - If update of the internal state fails
+    pub fn update_health_band(
+        &mut self,
+        loan_to_value_ratio: Option<Decimal>,
+        pool_states: &KeyValueStore<ResourceAddress, LendingPoolState>,
+        key_image_url_template: &Option<String>,
+        market_config: &MarketConfig,
+    ) -> Result<Decimal, String> {
+        let loan_to_value_ratio = match loan_to_value_ratio {
+            Some(loan_to_value_ratio) => loan_to_value_ratio,
+            None if self.cdp_data.loans.is_empty() => Decimal::ZERO,
+            None => {
+                let mut cdp_health_checker = CDPHealthChecker::new_without_update(self, pool_states);
+                cdp_health_checker.update_health_check_data()?;
+                cdp_health_checker.total_loan_to_value_ratio
+            }
+        };
+
+        if loan_to_value_ratio <= Decimal::ONE {
+            self.set_liquidation_deadline(None);
+        }
+
+        let health_band = CDPHealthBand::from_ltv(loan_to_value_ratio, market_config);
+
+        if health_band == self.cdp_data.health_band {
+            return Ok(loan_to_value_ratio);
+        }
+
+        if let Some(template) = key_image_url_template {
+            let current_key_image_url =
+                render_cdp_key_image_url(template, &self.cdp_id, &self.cdp_data.health_band);
+
+            if self.cdp_data.key_image_url == current_key_image_url {
+                self.cdp_data.key_image_url =
+                    render_cdp_key_image_url(template, &self.cdp_id, &health_band);
+            }
+        }
+
+        self.cdp_data.health_band = health_band;
+        self.display_updated = true;
+
+        Ok(loan_to_value_ratio)
+    }
+
     /// Cleanup tasks to perform upon liquidation.
     /// 
     /// *Error*
@@ -192,70 +898,177 @@ impl WrappedCDPData {
         &mut self
     ) -> Result<(), String> {
         if self.cdp_data.collaterals.len() == 0 && self.cdp_data.loans.len() > 0 {
+            self.updated_pools.extend(self.cdp_data.loans.keys().copied());
             self.cdp_data.loans.clear();
             self.loan_updated = true;
         }
         Ok(())
     }
 
-    /// Save the CDP, updating only the required fields
+    /// Save the CDP, updating only the required fields. The positions of a CDP not migrated yet
+    /// are moved into the market storage
     /// 
     /// *Params*
     /// - `res_manager``: The CDP resource manager
+    /// - `cdp_positions`: Reference to the on-chain key-value storage where key is the CDP id
+    ///                 and value is the CDP positions
     /// - `max_cdp_position`: The configured amount of positions for the CDP
+    /// - `pool_cdp_indexes`: Reference to the on-chain key-value storage where key is asset resource
+    ///                 address and value is the index of the CDPs with positions in the pool
     /// 
     /// *Error*
+    /// - If the CDP gains positions above the configured amount
     /// - If update of the internal state fails
     pub fn save_cdp(
         &self,
         res_manager: &ResourceManager,
+        cdp_positions: &KeyValueStore<NonFungibleLocalId, CDPPositions>,
         max_cdp_position: u8,
+        pool_cdp_indexes: &KeyValueStore<ResourceAddress, PoolCDPIndex>,
     ) -> Result<(), String> {
-        let mut updated = false;
+        // The limit is only enforced when the CDP gains positions, so that a CDP above a lowered
+        // limit can still be repaid or liquidated
+        let position_count = self.cdp_data.collaterals.len() + self.cdp_data.loans.len();
 
-        if self.collateral_updated {
-            res_manager.update_non_fungible_data(
-                &self.cdp_id,
-                "collaterals",
-                self.cdp_data.collaterals.clone(),
+        if position_count > max_cdp_position as usize {
+            let stored_position_count = cdp_positions
+                .get(&self.cdp_id)
+                .map(|positions| positions.collaterals.len() + positions.loans.len())
+                .unwrap_or(0);
+
+            if position_count > stored_position_count {
+                return Err(format!(
+                    "CDP has {} positions, max is {}",
+                    position_count, max_cdp_position
+                ));
+            }
+        }
+
+        // The pools of a CDP not migrated yet are not indexed
+        if self.legacy_positions {
+            self._add_to_pool_cdp_indexes(pool_cdp_indexes);
+        }
+
+        for pool_res_address in self.updated_pools.iter() {
+            let mut pool_cdp_index = pool_cdp_indexes
+                .get_mut(pool_res_address)
+                .ok_or(format!("CDP index not found for pool {:?}", pool_res_address))?;
+
+            if self.cdp_data.collaterals.contains_key(pool_res_address)
+                || self.cdp_data.loans.contains_key(pool_res_address)
+            {
+                pool_cdp_index.insert(&self.cdp_id);
+            } else {
+                pool_cdp_index.remove(&self.cdp_id);
+            }
+        }
+
+        if self.collateral_updated
+            || self.loan_updated
+            || self.cdp_data.liquidable.is_some()
+            || self.legacy_positions
+        {
+            self._store_positions(
+                res_manager,
+                cdp_positions,
+                Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch,
             );
-            updated = true;
         }
 
-        if self.loan_updated {
+        if self.display_updated {
             res_manager.update_non_fungible_data(
                 &self.cdp_id,
-                "loans",
-                self.cdp_data.loans.clone(),
+                "health_band",
+                self.cdp_data.health_band.clone(),
             );
-            updated = true;
-        }
-
-        if self.cdp_data.liquidable.is_some() {
             res_manager.update_non_fungible_data(
                 &self.cdp_id,
-                "liquidable",
-                self.cdp_data.liquidable.clone(),
+                "key_image_url",
+                self.cdp_data.key_image_url.clone(),
             );
-            updated = true;
         }
 
-        if updated {
-            let position_count = self.cdp_data.collaterals.len()
-                + self.cdp_data.loans.len();
+        // Logger::debug(format!("Save CDP: {:#?}", self)); 
+
+        Ok(())
+    }
+
+    /// Move the positions of a CDP not migrated yet from the NFT data into the market storage,
+    /// keeping the update timestamp, and add the CDP to the indexes of its pools
+    /// 
+    /// *Params*
+    /// - `res_manager``: The CDP resource manager
+    /// - `cdp_positions`: Reference to the on-chain key-value storage where key is the CDP id
+    ///                 and value is the CDP positions
+    /// - `pool_cdp_indexes`: Reference to the on-chain key-value storage where key is asset resource
+    ///                 address and value is the index of the CDPs with positions in the pool
+    /// 
+    /// *Output*
+    /// Whether the CDP was migrated
+    pub fn migrate_positions(
+        &self,
+        res_manager: &ResourceManager,
+        cdp_positions: &KeyValueStore<NonFungibleLocalId, CDPPositions>,
+        pool_cdp_indexes: &KeyValueStore<ResourceAddress, PoolCDPIndex>,
+    ) -> bool {
+        if !self.legacy_positions {
+            return false;
+        }
+
+        self._add_to_pool_cdp_indexes(pool_cdp_indexes);
+
+        self._store_positions(res_manager, cdp_positions, self.cdp_data.updated_at);
+
+        true
+    }
 
-            assert!(position_count as u8 <= max_cdp_position);
+    fn _add_to_pool_cdp_indexes(
+        &self,
+        pool_cdp_indexes: &KeyValueStore<ResourceAddress, PoolCDPIndex>,
+    ) {
+        for pool_res_address in self
+            .cdp_data
+            .collaterals
+            .keys()
+            .chain(self.cdp_data.loans.keys())
+        {
+            if let Some(mut pool_cdp_index) = pool_cdp_indexes.get_mut(pool_res_address) {
+                pool_cdp_index.insert(&self.cdp_id);
+            }
+        }
+    }
 
+    fn _store_positions(
+        &self,
+        res_manager: &ResourceManager,
+        cdp_positions: &KeyValueStore<NonFungibleLocalId, CDPPositions>,
+        updated_at: i64,
+    ) {
+        cdp_positions.insert(
+            self.cdp_id.clone(),
+            CDPPositions {
+                collaterals: self.cdp_data.collaterals.clone(),
+                loans: self.cdp_data.loans.clone(),
+                disabled_collaterals: self.disabled_collaterals.clone(),
+                liquidation_preference: self.liquidation_preference.clone(),
+                liquidable: self.cdp_data.liquidable,
+                liquidation_deadline: self.liquidation_deadline,
+                updated_at,
+            },
+        );
+
+        if self.legacy_positions {
             res_manager.update_non_fungible_data(
                 &self.cdp_id,
-                "updated_at",
-                Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch,
+                "collaterals",
+                IndexMap::<ResourceAddress, PreciseDecimal>::new(),
+            );
+            res_manager.update_non_fungible_data(
+                &self.cdp_id,
+                "loans",
+                IndexMap::<ResourceAddress, PreciseDecimal>::new(),
             );
         }
-
-        // Logger::debug(format!("Save CDP: {:#?}", self)); 
-
-        Ok(())
     }
 
     // local methods
@@ -279,6 +1092,8 @@ impl WrappedCDPData {
             if *entry < ZERO_EPSILON.into() {
                 map.remove(&key);
             }
+        } else if units < PreciseDecimal::ZERO {
+            return Err(format!("Position {:?} not found, units cannot be negative", key));
         } else {
             map.insert(key, units);
         }
@@ -1,9 +1,12 @@
-use super::{cdp_data::*, liquidation_threshold::*, pool_state::*};
+use super::{cdp_data::*, liquidation_threshold::*, market_config::*, pool_context::*, pool_state::*};
 use scrypto::prelude::*;
 
 // Amount at which a position is considered zeroed
 pub const ZERO_EPSILON: Decimal = dec!(0.000000001);
 
+// Number of bisection steps used to solve the repayment bringing a CDP back to the target health factor
+pub const LIQUIDATION_TARGET_ITERATIONS: u8 = 24;
+
 /// Type of position
 pub enum LoadPositionType {
     Collateral,
@@ -75,6 +78,8 @@ pub struct ExtendedCollateralPositionData {
     pub asset_type: u8,
     /// Liquidation threshold information from pool state
     pub liquidation_threshold: LiquidationThreshold,
+    /// Max borrow LTV information from pool state, lower than the liquidation threshold
+    pub borrow_ltv: LiquidationThreshold,
     /// Liquidation bonus rate information from pool state
     pub liquidation_bonus_rate: Decimal,
     /// The position data
@@ -127,10 +132,14 @@ pub struct ExtendedLoanPositionData {
     pub asset_type: u8,
     /// Ratio to be repaid before the loan can be closed
     pub loan_close_factor: Decimal,
+    /// Whether the wind-down of the pool is over. Such a loan can be fully liquidated whatever the CDP health
+    pub wound_down: bool,
     /// The position data
     pub data: PositionData,
     /// The amount of collateral needed to sustain this loan so that the position is not liquidable
     pub discounted_collateral_value: Decimal,
+    /// The amount of collateral needed to sustain this loan when increasing the CDP risk
+    pub discounted_borrow_collateral_value: Decimal,
 }
 impl ExtendedLoanPositionData {
     /// Load the `ExtendedLoanPositionData` data
@@ -168,26 +177,40 @@ impl ExtendedLoanPositionData {
     ) -> Result<(), String> {
         self.data.update_data(self.price)?;
 
-        self.discounted_collateral_value = collateral_positions.iter().fold(
-            Decimal::ZERO,
-            |mut discounted_collateral_value, (_, collateral_position)| {
-                let mut liquidation_threshold =
-                    collateral_position.liquidation_threshold.get_ratio(
-                        collateral_position.pool_res_address,
-                        collateral_position.asset_type,
-                        self.pool_res_address,
-                        self.asset_type,
+        (self.discounted_collateral_value, self.discounted_borrow_collateral_value) =
+            collateral_positions.iter().fold(
+                (Decimal::ZERO, Decimal::ZERO),
+                |(mut discounted_collateral_value, mut discounted_borrow_collateral_value),
+                 (_, collateral_position)| {
+                    let max_ratio = Decimal::ONE - collateral_position.liquidation_bonus_rate;
+
+                    let liquidation_threshold = max_ratio.min(
+                        collateral_position.liquidation_threshold.get_ratio(
+                            collateral_position.pool_res_address,
+                            collateral_position.asset_type,
+                            self.pool_res_address,
+                            self.asset_type,
+                        ),
                     );
 
-                liquidation_threshold = (Decimal::ONE - collateral_position.liquidation_bonus_rate)
-                    .min(liquidation_threshold);
+                    let borrow_ltv = liquidation_threshold.min(
+                        collateral_position.borrow_ltv.get_ratio(
+                            collateral_position.pool_res_address,
+                            collateral_position.asset_type,
+                            self.pool_res_address,
+                            self.asset_type,
+                        ),
+                    );
 
-                discounted_collateral_value += liquidation_threshold
-                    * collateral_position.data.value;
+                    discounted_collateral_value +=
+                        liquidation_threshold * collateral_position.data.value;
 
-                discounted_collateral_value
-            },
-        );
+                    discounted_borrow_collateral_value +=
+                        borrow_ltv * collateral_position.data.value;
+
+                    (discounted_collateral_value, discounted_borrow_collateral_value)
+                },
+            );
 
         Ok(())
     }
@@ -208,6 +231,10 @@ pub struct CDPHealthChecker {
     /// The loan to value ratio is the ratio between the total loan value, and the total collateral value.
     pub total_loan_to_value_ratio: Decimal,
 
+    /// The loan to value ratio computed with the max borrow LTV instead of the liquidation threshold.
+    /// Risk-increasing actions require it to be lower than 1
+    pub total_borrow_loan_to_value_ratio: Decimal,
+
     /// Max loan value in the CDP that can be repaid
     pub self_closable_loan_value: Decimal,
     
@@ -216,27 +243,57 @@ pub struct CDPHealthChecker {
 
     /// IndexMap of all the loan positions in the CDP. the key is the resource address of borrowed the asset
     pub loan_positions: IndexMap<ResourceAddress, ExtendedLoanPositionData>,
+
+    /// Resource addresses of the CDP pools having a stale price
+    pub stale_price_pools: Vec<ResourceAddress>,
 }
 impl CDPHealthChecker {
-    /// Constructor with pool state update side effect
+    /// Constructor with pool state update side effect. Pools already refreshed in the context
+    /// are not refreshed again
     /// 
     /// *Params*
     /// - `wrapped_cdp_data``: The CDP to check
-    /// - `load_type`: The load type
     /// - `pool_state`: Reference to the on-chain key-value storage where key is asset resource 
     ///                 address and value is the current pool state
+    /// - `pool_context`: The context of the current call
     /// 
     /// *Output*
     /// `CDPHealthChecker`
     pub fn new(
         wrapped_cdp_data: &WrappedCDPData,
         pool_states: &mut KeyValueStore<ResourceAddress, LendingPoolState>,
+        pool_context: &mut PoolContext,
     ) -> CDPHealthChecker {
-        Self::update_interest_and_price(wrapped_cdp_data, pool_states).expect("Error updating interest and price for CDP health checker");
+        pool_context
+            .refresh_cdp_pools(wrapped_cdp_data, pool_states)
+            .expect("Error updating interest and price for CDP health checker");
         Self::create_health_checker(wrapped_cdp_data, pool_states)
             .expect("Error creating CDP health checker")
     }
 
+    /// Fallible constructor with pool state update side effect, returning the error instead
+    /// of panicking. Pools already refreshed in the context are not refreshed again
+    /// 
+    /// *Params*
+    /// - `wrapped_cdp_data``: The CDP to check
+    /// - `pool_state`: Reference to the on-chain key-value storage where key is asset resource 
+    ///                 address and value is the current pool state
+    /// - `pool_context`: The context of the current call
+    /// 
+    /// *Output*
+    /// `CDPHealthChecker`
+    /// 
+    /// *Error*
+    /// - If the update of a pool state or the creation of the health checker fails
+    pub fn try_new(
+        wrapped_cdp_data: &WrappedCDPData,
+        pool_states: &mut KeyValueStore<ResourceAddress, LendingPoolState>,
+        pool_context: &mut PoolContext,
+    ) -> Result<CDPHealthChecker, String> {
+        pool_context.refresh_cdp_pools(wrapped_cdp_data, pool_states)?;
+        Self::create_health_checker(wrapped_cdp_data, pool_states)
+    }
+
     /// Constructor without pool state update side effect
     /// 
     /// *Params*
@@ -255,32 +312,6 @@ impl CDPHealthChecker {
             .expect("Error creating CDP health checker")
     }
 
-    fn update_interest_and_price(wrapped_cdp_data: &WrappedCDPData, pool_states: &mut KeyValueStore<ResourceAddress, LendingPoolState>) -> Result<(), String> {
-        let cdp_data: CollaterizedDebtPositionData = wrapped_cdp_data.get_data();
-
-        // Load the collateral positions
-        cdp_data
-            .collaterals
-            .iter()
-            .for_each(|(pool_res_address, _)| {
-                if let Some(mut pool_state) = pool_states.get_mut(pool_res_address) {
-                    pool_state.update_interest_and_price(None).expect("update interest and price");
-                }
-            });
-
-        // Load the loan positions
-        cdp_data
-            .loans
-            .iter()
-            .for_each(|(pool_res_address, _)| {
-                if let Some(mut pool_state) = pool_states.get_mut(pool_res_address) {
-                    pool_state.update_interest_and_price(None).expect("update interest and price");
-                }
-            });
-
-        Ok(())
-    }
-
     fn create_health_checker(
         wrapped_cdp_data: &WrappedCDPData,
         pool_states: &KeyValueStore<ResourceAddress, LendingPoolState>,
@@ -293,7 +324,9 @@ impl CDPHealthChecker {
             loan_positions: IndexMap::new(),
             total_loan_value: Decimal::ZERO,
             total_loan_to_value_ratio: Decimal::ZERO,
+            total_borrow_loan_to_value_ratio: Decimal::ZERO,
             self_closable_loan_value: Decimal::ZERO,
+            stale_price_pools: Vec::new(),
         };
 
         // Function to load collateral or loan positions
@@ -308,6 +341,10 @@ impl CDPHealthChecker {
 
             let pool_state = wrapped_pool_state.unwrap();
 
+            if pool_state.price_stale && !extended_cdp.stale_price_pools.contains(pool_res_address) {
+                extended_cdp.stale_price_pools.push(*pool_res_address);
+            }
+
             match position_type {
                 LoadPositionType::Collateral => {
                     let collateral_position =
@@ -327,10 +364,11 @@ impl CDPHealthChecker {
             Ok(())
         };
 
-        // Load the collateral positions
+        // Load the collateral positions, excluding the deposited assets not used as collateral
         cdp_data
             .collaterals
             .iter()
+            .filter(|(pool_res_address, _)| wrapped_cdp_data.is_used_as_collateral(**pool_res_address))
             .try_for_each(|(pool_res_address, units)| {
                 load_data(pool_res_address, *units, LoadPositionType::Collateral)
             })?;
@@ -349,31 +387,41 @@ impl CDPHealthChecker {
 
     /// Perform health check
     /// 
+    /// *Output*
+    /// - The LTV ratio of the CDP
+    /// 
     /// *Error*
     /// - If the health check fails
-    pub fn check_cdp(&mut self) -> Result<(), String> {
+    pub fn check_cdp(&mut self) -> Result<Decimal, String> {
+        self.check_price_validity()?;
+
         self.update_health_check_data()?;
 
-        if self.total_loan_to_value_ratio > Decimal::ONE {
+        if self.total_borrow_loan_to_value_ratio > Decimal::ONE {
             return Err(format!(
-                "total_loan_to_value_ratio need to be lower than 1, but is {}.",
-                self.total_loan_to_value_ratio
+                "total_borrow_loan_to_value_ratio need to be lower than 1, but is {}.",
+                self.total_borrow_loan_to_value_ratio
             ));
         }
 
         //
 
-        Ok(())
+        Ok(self.total_loan_to_value_ratio)
     }
 
-    /// Check if CDP can be liquidated
+    /// Check if CDP can be liquidated, either because it is unhealthy or because it has loans in a
+    /// pool whose wind-down is over
     /// 
     /// *Error*
     /// - If the CDP is not liquidable
     pub fn can_liquidate(&mut self) -> Result<(), String> {
+        self.check_price_validity()?;
+
         self.update_health_check_data()?;
 
-        if self.total_loan_to_value_ratio <= Decimal::ONE {
+        if self.total_loan_to_value_ratio <= Decimal::ONE
+            && self.get_wound_down_loan_value() == Decimal::ZERO
+        {
             return Err(format!(
                 "CDP can not be liquidated: LTV ratio of {} is lower than 1",
                 self.total_loan_to_value_ratio
@@ -383,6 +431,30 @@ impl CDPHealthChecker {
         Ok(())
     }
 
+    /// Getter of the value of the loans in pools whose wind-down is over
+    pub fn get_wound_down_loan_value(&self) -> Decimal {
+        self.loan_positions
+            .values()
+            .filter(|loan| loan.wound_down)
+            .fold(Decimal::ZERO, |total, loan| total + loan.data.value)
+    }
+
+    /// Check that no pool of the CDP has a stale price. Health checks relying on a stale price
+    /// are refused, so only risk-reducing operations are possible
+    /// 
+    /// *Error*
+    /// - If the price of a pool of the CDP is stale
+    pub fn check_price_validity(&self) -> Result<(), String> {
+        if let Some(pool_res_address) = self.stale_price_pools.first() {
+            return Err(format!(
+                "Price of {:?} is stale, only risk-reducing operations are allowed",
+                pool_res_address
+            ));
+        }
+
+        Ok(())
+    }
+
     fn get_collateral_position(
         &mut self,
         pool_state: &KeyValueEntryRef<'_, LendingPoolState>,
@@ -398,7 +470,8 @@ impl CDPHealthChecker {
                     pool_res_address: pool_state.pool_res_address,
                     asset_type: pool_state.pool_config.asset_type,
                     liquidation_bonus_rate: pool_state.pool_config.liquidation_bonus_rate,
-                    liquidation_threshold: pool_state.liquidation_threshold.clone(),
+                    liquidation_threshold: pool_state.get_liquidation_threshold(),
+                    borrow_ltv: pool_state.get_borrow_ltv(),
                     price: pool_state.price,
                     data: PositionData {
                         units: pdec!(0),
@@ -435,6 +508,7 @@ impl CDPHealthChecker {
                     asset_type: pool_state.pool_config.asset_type,
 
                     loan_close_factor: pool_state.pool_config.loan_close_factor,
+                    wound_down: pool_state.is_wound_down(),
                     data: PositionData {
                         units: pdec!(0),
                         amount: dec!(0),
@@ -443,6 +517,7 @@ impl CDPHealthChecker {
                     },
 
                     discounted_collateral_value: Decimal::ZERO,
+                    discounted_borrow_collateral_value: Decimal::ZERO,
                 },
             );
         };
@@ -469,14 +544,16 @@ impl CDPHealthChecker {
 
         let (
             total_weighted_discounted_collateral_value,
+            total_weighted_discounted_borrow_collateral_value,
             total_loan_value,
             self_closable_loan_value,
         ) = self.loan_positions.iter_mut().fold(
-            Ok((Decimal::ZERO, Decimal::ZERO, Decimal::ZERO)),
-            |result: Result<(Decimal, Decimal, Decimal), String>, (_, extended_loan)| {
+            Ok((Decimal::ZERO, Decimal::ZERO, Decimal::ZERO, Decimal::ZERO)),
+            |result: Result<(Decimal, Decimal, Decimal, Decimal), String>, (_, extended_loan)| {
                 result.and_then(
                     |(
                         mut total_weighted_discounted_collateral_value,
+                        mut total_weighted_discounted_borrow_collateral_value,
                         mut total_loan_value,
                         mut self_closable_loan_value,
                     )| {
@@ -493,13 +570,22 @@ impl CDPHealthChecker {
                         total_weighted_discounted_collateral_value +=
                             extended_loan.discounted_collateral_value * position_total_loan_value;
 
+                        total_weighted_discounted_borrow_collateral_value +=
+                            extended_loan.discounted_borrow_collateral_value * position_total_loan_value;
+
                         //
 
-                        self_closable_loan_value +=
-                            extended_loan.data.value * extended_loan.loan_close_factor;
+                        let loan_close_factor = if extended_loan.wound_down {
+                            Decimal::ONE
+                        } else {
+                            extended_loan.loan_close_factor
+                        };
+
+                        self_closable_loan_value += extended_loan.data.value * loan_close_factor;
 
                         Ok((
                             total_weighted_discounted_collateral_value,
+                            total_weighted_discounted_borrow_collateral_value,
                             total_loan_value,
                             self_closable_loan_value
                         ))
@@ -508,6 +594,120 @@ impl CDPHealthChecker {
             },
         )?;
 
+        self.self_closable_loan_value = self_closable_loan_value;
+
+        self.total_loan_value = total_loan_value;
+        self.total_loan_to_value_ratio = Self::_get_loan_to_value_ratio(
+            total_weighted_discounted_collateral_value,
+            total_loan_value,
+        );
+        self.total_borrow_loan_to_value_ratio = Self::_get_loan_to_value_ratio(
+            total_weighted_discounted_borrow_collateral_value,
+            total_loan_value,
+        );
+
+        Ok(())
+    }
+
+    /// Update the loan value that can be repaid in liquidation, according to the target health
+    /// factor of the market. The CDP can be fully liquidated under the full liquidation health
+    /// factor or under the dust value. Otherwise, the liquidable value is the minimum repayment
+    /// bringing the CDP back to the target health factor, assuming loans are repaid and collaterals
+    /// are seized in proportion of their value. Without target health factor, the loan close factors
+    /// apply and the value is left unchanged. The loans of wound down pools can always be fully
+    /// repaid, and are the only liquidable loans of a healthy CDP. Must be called after
+    /// `update_health_check_data`
+    /// 
+    /// *Params*
+    /// - `market_config`: The market configuration
+    /// 
+    /// *Error*
+    /// - If update of the internal state fails
+    pub fn update_liquidable_value(&mut self, market_config: &MarketConfig) -> Result<(), String> {
+        // A healthy CDP is only liquidable for its loans in wound down pools
+        if self.total_loan_to_value_ratio <= Decimal::ONE {
+            self.self_closable_loan_value = self.get_wound_down_loan_value();
+            return Ok(());
+        }
+
+        let target_health_factor = match market_config.target_health_factor {
+            Some(target_health_factor) => target_health_factor,
+            None => return Ok(()),
+        };
+
+        let below_full_liquidation_health_factor = market_config.full_liquidation_health_factor
+            > Decimal::ZERO
+            && self.total_loan_to_value_ratio
+                > Decimal::ONE / market_config.full_liquidation_health_factor;
+
+        // Value of the loans the collaterals can repay, once the liquidation bonus is deducted
+        let redeemable_collateral_value = self.collateral_positions.values().fold(
+            Decimal::ZERO,
+            |total, collateral| {
+                total + collateral.data.value / (Decimal::ONE + collateral.liquidation_bonus_rate)
+            },
+        );
+
+        if below_full_liquidation_health_factor
+            || self.total_loan_value < market_config.liquidation_dust_value
+            || redeemable_collateral_value == Decimal::ZERO
+        {
+            self.self_closable_loan_value = self.total_loan_value;
+            return Ok(());
+        }
+
+        let target_loan_to_value_ratio = Decimal::ONE / target_health_factor;
+        let seized_to_repaid_ratio = self.total_loan_value / redeemable_collateral_value;
+
+        // Bisection on the repaid share of the loans. The resulting LTV ratio is monotonic in it
+        let mut low = Decimal::ZERO;
+        let mut high = Decimal::ONE;
+
+        for _ in 0..LIQUIDATION_TARGET_ITERATIONS {
+            let repaid_ratio = (low + high) / 2;
+
+            let loan_to_value_ratio =
+                self._simulate_liquidation(repaid_ratio, seized_to_repaid_ratio)?;
+
+            if loan_to_value_ratio <= target_loan_to_value_ratio {
+                high = repaid_ratio;
+            } else {
+                low = repaid_ratio;
+            }
+        }
+
+        self.self_closable_loan_value =
+            (self.total_loan_value * high).max(self.get_wound_down_loan_value());
+
+        Ok(())
+    }
+
+    fn _simulate_liquidation(
+        &self,
+        repaid_ratio: Decimal,
+        seized_to_repaid_ratio: Decimal,
+    ) -> Result<Decimal, String> {
+        let seized_ratio = (repaid_ratio * seized_to_repaid_ratio).min(Decimal::ONE);
+
+        let mut simulated = self.clone();
+
+        simulated.loan_positions.values_mut().for_each(|loan| {
+            loan.data.units = loan.data.units * (Decimal::ONE - repaid_ratio);
+        });
+
+        simulated.collateral_positions.values_mut().for_each(|collateral| {
+            collateral.data.units = collateral.data.units * (Decimal::ONE - seized_ratio);
+        });
+
+        simulated.update_health_check_data()?;
+
+        Ok(simulated.total_loan_to_value_ratio)
+    }
+
+    fn _get_loan_to_value_ratio(
+        total_weighted_discounted_collateral_value: Decimal,
+        total_loan_value: Decimal,
+    ) -> Decimal {
         // Calculate total discounted collateral value which is the sum of all discounted collateral value
         let total_discounted_collateral_value = if total_loan_value < ZERO_EPSILON {
             Decimal::ZERO
@@ -515,26 +715,17 @@ impl CDPHealthChecker {
             total_weighted_discounted_collateral_value / total_loan_value
         };
 
-        let total_loan_to_value_ratio: Decimal;
-
         if total_discounted_collateral_value == Decimal::ZERO {
             // In case the total discounted collateral value is zero,
             // we set the LTV to zero if the total loan value is also zero and to max if the total loan value is not zero
             if total_loan_value < ZERO_EPSILON {
-                total_loan_to_value_ratio = Decimal::ZERO;
+                Decimal::ZERO
             } else {
                 // This happens when there is no collateral at all
-                total_loan_to_value_ratio = Decimal::MAX;
-            };
+                Decimal::MAX
+            }
         } else {
-            total_loan_to_value_ratio = total_loan_value / total_discounted_collateral_value;
+            total_loan_value / total_discounted_collateral_value
         }
-
-        self.self_closable_loan_value = self_closable_loan_value;
-
-        self.total_loan_value = total_loan_value;
-        self.total_loan_to_value_ratio = total_loan_to_value_ratio;
-
-        Ok(())
     }
 }
@@ -0,0 +1,91 @@
+use scrypto::prelude::*;
+
+/// Index of the CDPs holding collaterals or loans in a lending pool.
+/// CDP ids are stored in contiguous slots so that the index can be paginated.
+/// When a CDP is removed, the CDP in the last slot is moved into the freed slot, and the
+/// version of the index is incremented so that paginated scans can detect the move.
+#[derive(ScryptoSbor)]
+pub struct PoolCDPIndex {
+    /// Map the slots to the CDP ids
+    pub cdp_ids: KeyValueStore<u64, NonFungibleLocalId>,
+    /// Map the CDP ids to their slots
+    pub slots: KeyValueStore<NonFungibleLocalId, u64>,
+    /// Number of indexed CDPs
+    pub count: u64,
+    /// Incremented on each removal, as a removal can move a CDP to a slot already scanned
+    pub version: u64,
+}
+
+impl PoolCDPIndex {
+    /// Constructor of an empty index
+    pub fn new() -> PoolCDPIndex {
+        PoolCDPIndex {
+            cdp_ids: KeyValueStore::new(),
+            slots: KeyValueStore::new(),
+            count: 0,
+            version: 0,
+        }
+    }
+
+    /// Whether the CDP is in the index
+    pub fn contains(&self, cdp_id: &NonFungibleLocalId) -> bool {
+        self.slots.get(cdp_id).is_some()
+    }
+
+    /// Add a CDP to the index, nothing is done if the CDP is already indexed
+    ///
+    /// *Params*
+    /// - `cdp_id`: The id of the CDP to add
+    pub fn insert(&mut self, cdp_id: &NonFungibleLocalId) {
+        if self.contains(cdp_id) {
+            return;
+        }
+
+        self.cdp_ids.insert(self.count, cdp_id.clone());
+        self.slots.insert(cdp_id.clone(), self.count);
+        self.count += 1;
+    }
+
+    /// Remove a CDP from the index, nothing is done if the CDP is not indexed
+    ///
+    /// *Params*
+    /// - `cdp_id`: The id of the CDP to remove
+    pub fn remove(&mut self, cdp_id: &NonFungibleLocalId) {
+        let slot = match self.slots.get(cdp_id) {
+            Some(slot) => *slot,
+            None => return,
+        };
+
+        let last_slot = self.count - 1;
+
+        if slot != last_slot {
+            let last_cdp_id = self.cdp_ids.get(&last_slot).unwrap().clone();
+            self.cdp_ids.insert(slot, last_cdp_id.clone());
+            self.slots.insert(last_cdp_id, slot);
+        }
+
+        self.cdp_ids.remove(&last_slot);
+        self.slots.remove(cdp_id);
+        self.count -= 1;
+        self.version += 1;
+    }
+
+    /// Get a page of the indexed CDPs
+    ///
+    /// *Params*
+    /// - `cursor`: The first slot to read
+    /// - `limit`: The maximum number of CDP ids to return
+    ///
+    /// *Output*
+    /// - The CDP ids of the page
+    /// - The cursor of the next page, `None` if the end of the index is reached
+    pub fn list(&self, cursor: u64, limit: u64) -> (Vec<NonFungibleLocalId>, Option<u64>) {
+        let end = cursor.saturating_add(limit).min(self.count);
+
+        let cdp_ids = (cursor..end)
+            .map(|slot| self.cdp_ids.get(&slot).unwrap().clone())
+            .collect();
+
+        (cdp_ids, if end < self.count { Some(end) } else { None })
+    }
+}
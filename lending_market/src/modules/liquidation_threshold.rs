@@ -73,7 +73,95 @@ impl LiquidationThreshold {
         loan_res_address: ResourceAddress,
         loan_asset_type: u8,
     ) -> Decimal {
-        if loan_res_address == collateral_res_address {
+        self._resolve_ratio(
+            collateral_res_address,
+            collateral_asset_type,
+            Some(loan_res_address),
+            loan_asset_type,
+        )
+    }
+
+    /// Check that all the ratios are strictly lower than the ones of another configuration, for
+    /// any loan of a given collateral, and keep at least a relative margin below them.
+    /// Zero ratios are accepted on both sides.
+    /// Used to keep the max borrow LTV below the liquidation threshold
+    /// 
+    /// *Params*
+    /// - `other`: The configuration to compare with
+    /// - `margin`: The minimum relative margin to keep below the ratios of the other configuration
+    /// - `collateral_res_address`: The collateral resource address
+    /// - `collateral_asset_type`: The collateral asset type
+    /// 
+    /// *Error*
+    /// - If a ratio is not strictly lower than the one of the other configuration reduced by the margin
+    pub fn check_below(
+        &self,
+        other: &LiquidationThreshold,
+        margin: Decimal,
+        collateral_res_address: ResourceAddress,
+        collateral_asset_type: u8,
+    ) -> Result<(), String> {
+        // Every path of the ratio resolution is covered by the configured entries, the collateral
+        // itself, and a resource and an asset type without entry
+        let mut loan_res_addresses: Vec<Option<ResourceAddress>> = self
+            .resource
+            .keys()
+            .chain(other.resource.keys())
+            .map(|res_address| Some(*res_address))
+            .collect();
+        loan_res_addresses.push(Some(collateral_res_address));
+        loan_res_addresses.push(None);
+
+        let mut loan_asset_types: Vec<u8> = self
+            .asset_type
+            .keys()
+            .chain(other.asset_type.keys())
+            .copied()
+            .collect();
+        loan_asset_types.push(collateral_asset_type);
+        if let Some(asset_type) = (0..=u8::MAX).find(|asset_type| !loan_asset_types.contains(asset_type)) {
+            loan_asset_types.push(asset_type);
+        }
+
+        for loan_res_address in loan_res_addresses.iter() {
+            for loan_asset_type in loan_asset_types.iter() {
+                let ratio = self._resolve_ratio(
+                    collateral_res_address,
+                    collateral_asset_type,
+                    *loan_res_address,
+                    *loan_asset_type,
+                );
+
+                let other_ratio = other._resolve_ratio(
+                    collateral_res_address,
+                    collateral_asset_type,
+                    *loan_res_address,
+                    *loan_asset_type,
+                );
+
+                let max_ratio = other_ratio * (Decimal::ONE - margin);
+
+                if (ratio >= other_ratio || ratio > max_ratio) && ratio != Decimal::ZERO {
+                    return Err(format!(
+                        "Ratio {} must be lower than {} and at most {}",
+                        ratio, other_ratio, max_ratio
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the ratio, a `None` loan resource address standing for a resource without entry
+    fn _resolve_ratio(
+        &self,
+        collateral_res_address: ResourceAddress,
+        collateral_asset_type: u8,
+        loan_res_address: Option<ResourceAddress>,
+        loan_asset_type: u8,
+    ) -> Decimal {
+        if loan_res_address == Some(collateral_res_address) {
             if self.identical_resource.is_some() {
                 return self.identical_resource.unwrap();
             } else {
@@ -85,9 +173,8 @@ impl LiquidationThreshold {
             return self.identical_asset_type.unwrap();
         }
 
-        self.resource
-            .get(&loan_res_address)
-            .copied()
+        loan_res_address
+            .and_then(|loan_res_address| self.resource.get(&loan_res_address).copied())
             .unwrap_or_else(|| {
                 self.asset_type
                     .get(&loan_asset_type)
@@ -134,6 +221,28 @@ impl LiquidationThreshold {
         Ok(())
     }
 
+    /// Get a copy of the liquidation threshold with all the ratios multiplied by a factor
+    /// 
+    /// *Params*
+    /// - `factor`: The factor to apply, between 0 and 1
+    pub fn scale(&self, factor: Decimal) -> LiquidationThreshold {
+        LiquidationThreshold {
+            identical_resource: self.identical_resource.map(|value| value * factor),
+            identical_asset_type: self.identical_asset_type.map(|value| value * factor),
+            resource: self
+                .resource
+                .iter()
+                .map(|(res_address, value)| (*res_address, *value * factor))
+                .collect(),
+            asset_type: self
+                .asset_type
+                .iter()
+                .map(|(asset_type, value)| (*asset_type, *value * factor))
+                .collect(),
+            default_value: self.default_value * factor,
+        }
+    }
+
     fn set_resource_entry(&mut self, resource: ResourceAddress, threshold: Option<Decimal>) {
         if let Some(threshold) = threshold {
             self.resource.insert(resource, threshold);
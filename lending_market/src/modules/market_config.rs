@@ -6,18 +6,51 @@ pub enum UpdateMarketConfigInput {
     MaxCDPPosition(u8),
     MaxLiquidableValue(Decimal),
     LiquidationDexSwapRate(Decimal),
+    TargetHealthFactor(Option<Decimal>),
+    FullLiquidationHealthFactor(Decimal),
+    LiquidationDustValue(Decimal),
+    LiquidationGracePeriod(Option<i64>),
+    HardLiquidationHealthFactor(Decimal),
+    MinWithdrawalRequestValue(Decimal),
+    BorrowLTVMargin(Decimal),
+    ModerateHealthBandLTV(Decimal),
+    RiskyHealthBandLTV(Decimal),
 }
 
 /// The lending market configuration
 #[derive(ScryptoSbor, Clone)]
 pub struct MarketConfig {
-    /// Max positions per CDP per user
+    /// Max positions per CDP. Positions are not stored in the NFT data anymore, so the limit
+    /// can be raised up to the number of listed pools
     pub max_cdp_position: u8,
     /// Max liquidable value to take out of collateral when partially liquidating a CDP (rate)
     pub max_liquidable_value: Decimal,
     /// Dex swap efficiency, where 1 means the whole collateral is converted to loan to liquidate, but often this is lesser than 1 so 
     /// a certain tolerance on the fact that not all collateral is used to extinguish the loan is given this way
-    pub liquidation_dex_swap_rate: Decimal
+    pub liquidation_dex_swap_rate: Decimal,
+    /// Health factor a liquidation brings the CDP back to. If not set, the liquidable value of
+    /// each loan is capped by the loan close factor of its pool
+    pub target_health_factor: Option<Decimal>,
+    /// Health factor under which a CDP can be fully liquidated, when a target health factor is set
+    pub full_liquidation_health_factor: Decimal,
+    /// Total loan value under which a CDP can be fully liquidated, when a target health factor is set
+    pub liquidation_dust_value: Decimal,
+    /// Delay in seconds given to a CDP at risk to be topped up before it can be liquidated.
+    /// If not set, CDPs can be liquidated as soon as their health factor is lower than 1
+    pub liquidation_grace_period: Option<i64>,
+    /// Health factor under which a CDP can be liquidated without grace period
+    pub hard_liquidation_health_factor: Decimal,
+    /// Minimum value of the pool units locked by a withdrawal request, preventing the queue
+    /// from being filled with dust requests
+    pub min_withdrawal_request_value: Decimal,
+    /// Minimum relative margin kept between the max borrow LTV and the liquidation threshold of
+    /// a pool. Pools get a max borrow LTV derived from their liquidation threshold with this margin
+    /// when listed or when the margin is updated, a zero margin lets them borrow up to the threshold
+    pub borrow_ltv_margin: Decimal,
+    /// LTV ratio from which a CDP is shown in the moderate health band
+    pub moderate_health_band_ltv: Decimal,
+    /// LTV ratio from which a CDP is shown in the risky health band, close to liquidation
+    pub risky_health_band_ltv: Decimal,
 }
 impl MarketConfig {
     /// Perform a check on the market configuration
@@ -34,6 +67,37 @@ impl MarketConfig {
         if self.liquidation_dex_swap_rate < dec!(0) || self.liquidation_dex_swap_rate > dec!(1) {
             return Err("Liquidation dex swap rate value must be in range 0..1".into());
         }
+        if let Some(target_health_factor) = self.target_health_factor {
+            if target_health_factor <= dec!(1) {
+                return Err("Target health factor must be greater than 1".into());
+            }
+        }
+        if self.full_liquidation_health_factor < dec!(0) || self.full_liquidation_health_factor > dec!(1) {
+            return Err("Full liquidation health factor must be in range 0..1".into());
+        }
+        if self.liquidation_dust_value < dec!(0) {
+            return Err("Liquidation dust value must be non-negative".into());
+        }
+        if let Some(liquidation_grace_period) = self.liquidation_grace_period {
+            if liquidation_grace_period <= 0 {
+                return Err("Liquidation grace period must be greater than 0".into());
+            }
+        }
+        if self.hard_liquidation_health_factor < dec!(0) || self.hard_liquidation_health_factor > dec!(1) {
+            return Err("Hard liquidation health factor must be in range 0..1".into());
+        }
+        if self.min_withdrawal_request_value < dec!(0) {
+            return Err("Min withdrawal request value must be non-negative".into());
+        }
+        if self.borrow_ltv_margin < dec!(0) || self.borrow_ltv_margin >= dec!(1) {
+            return Err("Borrow LTV margin must be in range 0..1, excluding 1".into());
+        }
+        if self.moderate_health_band_ltv <= dec!(0)
+            || self.moderate_health_band_ltv >= self.risky_health_band_ltv
+            || self.risky_health_band_ltv > dec!(1)
+        {
+            return Err("Health band LTVs must verify 0 < moderate < risky <= 1".into());
+        }
 
         Ok(())
     }
@@ -56,6 +120,33 @@ impl MarketConfig {
             UpdateMarketConfigInput::LiquidationDexSwapRate(liquidation_dex_swap_rate) => {
                 self.liquidation_dex_swap_rate = liquidation_dex_swap_rate;
             }
+            UpdateMarketConfigInput::TargetHealthFactor(target_health_factor) => {
+                self.target_health_factor = target_health_factor;
+            }
+            UpdateMarketConfigInput::FullLiquidationHealthFactor(full_liquidation_health_factor) => {
+                self.full_liquidation_health_factor = full_liquidation_health_factor;
+            }
+            UpdateMarketConfigInput::LiquidationDustValue(liquidation_dust_value) => {
+                self.liquidation_dust_value = liquidation_dust_value;
+            }
+            UpdateMarketConfigInput::LiquidationGracePeriod(liquidation_grace_period) => {
+                self.liquidation_grace_period = liquidation_grace_period;
+            }
+            UpdateMarketConfigInput::HardLiquidationHealthFactor(hard_liquidation_health_factor) => {
+                self.hard_liquidation_health_factor = hard_liquidation_health_factor;
+            }
+            UpdateMarketConfigInput::MinWithdrawalRequestValue(min_withdrawal_request_value) => {
+                self.min_withdrawal_request_value = min_withdrawal_request_value;
+            }
+            UpdateMarketConfigInput::BorrowLTVMargin(borrow_ltv_margin) => {
+                self.borrow_ltv_margin = borrow_ltv_margin;
+            }
+            UpdateMarketConfigInput::ModerateHealthBandLTV(moderate_health_band_ltv) => {
+                self.moderate_health_band_ltv = moderate_health_band_ltv;
+            }
+            UpdateMarketConfigInput::RiskyHealthBandLTV(risky_health_band_ltv) => {
+                self.risky_health_band_ltv = risky_health_band_ltv;
+            }
         }
 
         self.check()?;
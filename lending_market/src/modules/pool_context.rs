@@ -0,0 +1,83 @@
+use super::{cdp_data::*, pool_state::*};
+use scrypto::prelude::*;
+
+/// Context of a market method call, keeping track of the pools whose price and interest are
+/// already refreshed. Each pool is refreshed at most once per call, whatever the number of
+/// positions and health checks involving it
+#[derive(Default)]
+pub struct PoolContext {
+    /// Resource addresses of the refreshed pools
+    refreshed: IndexSet<ResourceAddress>,
+}
+
+impl PoolContext {
+    /// Constructor of an empty context
+    pub fn new() -> PoolContext {
+        PoolContext::default()
+    }
+
+    /// Whether the pool is already refreshed in this context
+    ///
+    /// *Params*
+    /// - `pool_res_address`: The pool resource address
+    pub fn is_refreshed(&self, pool_res_address: &ResourceAddress) -> bool {
+        self.refreshed.contains(pool_res_address)
+    }
+
+    /// Refresh price and interest of a pool, unless already done in this context.
+    /// Bypassing the debounce always forces the refresh
+    ///
+    /// *Params*
+    /// - `pool_state`: The pool state to refresh
+    /// - `bypass_debounce`: Whether to bypass the price and the interest update debounce
+    ///
+    /// *Error*
+    /// - If the update of the pool state fails
+    pub fn refresh(
+        &mut self,
+        pool_state: &mut LendingPoolState,
+        bypass_debounce: Option<(bool, bool)>,
+    ) -> Result<(), String> {
+        if bypass_debounce.is_none() && self.is_refreshed(&pool_state.pool_res_address) {
+            return Ok(());
+        }
+
+        pool_state.update_interest_and_price(bypass_debounce)?;
+
+        self.refreshed.insert(pool_state.pool_res_address);
+
+        Ok(())
+    }
+
+    /// Refresh all the pools of a CDP not yet refreshed in this context
+    ///
+    /// *Params*
+    /// - `wrapped_cdp_data`: The CDP
+    /// - `pool_states`: Reference to the on-chain key-value storage where key is asset resource
+    ///                  address and value is the current pool state
+    ///
+    /// *Error*
+    /// - If the update of a pool state fails
+    pub fn refresh_cdp_pools(
+        &mut self,
+        wrapped_cdp_data: &WrappedCDPData,
+        pool_states: &mut KeyValueStore<ResourceAddress, LendingPoolState>,
+    ) -> Result<(), String> {
+        let cdp_data: CollaterizedDebtPositionData = wrapped_cdp_data.get_data();
+
+        cdp_data
+            .collaterals
+            .keys()
+            .chain(cdp_data.loans.keys())
+            .try_for_each(|pool_res_address| {
+                if self.is_refreshed(pool_res_address) {
+                    return Ok(());
+                }
+
+                match pool_states.get_mut(pool_res_address) {
+                    Some(mut pool_state) => self.refresh(&mut pool_state, None),
+                    None => Ok(()),
+                }
+            })
+    }
+}
@@ -0,0 +1,101 @@
+use scrypto::prelude::*;
+
+/// Duration of a history bucket, in seconds
+pub const HISTORY_BUCKET_PERIOD: i64 = 60 * 60;
+
+/// Maximum number of history buckets kept on ledger, older buckets are removed
+pub const HISTORY_MAX_BUCKETS: i64 = 24 * 30;
+
+/// Snapshot of the pool state, taken at each interest update
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct PoolSnapshot {
+    /// Timestamp of the snapshot
+    pub timestamp: i64,
+    /// The pool utilization
+    pub utilization: Decimal,
+    /// The yearly borrow interest rate
+    pub borrow_rate: Decimal,
+    /// The yearly supply interest rate, net of the protocol fee
+    pub supply_rate: Decimal,
+    /// The total deposit amount
+    pub total_deposit: Decimal,
+    /// The total loan amount
+    pub total_loan: Decimal,
+    /// The asset price
+    pub price: Decimal,
+}
+
+/// Bounded history of pool snapshots, grouped in buckets of `HISTORY_BUCKET_PERIOD` seconds.
+/// Only the last snapshot of each bucket is kept.
+#[derive(ScryptoSbor)]
+pub struct PoolHistory {
+    /// Map the bucket index to the last snapshot taken in the bucket
+    pub snapshots: KeyValueStore<i64, PoolSnapshot>,
+    /// Index of the oldest bucket kept
+    pub first_bucket: i64,
+    /// Index of the most recent bucket, `None` if no snapshot was recorded yet
+    pub last_bucket: Option<i64>,
+}
+
+impl PoolHistory {
+    /// Constructor of an empty history
+    pub fn new() -> PoolHistory {
+        PoolHistory {
+            snapshots: KeyValueStore::new(),
+            first_bucket: 0,
+            last_bucket: None,
+        }
+    }
+
+    /// Record a snapshot, replacing the previous one of the same bucket and removing the buckets
+    /// falling out of the history window
+    ///
+    /// *Params*
+    /// - `snapshot`: The snapshot to record
+    pub fn record(&mut self, snapshot: PoolSnapshot) {
+        let bucket = snapshot.timestamp / HISTORY_BUCKET_PERIOD;
+
+        if let Some(last_bucket) = self.last_bucket {
+            if bucket < last_bucket {
+                return;
+            }
+
+            let new_first_bucket = (bucket - HISTORY_MAX_BUCKETS + 1).max(self.first_bucket);
+
+            // Recorded buckets are all between first and last bucket
+            for old_bucket in self.first_bucket..new_first_bucket.min(last_bucket + 1) {
+                self.snapshots.remove(&old_bucket);
+            }
+
+            self.first_bucket = new_first_bucket;
+        } else {
+            self.first_bucket = bucket;
+        }
+
+        self.snapshots.insert(bucket, snapshot);
+        self.last_bucket = Some(bucket);
+    }
+
+    /// Getter of the snapshots taken between two timestamps
+    ///
+    /// *Params*
+    /// - `from`: The start timestamp, inclusive
+    /// - `to`: The end timestamp, inclusive
+    ///
+    /// *Output*
+    /// The snapshots, ordered by timestamp
+    pub fn get(&self, from: i64, to: i64) -> Vec<PoolSnapshot> {
+        let last_bucket = match self.last_bucket {
+            Some(last_bucket) => last_bucket,
+            None => return vec![],
+        };
+
+        let from_bucket = (from / HISTORY_BUCKET_PERIOD).max(self.first_bucket);
+        let to_bucket = (to / HISTORY_BUCKET_PERIOD).min(last_bucket);
+
+        (from_bucket..=to_bucket)
+            .filter_map(|bucket| self.snapshots.get(&bucket).map(|snapshot| snapshot.clone()))
+            .filter(|snapshot| snapshot.timestamp >= from && snapshot.timestamp <= to)
+            .collect()
+    }
+}
@@ -1,7 +1,11 @@
+use super::cdp_data::CDPHealthBand;
 use super::cdp_health_checker::ZERO_EPSILON;
 use super::operation_status::*;
 use crate::lending_market::lending_market::*;
-use crate::modules::{interest_strategy::*, liquidation_threshold::*, pool_config::*, utils::*};
+use crate::modules::{
+    interest_strategy::*, liquidation_threshold::*, pool_config::*, pool_history::*, utils::*,
+    withdrawal_queue::*,
+};
 use scrypto::blueprints::consensus_manager::*;
 use scrypto::prelude::*;
 
@@ -26,6 +30,162 @@ pub struct LendingPoolUpdatedEvent {
     pub amount: Decimal,
 }
 
+/// Source of the protocol revenue of a pool
+#[derive(ScryptoSbor, Clone, Debug)]
+pub enum RevenueSource {
+    /// Share of the interests retained by the protocol
+    Interest,
+    /// Share of the seized collaterals retained by the protocol
+    Liquidation,
+    /// Share of the flashloan fees retained by the protocol
+    Flashloan,
+}
+
+/// Cumulative protocol revenue of a pool, in pool asset amount
+#[derive(ScryptoSbor, Clone, Debug, Default)]
+pub struct PoolRevenue {
+    /// Revenue coming from interests
+    pub interest: Decimal,
+    /// Revenue coming from liquidations
+    pub liquidation: Decimal,
+    /// Revenue coming from flashloans
+    pub flashloan: Decimal,
+    /// Revenue already collected with `collect_reserve`
+    pub collected: Decimal,
+    /// Revenue moved back into the pool liquidity with `cover_shortfall`
+    pub covered: Decimal,
+}
+
+impl PoolRevenue {
+    /// Account new revenue
+    /// 
+    /// *Params*
+    /// - `source`: The source of the revenue
+    /// - `amount`: The amount of revenue
+    pub fn record(&mut self, source: RevenueSource, amount: Decimal) {
+        match source {
+            RevenueSource::Interest => self.interest += amount,
+            RevenueSource::Liquidation => self.liquidation += amount,
+            RevenueSource::Flashloan => self.flashloan += amount,
+        }
+    }
+
+    /// Getter of the total revenue, from all the sources
+    pub fn total(&self) -> Decimal {
+        self.interest + self.liquidation + self.flashloan
+    }
+
+    /// Getter of the revenue still to collect, either in the reserve or still in the pool liquidity
+    pub fn pending(&self) -> Decimal {
+        self.total() - self.collected - self.covered
+    }
+}
+
+/// Policy applied to the reserve of a pool
+#[derive(ScryptoSbor, Clone, Debug, Default)]
+pub struct ReservePolicy {
+    /// Amount of asset kept in the reserve by `collect_reserve`, available to cover shortfalls
+    pub min_buffer: Decimal,
+    /// Whether the reserve is deposited in the pool as pool units, earning yield until collected
+    pub auto_deposit: bool,
+}
+
+/// Event occurring when the reserve of a pool is collected
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ReserveCollectedEvent {
+    /// The pool resource address
+    pub pool_res_address: ResourceAddress,
+    /// The collected amount
+    pub amount: Decimal,
+    /// The asset price at collection time
+    pub price: Decimal,
+    /// The pool revenue after the collection
+    pub revenue: PoolRevenue,
+}
+
+/// Listing lifecycle of a pool
+#[derive(ScryptoSbor, Clone, Debug, PartialEq)]
+pub enum ListingStatus {
+    /// The pool is fully operational
+    Listed,
+    /// No new contributions, collaterals or borrows are accepted
+    Frozen,
+    /// Frozen, and the liquidation threshold of the asset used as collateral is linearly reduced to zero.
+    /// Once the wind-down is over, the loans of the asset can be liquidated whatever the CDP health
+    WindingDown {
+        /// Timestamp of the wind-down start
+        started_at: i64,
+        /// Duration of the wind-down, in seconds
+        duration: i64,
+    },
+    /// The pool has no CDP positions left and is removed from the listed assets,
+    /// remaining pool units can still be redeemed
+    Retired,
+}
+
+/// Conversion of the pool units of a replaced pool component into the pool units of the new one
+#[derive(ScryptoSbor)]
+pub struct PoolUnitMigration {
+    /// The pool resource address
+    pub pool_res_address: ResourceAddress,
+    /// Amount of new pool units given for each replaced pool unit
+    pub conversion_rate: PreciseDecimal,
+    /// New pool units waiting to be claimed by the holders of the replaced pool units
+    pub pool_units: Vault,
+    /// Replaced pool units collected from their holders
+    pub replaced_pool_units: Vault,
+    /// Emptied vaults of the replaced pool units, kept since vaults cannot be dropped
+    pub replaced_vaults: Vec<Vault>,
+}
+
+/// Roles of a listed asset resource manager that could be used against the protocol, like
+/// recalling or freezing the collaterals held by the market
+pub const AUDITED_ASSET_ROLES: [&str; 9] = [
+    "recaller",
+    "recaller_updater",
+    "freezer",
+    "freezer_updater",
+    "minter_updater",
+    "withdrawer",
+    "withdrawer_updater",
+    "depositor",
+    "depositor_updater",
+];
+
+/// Event occurring when an audit detects a change of the risk-relevant roles of a listed asset
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct AssetRolesChangedEvent {
+    /// The pool resource address
+    pub pool_res_address: ResourceAddress,
+    /// The names of the changed roles
+    pub changed_roles: Vec<String>,
+}
+
+/// Event occurring when the price of a pool enters or leaves the stale mode
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct PoolPriceStatusChangedEvent {
+    /// The pool resource address
+    pub pool_res_address: ResourceAddress,
+    /// Whether the price is stale
+    pub stale: bool,
+}
+
+/// Getter of the risk-relevant roles of an asset
+///
+/// *Params*
+/// - `res_address`: The asset resource address
+///
+/// *Output*
+/// Map of the role names to their access rule, `None` if the role is not defined
+pub fn get_asset_roles(res_address: ResourceAddress) -> IndexMap<String, Option<AccessRule>> {
+    let res_manager = ResourceManager::from_address(res_address);
+
+    AUDITED_ASSET_ROLES
+        .iter()
+        .map(|role| (role.to_string(), res_manager.get_role(role)))
+        .collect()
+}
+
 /// Market stats of a pool
 #[derive(ScryptoSbor)]
 pub struct MarketStatsPool {
@@ -66,6 +226,30 @@ pub struct MarketStatsAllPools {
     pub market_stats_pools: Vec<MarketStatsPool>,
 }
 
+/// Consolidated view of the positions of an account, across CDPs and free pool units
+#[derive(ScryptoSbor)]
+pub struct AccountSummary {
+    /// Value of the pool units held outside of the CDPs
+    pub free_supplied_value: Decimal,
+    /// Value of the pool units locked as collateral in the CDPs
+    pub collateral_value: Decimal,
+    /// Total supplied value, free and collateralized
+    pub total_supplied_value: Decimal,
+    /// Total borrowed value
+    pub total_borrowed_value: Decimal,
+    /// Supply APY of the account, weighted by the supplied value of each pool
+    pub supply_apy: PreciseDecimal,
+    /// Borrow APY of the account, weighted by the borrowed value of each pool
+    pub borrow_apy: PreciseDecimal,
+    /// Net APY, the yearly supply earnings minus the borrow costs over the net supplied value.
+    /// Zero if the net supplied value is not positive
+    pub net_apy: PreciseDecimal,
+    /// Highest LTV ratio among the CDPs, the CDP closest to liquidation drives the health of the account
+    pub max_loan_to_value_ratio: Decimal,
+    /// Health band derived from the highest LTV ratio
+    pub health_band: CDPHealthBand,
+}
+
 /// The pool state
 #[derive(ScryptoSbor)]
 pub struct LendingPoolState {
@@ -89,6 +273,10 @@ pub struct LendingPoolState {
     /// The timestamp when the price update happened
     pub price_updated_at: i64,
 
+    /// Whether the oracle price is too old. The last known price is kept and only risk-reducing
+    /// operations are allowed until a valid price is received
+    pub price_stale: bool,
+
     /// The interest rate
     pub interest_rate: Decimal,
 
@@ -120,6 +308,11 @@ pub struct LendingPoolState {
     /// The pool liquidation threshold
     pub liquidation_threshold: LiquidationThreshold,
 
+    /// The pool max borrow LTV, kept lower than the liquidation threshold.
+    /// The liquidation threshold is used if not set, which only happens when the pool
+    /// is created with a zero borrow LTV margin
+    pub borrow_ltv: Option<LiquidationThreshold>,
+
     /// The pool config
     pub pool_config: PoolConfig,
 
@@ -131,6 +324,27 @@ pub struct LendingPoolState {
 
     /// The total reserve amount from pool operation fees
     pub total_reserved_amount: Decimal,
+
+    /// Bounded history of the pool snapshots, recorded at each interest update
+    pub history: PoolHistory,
+
+    /// Cumulative protocol revenue, by source
+    pub revenue: PoolRevenue,
+
+    /// Policy applied to the reserve
+    pub reserve_policy: ReservePolicy,
+
+    /// Vault holding the pool units of the reserve deposited in the pool
+    pub reserve_pool_units: Vault,
+
+    /// Listing lifecycle status
+    pub listing_status: ListingStatus,
+
+    /// Risk-relevant roles of the asset, as recorded at listing or at the last audit
+    pub asset_roles: IndexMap<String, Option<AccessRule>>,
+
+    /// Queue of the withdrawal requests waiting for liquidity
+    pub withdrawal_queue: WithdrawalQueue,
 }
 
 impl LendingPoolState {
@@ -151,6 +365,119 @@ impl LendingPoolState {
         Ok(())
     }
 
+    /// Getter of the liquidation threshold in effect, linearly reduced to zero while the pool is wound down
+    pub fn get_liquidation_threshold(&self) -> LiquidationThreshold {
+        self._scale_to_listing_status(&self.liquidation_threshold)
+    }
+
+    /// Getter of the max borrow LTV in effect, reduced like the liquidation threshold while the
+    /// pool is wound down
+    pub fn get_borrow_ltv(&self) -> LiquidationThreshold {
+        self._scale_to_listing_status(
+            self.borrow_ltv
+                .as_ref()
+                .unwrap_or(&self.liquidation_threshold),
+        )
+    }
+
+    fn _scale_to_listing_status(&self, ratios: &LiquidationThreshold) -> LiquidationThreshold {
+        match self.listing_status {
+            ListingStatus::WindingDown { started_at, duration } => {
+                let now = Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch;
+                let elapsed = (now - started_at).max(0).min(duration);
+
+                ratios.scale(Decimal::ONE - Decimal::from(elapsed) / Decimal::from(duration))
+            }
+            ListingStatus::Retired => ratios.scale(Decimal::ZERO),
+            _ => ratios.clone(),
+        }
+    }
+
+    /// Perform a check on the price validity, required by the risk-increasing operations
+    /// 
+    /// *Error*
+    /// - If the price is stale
+    pub fn check_price_validity(&self) -> Result<(), String> {
+        if self.price_stale {
+            return Err(format!(
+                "Price of {:?} is stale, only risk-reducing operations are allowed",
+                self.pool_res_address
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Whether the wind-down of the pool is completed
+    pub fn is_wound_down(&self) -> bool {
+        match self.listing_status {
+            ListingStatus::WindingDown { started_at, duration } => {
+                Clock::current_time(TimePrecision::Second).seconds_since_unix_epoch
+                    >= started_at + duration
+            }
+            _ => false,
+        }
+    }
+
+    /// Compare the risk-relevant roles of the asset with the recorded ones. If any role changed,
+    /// borrow, add collateral, remove collateral and liquidation are disabled until an admin enables
+    /// them again. The recorded roles are kept as the baseline until an admin confirms the new ones,
+    /// so every audit keeps reporting the change until then
+    ///
+    /// *Output*
+    /// The names of the changed roles
+    ///
+    /// *Error*
+    /// - If the operating status update fails
+    pub fn audit_asset_roles(&mut self) -> Result<Vec<String>, String> {
+        let current_roles = get_asset_roles(self.pool_res_address);
+
+        let changed_roles: Vec<String> = current_roles
+            .iter()
+            .filter(|(role, rule)| self.asset_roles.get(*role) != Some(*rule))
+            .map(|(role, _)| role.clone())
+            .collect();
+
+        if changed_roles.is_empty() {
+            return Ok(changed_roles);
+        }
+
+        for value in [
+            OperatingService::Borrow,
+            OperatingService::AddCollateral,
+            OperatingService::RemoveCollateral,
+            OperatingService::Liquidation,
+        ] {
+            self.operating_status.update(value, false, true)?;
+        }
+
+        Runtime::emit_event(AssetRolesChangedEvent {
+            pool_res_address: self.pool_res_address,
+            changed_roles: changed_roles.clone(),
+        });
+
+        Ok(changed_roles)
+    }
+
+    /// Record the current risk-relevant roles of the asset as the new baseline.
+    /// The operating services disabled by the audit are not enabled again
+    ///
+    /// *Output*
+    /// The names of the roles that changed from the previous baseline
+    pub fn confirm_asset_roles(&mut self) -> Vec<String> {
+        let current_roles = get_asset_roles(self.pool_res_address);
+
+        let changed_roles: Vec<String> = current_roles
+            .iter()
+            .filter(|(role, rule)| self.asset_roles.get(*role) != Some(*rule))
+            .map(|(role, _)| role.clone())
+            .collect();
+
+        self.asset_roles = current_roles;
+
+        changed_roles
+    }
+
     /// Getter of the current loan unit ratio
     /// 
     /// *Error*
@@ -188,6 +515,36 @@ impl LendingPoolState {
         Ok(ratio)
     }
 
+    /// Getter of the price of a pool unit, derived from the asset price. The unit to asset ratio of
+    /// the pool component and the one tracked by the pool state are compared and the higher one,
+    /// giving the lower unit price, is used: a donation inflating one of the two ratios cannot
+    /// raise the pool unit price
+    ///
+    /// *Output*
+    /// The `PriceInfo` of the pool unit, timestamped with the asset price update
+    ///
+    /// *Error*
+    /// - If the deposit unit ratio indicates an unhealthy pool
+    pub fn get_pool_unit_price(&self) -> Result<PriceInfo, String> {
+        let pool_unit_ratio = self.pool.get_pool_unit_ratio();
+        let deposit_unit_ratio = self.get_deposit_unit_ratio()?;
+
+        let unit_ratio = if pool_unit_ratio > deposit_unit_ratio {
+            pool_unit_ratio
+        } else {
+            deposit_unit_ratio
+        };
+
+        let price = (self.price / unit_ratio)
+            .checked_truncate(RoundingMode::ToZero)
+            .ok_or("Error while calculating pool unit price")?;
+
+        Ok(PriceInfo {
+            timestamp: self.price_updated_at,
+            price,
+        })
+    }
+
     /* CORE LOGIC AND UTILITY METHODS */
 
     /// Proxy of the pool component contribute method
@@ -251,6 +608,67 @@ impl LendingPoolState {
         redeemed
     }
 
+    /// Maximum amount of the given pool units that the available liquidity allows to redeem.
+    /// Computed from the pool state, so that it does not rely on a method missing from the
+    /// deployed pool package
+    /// 
+    /// *Params*
+    /// - `units`: pool units to redeem
+    pub fn get_max_redeemable_units(&self, units: Decimal) -> Decimal {
+        let (pooled_amount, _) = self.pool.get_pooled_amount();
+
+        let max_units = (PreciseDecimal::from(pooled_amount) * self.pool.get_pool_unit_ratio())
+            .checked_truncate(RoundingMode::ToZero)
+            .unwrap_or(Decimal::ZERO);
+
+        units.min(max_units)
+    }
+
+    /// Redeem as much pool units as the available liquidity allows
+    /// 
+    /// *Params*
+    /// - `pool_units`: pool units to redeem
+    /// 
+    /// *Output*
+    /// - The redeemed assets
+    /// - The pool units which could not be redeemed
+    pub fn redeem_partial_proxy(&mut self, mut pool_units: Bucket) -> (Bucket, Bucket) {
+        let redeemable_units = self.get_max_redeemable_units(pool_units.amount());
+
+        if redeemable_units == Decimal::ZERO {
+            return (Bucket::new(self.pool_res_address), pool_units);
+        }
+
+        let redeemed = self.redeem_proxy(pool_units.take(redeemable_units), false);
+
+        (redeemed, pool_units)
+    }
+
+    /// Pay the withdrawal requests waiting in the queue with the available liquidity, in
+    /// first-come-first-served order. At most `WITHDRAWAL_QUEUE_MAX_PROCESSED` requests are paid
+    /// and `WITHDRAWAL_QUEUE_MAX_SKIPPED` paid or cancelled slots are skipped
+    pub fn process_withdrawal_queue(&mut self) {
+        let mut skip_budget = WITHDRAWAL_QUEUE_MAX_SKIPPED;
+
+        for _ in 0..WITHDRAWAL_QUEUE_MAX_PROCESSED {
+            let (request_id, remaining_units) = match self.withdrawal_queue.front(&mut skip_budget) {
+                Some(request) => request,
+                None => break,
+            };
+
+            let redeemable_units = self.get_max_redeemable_units(remaining_units);
+
+            if redeemable_units == Decimal::ZERO {
+                break;
+            }
+
+            let pool_units = self.withdrawal_queue.take_units(request_id, redeemable_units);
+            let redeemed = self.redeem_proxy(pool_units, true);
+
+            self.withdrawal_queue.pay(request_id, redeemed);
+        }
+    }
+
     /// Add pool uints to the Vault of locked collaterals
     /// 
     /// *Params*
@@ -393,6 +811,8 @@ impl LendingPoolState {
         self.pool
             .protected_deposit(payment, DepositType::FromTemporaryUse);
 
+        self.process_withdrawal_queue();
+
         Runtime::emit_event(LendingPoolUpdatedEvent {
             pool_res_address: self.pool_res_address,
             event_type: LendingPoolUpdatedEventType::LoanState,
@@ -457,6 +877,32 @@ impl LendingPoolState {
         }
     }
 
+    /// Getter of the yearly supply interest rate, net of the protocol fee
+    pub fn get_supply_rate(&self) -> Decimal {
+        self.get_pool_utilization()
+            * self.interest_rate
+            * (1 - self.pool_config.protocol_interest_fee_rate)
+    }
+
+    /// Getter of the effective supply and borrow APY, with interests compounded every second
+    /// 
+    /// *Output*
+    /// - The supply APY
+    /// - The borrow APY
+    pub fn get_supply_and_borrow_apy(&self) -> (PreciseDecimal, PreciseDecimal) {
+        let second_per_year = 31536000;
+
+        let deposit_rate = self.get_supply_rate();
+
+        let supply_apy_term: PreciseDecimal = PreciseDecimal::ONE + deposit_rate / second_per_year;
+        let supply_apy = supply_apy_term.checked_powi(second_per_year).unwrap() - dec!(1);
+
+        let borrow_apy_term: PreciseDecimal = pdec!(1) + self.interest_rate / second_per_year;
+        let borrow_apy = borrow_apy_term.checked_powi(second_per_year).unwrap() - dec!(1);
+
+        (supply_apy, borrow_apy)
+    }
+
     /// Drains the interest fee from the pool, as much as available at the time of calling. The rest is accounted
     /// to be drained at a later moment.
     pub fn allocate_reserve(&mut self) {
@@ -464,6 +910,121 @@ impl LendingPoolState {
         let reserve = self.pool.protected_withdraw(reserve_amount, WithdrawType::LiquidityWithdrawal, WithdrawStrategy::Rounded(RoundingMode::ToNearestMidpointToEven));
         self.total_reserved_amount -= reserve.amount();
         self.reserve.put(reserve);
+        self.deposit_reserve();
+    }
+
+    /// Getter of the reserve amount, including the reserve deposited in the pool
+    pub fn get_reserve_amount(&self) -> Decimal {
+        let deposited_amount = (self.reserve_pool_units.amount() / self.pool.get_pool_unit_ratio())
+            .checked_truncate(RoundingMode::ToZero)
+            .unwrap();
+
+        self.reserve.amount() + deposited_amount
+    }
+
+    /// Deposit the reserve vault content in the pool if the reserve policy requires it
+    pub fn deposit_reserve(&mut self) {
+        if !self.reserve_policy.auto_deposit || self.reserve.amount() == Decimal::ZERO {
+            return;
+        }
+
+        let assets = self.reserve.take_all();
+        let amount = assets.amount();
+
+        let pool_units = self.pool.contribute(assets);
+        self._update_deposit_unit(amount)
+            .expect("update deposit unit for reserve deposit");
+
+        self.reserve_pool_units.put(pool_units);
+    }
+
+    /// Redeem the reserve deposited in the pool back into the reserve vault, as much as the
+    /// available liquidity allows
+    pub fn withdraw_reserve(&mut self) {
+        let available_units = self.pool.get_pooled_amount().0 * self.pool.get_pool_unit_ratio();
+
+        let units = available_units
+            .checked_truncate(RoundingMode::ToZero)
+            .unwrap()
+            .min(self.reserve_pool_units.amount());
+
+        if units == Decimal::ZERO {
+            return;
+        }
+
+        let redeemed = self.pool.redeem(self.reserve_pool_units.take(units));
+        self._update_deposit_unit(-redeemed.amount())
+            .expect("update deposit unit for reserve withdraw");
+
+        self.reserve.put(redeemed);
+    }
+
+    /// Take the reserve content above the policy buffer, accounting it as collected revenue
+    /// 
+    /// *Params*
+    /// - `min_amount`: Minimum amount to collect, nothing is collected below it
+    /// 
+    /// *Output*
+    /// The collected assets, `None` if the collectable reserve is lower than the minimum amount
+    pub fn collect_reserve(&mut self, min_amount: Decimal) -> Option<Bucket> {
+        self.withdraw_reserve();
+
+        let collectable_amount =
+            (self.reserve.amount() - self.reserve_policy.min_buffer).max(Decimal::ZERO);
+
+        if collectable_amount < min_amount {
+            self.deposit_reserve();
+            return None;
+        }
+
+        let reserve = self.reserve.take_advanced(
+            collectable_amount,
+            WithdrawStrategy::Rounded(RoundingMode::ToZero),
+        );
+        self.revenue.collected += reserve.amount();
+
+        self.deposit_reserve();
+
+        Runtime::emit_event(ReserveCollectedEvent {
+            pool_res_address: self.pool_res_address,
+            amount: reserve.amount(),
+            price: self.price,
+            revenue: self.revenue.clone(),
+        });
+
+        Some(reserve)
+    }
+
+    /// Move reserve back into the pool liquidity, without minting pool units, in order to
+    /// cover bad debt or rounding deficits. The total deposit is not changed since the liquidity
+    /// only fills the missing amount
+    /// 
+    /// *Params*
+    /// - `amount`: The amount to move, capped to the reserve amount
+    /// 
+    /// *Output*
+    /// The amount moved into the pool liquidity
+    pub fn cover_shortfall(&mut self, amount: Decimal) -> Decimal {
+        self.withdraw_reserve();
+
+        let assets = self.reserve.take_advanced(
+            amount.min(self.reserve.amount()),
+            WithdrawStrategy::Rounded(RoundingMode::ToZero),
+        );
+        let covered_amount = assets.amount();
+
+        self.pool.protected_deposit(assets, DepositType::LiquiditySupply);
+        self.revenue.covered += covered_amount;
+
+        self.deposit_reserve();
+
+        Runtime::emit_event(LendingPoolUpdatedEvent {
+            pool_res_address: self.pool_res_address,
+            event_type: LendingPoolUpdatedEventType::DepositState,
+            amount: covered_amount,
+        });
+
+        covered_amount
     }
 
     /* PRIVATE UTILITY METHODS */
@@ -471,11 +1032,22 @@ impl LendingPoolState {
     fn _update_price(&mut self, now: i64) -> Result<(), String> {
         let price_feed_result = get_price(self.price_feed_comp, self.pool_res_address)?;
 
-        // Handle price update too old
-        if ((now - price_feed_result.timestamp) / SECOND_PER_MINUTE)
-            >= self.pool_config.price_expiration_period
-        {
-            return Err("Price info is too old".to_string());
+        // Handle price update too old: keep the last known price and enter the stale mode.
+        // The price update time is left unchanged so that the next call retries
+        let price_stale = ((now - price_feed_result.timestamp) / SECOND_PER_MINUTE)
+            >= self.pool_config.price_expiration_period;
+
+        if price_stale != self.price_stale {
+            self.price_stale = price_stale;
+
+            Runtime::emit_event(PoolPriceStatusChangedEvent {
+                pool_res_address: self.pool_res_address,
+                stale: price_stale,
+            });
+        }
+
+        if price_stale {
+            return Ok(());
         }
 
         self.price_updated_at = now;
@@ -509,6 +1081,7 @@ impl LendingPoolState {
             .checked_truncate(RoundingMode::ToNearestMidpointToEven)
             .unwrap());
         self.total_reserved_amount += reserve_delta;
+        self.revenue.record(RevenueSource::Interest, reserve_delta);
 
         let mut accrued_interest_amount = if new_total_loan_amount - self.total_loan < PreciseDecimal::ZERO { new_total_loan_amount } else { new_total_loan_amount - self.total_loan };
 
@@ -541,6 +1114,20 @@ impl LendingPoolState {
             // Logger::debug(format!("INTEREST update: now {:?} - pool_utilization {:?} - interest_rate {:?}", self.interest_updated_at, self.pool_utilization, self.interest_rate));
         }
 
+        self.history.record(PoolSnapshot {
+            timestamp: now,
+            utilization: self.pool_utilization,
+            borrow_rate: self.interest_rate,
+            supply_rate: self.get_supply_rate(),
+            total_deposit: self.total_deposit
+                .checked_truncate(RoundingMode::ToNearestMidpointToEven)
+                .unwrap(),
+            total_loan: self.total_loan
+                .checked_truncate(RoundingMode::ToNearestMidpointToEven)
+                .unwrap(),
+            price: self.price,
+        });
+
         Runtime::emit_event(LendingPoolUpdatedEvent {
             pool_res_address: self.pool_res_address,
             event_type: LendingPoolUpdatedEventType::Interest,
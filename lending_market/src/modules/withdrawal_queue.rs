@@ -0,0 +1,207 @@
+use scrypto::prelude::*;
+
+/// Maximum number of withdrawal requests paid on each repayment, bounding the repayment cost
+pub const WITHDRAWAL_QUEUE_MAX_PROCESSED: u64 = 10;
+
+/// Maximum number of paid or cancelled queue slots skipped on each repayment, bounding the cost
+/// of a queue filled with cancelled requests. Remaining slots are skipped on the next repayments
+pub const WITHDRAWAL_QUEUE_MAX_SKIPPED: u64 = 20;
+
+/// A queued withdrawal request
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct WithdrawalRequest {
+    /// Pool units locked and not yet redeemed
+    pub remaining_units: Decimal,
+    /// Redeemed amount of asset waiting to be claimed
+    pub claimable_amount: Decimal,
+    /// Position of the request in the queue
+    pub position: u64,
+}
+
+/// First-come-first-served queue of the withdrawal requests of a pool, paid from incoming repayments
+#[derive(ScryptoSbor)]
+pub struct WithdrawalQueue {
+    /// Map the request id to the request
+    pub requests: KeyValueStore<u64, WithdrawalRequest>,
+    /// Map the queue position to the request id
+    pub positions: KeyValueStore<u64, u64>,
+    /// Position of the first request waiting to be paid
+    pub head: u64,
+    /// Position following the last request
+    pub tail: u64,
+    /// Pool units locked by the requests
+    pub locked_units: Vault,
+    /// Redeemed assets waiting to be claimed
+    pub claimable: Vault,
+}
+
+impl WithdrawalQueue {
+    /// Constructor of an empty queue
+    ///
+    /// *Params*
+    /// - `pool_unit_res_address`: The pool unit resource address
+    /// - `pool_res_address`: The pool resource address
+    pub fn new(
+        pool_unit_res_address: ResourceAddress,
+        pool_res_address: ResourceAddress,
+    ) -> WithdrawalQueue {
+        WithdrawalQueue {
+            requests: KeyValueStore::new(),
+            positions: KeyValueStore::new(),
+            head: 0,
+            tail: 0,
+            locked_units: Vault::new(pool_unit_res_address),
+            claimable: Vault::new(pool_res_address),
+        }
+    }
+
+    /// Add a request at the end of the queue
+    ///
+    /// *Params*
+    /// - `request_id`: The request id
+    /// - `pool_units`: The pool units to redeem
+    ///
+    /// *Error*
+    /// - If the pool units are empty
+    pub fn push(&mut self, request_id: u64, pool_units: Bucket) -> Result<(), String> {
+        if pool_units.amount() <= Decimal::ZERO {
+            return Err("Pool units must be positive".into());
+        }
+
+        self.requests.insert(
+            request_id,
+            WithdrawalRequest {
+                remaining_units: pool_units.amount(),
+                claimable_amount: Decimal::ZERO,
+                position: self.tail,
+            },
+        );
+        self.positions.insert(self.tail, request_id);
+        self.tail += 1;
+
+        self.locked_units.put(pool_units);
+
+        Ok(())
+    }
+
+    /// Getter of the first request waiting to be paid. Paid and cancelled requests are removed
+    /// from the front of the queue, within the skip budget
+    ///
+    /// *Params*
+    /// - `skip_budget`: Number of slots that can still be skipped, decreased for each skipped slot
+    ///
+    /// *Output*
+    /// The request id and its remaining pool units, `None` if no request is waiting or if the
+    /// skip budget is exhausted
+    pub fn front(&mut self, skip_budget: &mut u64) -> Option<(u64, Decimal)> {
+        while self.head < self.tail {
+            let remaining_request = self.positions.get(&self.head).and_then(|request_id| {
+                let request_id = *request_id;
+                self.requests
+                    .get(&request_id)
+                    .map(|request| (request_id, request.remaining_units))
+                    .filter(|(_, remaining_units)| *remaining_units > Decimal::ZERO)
+            });
+
+            if remaining_request.is_some() {
+                return remaining_request;
+            }
+
+            if *skip_budget == 0 {
+                return None;
+            }
+
+            *skip_budget -= 1;
+            self.positions.remove(&self.head);
+            self.head += 1;
+        }
+
+        None
+    }
+
+    /// Take locked pool units of a request to redeem them
+    ///
+    /// *Params*
+    /// - `request_id`: The request id
+    /// - `units`: The amount of pool units to take
+    ///
+    /// *Output*
+    /// The pool units
+    pub fn take_units(&mut self, request_id: u64, units: Decimal) -> Bucket {
+        let mut request = self.requests.get_mut(&request_id).unwrap();
+
+        request.remaining_units -= units;
+
+        self.locked_units.take(units)
+    }
+
+    /// Record the redeemed assets of a request, available to be claimed
+    ///
+    /// *Params*
+    /// - `request_id`: The request id
+    /// - `assets`: The redeemed assets
+    pub fn pay(&mut self, request_id: u64, assets: Bucket) {
+        let mut request = self.requests.get_mut(&request_id).unwrap();
+
+        request.claimable_amount += assets.amount();
+
+        self.claimable.put(assets);
+    }
+
+    /// Claim the redeemed assets of a request. The request is removed once fully paid
+    ///
+    /// *Params*
+    /// - `request_id`: The request id
+    ///
+    /// *Output*
+    /// - The claimed assets
+    /// - Whether the request is fully paid
+    ///
+    /// *Error*
+    /// - If the request is not found
+    pub fn claim(&mut self, request_id: u64) -> Result<(Bucket, bool), String> {
+        let (assets, fully_paid) = {
+            let mut request = self
+                .requests
+                .get_mut(&request_id)
+                .ok_or("Withdrawal request not found")?;
+
+            let assets = self.claimable.take(request.claimable_amount);
+            request.claimable_amount = Decimal::ZERO;
+
+            (assets, request.remaining_units == Decimal::ZERO)
+        };
+
+        if fully_paid {
+            self.requests.remove(&request_id);
+        }
+
+        Ok((assets, fully_paid))
+    }
+
+    /// Cancel a request, returning its redeemed assets and its remaining pool units.
+    /// The queue slot of the request is freed
+    ///
+    /// *Params*
+    /// - `request_id`: The request id
+    ///
+    /// *Output*
+    /// - The redeemed assets
+    /// - The remaining pool units
+    ///
+    /// *Error*
+    /// - If the request is not found
+    pub fn cancel(&mut self, request_id: u64) -> Result<(Bucket, Bucket), String> {
+        let request = self
+            .requests
+            .remove(&request_id)
+            .ok_or("Withdrawal request not found")?;
+
+        self.positions.remove(&request.position);
+
+        Ok((
+            self.claimable.take(request.claimable_amount),
+            self.locked_units.take(request.remaining_units),
+        ))
+    }
+}
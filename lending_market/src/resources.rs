@@ -7,6 +7,13 @@ pub struct AdminBadgeData {}
 #[derive(ScryptoSbor, NonFungibleData)]
 pub struct LiquidatorBadgeData {}
 
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct WithdrawalRequestData {
+    pub pool_res_address: ResourceAddress,
+    pub pool_units: Decimal,
+    pub requested_at: i64,
+}
+
 #[derive(ScryptoSbor)]
 pub struct BatchFlashloanItem {
     pub loan_amount: Decimal,
@@ -18,6 +25,8 @@ pub struct BatchFlashloanItem {
 pub struct LiquidationTerm {
     pub cdp_id: NonFungibleLocalId,
     pub payement_value: Decimal,
+    pub liquidator_badge_id: Option<NonFungibleLocalId>,
+    pub seized_collaterals: Vec<SeizedCollateral>,
 }
 
 #[derive(ScryptoSbor)]
@@ -91,7 +100,7 @@ pub fn create_cdp_res_manager(
     owner_rule: AccessRule,
     component_rule: AccessRule,
 ) -> ResourceManager {
-    ResourceBuilder::new_integer_non_fungible::<CollaterizedDebtPositionData>(OwnerRole::None)
+    ResourceBuilder::new_integer_non_fungible::<CDPNonFungibleData>(OwnerRole::None)
         .metadata(metadata!(
             roles {
                 metadata_setter => owner_rule.clone();
@@ -115,6 +124,30 @@ pub fn create_cdp_res_manager(
         .create_with_no_initial_supply()
 }
 
+pub fn create_withdrawal_request_res_manager(
+    owner_rule: AccessRule,
+    component_rule: AccessRule,
+) -> ResourceManager {
+    ResourceBuilder::new_integer_non_fungible::<WithdrawalRequestData>(OwnerRole::None)
+        .metadata(metadata!(
+            roles {
+                metadata_setter => owner_rule.clone();
+                metadata_setter_updater => owner_rule.clone();
+                metadata_locker => owner_rule.clone();
+                metadata_locker_updater => owner_rule;
+            }
+        ))
+        .mint_roles(mint_roles! {
+          minter => component_rule.clone();
+          minter_updater => rule!(deny_all);
+        })
+        .burn_roles(burn_roles! {
+          burner => component_rule;
+          burner_updater => rule!(deny_all);
+        })
+        .create_with_no_initial_supply()
+}
+
 pub fn create_transient_res_manager(
     owner_rule: AccessRule,
     component_rule: AccessRule,
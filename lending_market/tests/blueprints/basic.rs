@@ -1,5 +1,8 @@
 use crate::helpers::{init::{find_event_in_result, TestHelper}, methods::*};
-use lending_market::modules::{cdp_data::CDPLiquidableEvent, cdp_health_checker::ZERO_EPSILON};
+use lending_market::modules::{
+    cdp_data::{CDPLiquidableEvent, LiquidationEvent, RepayEvent},
+    cdp_health_checker::ZERO_EPSILON,
+};
 use radix_engine_interface::prelude::*;
 use scrypto_unit::*;
 use std::path::Path;
@@ -142,15 +145,19 @@ fn test_deposit_withdraw_borrow_repay() {
     get_resource(&mut helper, borrower_key, borrower_account, dec!(50), usd) //
         .expect_commit_success();
 
-    market_repay(
+    let receipt = market_repay(
         &mut helper,
         borrower_key,
         borrower_account,
         1u64,
         usd,
         dec!(100.250312164987776789),
-    )
-        .expect_commit_success();
+    );
+    let event: RepayEvent = find_event_in_result(receipt.expect_commit_success(), "RepayEvent").expect("RepayEvent not found");
+    assert_eq!(NonFungibleLocalId::integer(1), event.cdp_id);
+    assert_eq!(1, event.repayments.len());
+    assert_eq!(usd, event.repayments[0].pool_res_address);
+    assert!(event.repayments[0].delta_units < pdec!(0));
 
     market_remove_collateral(
         &mut helper,
@@ -459,7 +466,7 @@ fn test_liquidation() {
     ).expect_commit_failure();
 
     let payments: Vec<(ResourceAddress, Decimal)> = vec![(usd, dec!(202)), (btc, dec!(0.0099)), (eth, dec!(0.062))];
-    market_liquidation(
+    let receipt = market_liquidation(
         &mut helper,
         liquidator_user_key,
         liquidator_user_account,
@@ -468,7 +475,12 @@ fn test_liquidation() {
         payments,
         None,
         requested_collaterals,
-    ).expect_commit_success();
+    );
+    let event: LiquidationEvent = find_event_in_result(receipt.expect_commit_success(), "LiquidationEvent").expect("LiquidationEvent not found");
+    assert_eq!(NonFungibleLocalId::integer(cdp_id), event.cdp_id);
+    assert_eq!(1, event.seized_collaterals.len());
+    assert_eq!(XRD, event.seized_collaterals[0].position.pool_res_address);
+    assert!(event.bonus_value > dec!(0));
 
     assert_eq!(dec!(18400), helper
         .test_runner
@@ -0,0 +1,727 @@
+use crate::helpers::{init::{find_event_in_result, TestHelper}, methods::*};
+use lending_market::modules::{
+    cdp_data::{CDPHealthBand, CDPNonFungibleData, CDPPage, WrappedCDPData},
+    pool_state::{AccountSummary, PoolPriceStatusChangedEvent},
+};
+use radix_engine_interface::prelude::*;
+
+/// Manifest counterpart of the market `UpdateMarketConfigInput`
+#[derive(ManifestSbor)]
+#[allow(dead_code)]
+enum UpdateMarketConfigInput {
+    MaxCDPPosition(u8),
+}
+
+#[test]
+fn test_merge_split_burn_cdp() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    let usd = helper.faucet.usdc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(5_000))],
+    )
+    .expect_commit_success();
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(5_000))],
+    )
+    .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 2u64, usd, dec!(100))
+        .expect_commit_success();
+
+    assert_eq!(
+        helper
+            .test_runner
+            .get_component_balance(borrower_account, helper.market.cdp_resource_address),
+        dec!(2)
+    );
+
+    // A CDP with positions cannot be burnt
+    market_burn_cdp(&mut helper, borrower_key, borrower_account, vec![2u64])
+        .expect_commit_failure();
+
+    // The merged CDP would have two positions, more than the max CDP position
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::MaxCDPPosition(1u8)),
+    )
+    .expect_commit_success();
+
+    market_merge_cdps(&mut helper, borrower_key, borrower_account, 1u64, vec![2u64])
+        .expect_commit_failure();
+
+    // The new CDP would get two positions
+    market_split_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        2u64,
+        vec![(XRD, dec!(1_000))],
+        vec![(usd, dec!(10))],
+    )
+    .expect_commit_failure();
+
+    // A CDP above the lowered limit can still reduce its positions
+    market_repay(&mut helper, borrower_key, borrower_account, 2u64, usd, dec!(10))
+        .expect_commit_success();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::MaxCDPPosition(10u8)),
+    )
+    .expect_commit_success();
+
+    // MERGE CDP 2 INTO CDP 1
+    market_merge_cdps(&mut helper, borrower_key, borrower_account, 1u64, vec![2u64])
+        .expect_commit_success();
+
+    assert_eq!(
+        helper
+            .test_runner
+            .get_component_balance(borrower_account, helper.market.cdp_resource_address),
+        dec!(1)
+    );
+
+    // Moving all the collateral away from the loan makes the original CDP unhealthy
+    market_split_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        vec![(XRD, dec!(10_000))],
+        vec![],
+    )
+    .expect_commit_failure();
+
+    // Amounts to move must be positive and covered by the CDP positions
+    market_split_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        vec![(XRD, dec!(-5_000))],
+        vec![],
+    )
+    .expect_commit_failure();
+
+    market_split_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        vec![(XRD, dec!(20_000))],
+        vec![],
+    )
+    .expect_commit_failure();
+
+    market_split_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        vec![(XRD, dec!(5_000))],
+        vec![(usd, dec!(-100))],
+    )
+    .expect_commit_failure();
+
+    market_split_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        vec![(XRD, dec!(5_000))],
+        vec![(usd, dec!(1_000))],
+    )
+    .expect_commit_failure();
+
+    // SPLIT HALF OF THE COLLATERAL INTO CDP 3
+    market_split_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        vec![(XRD, dec!(5_000))],
+        vec![],
+    )
+    .expect_commit_success();
+
+    assert_eq!(
+        helper
+            .test_runner
+            .get_component_balance(borrower_account, helper.market.cdp_resource_address),
+        dec!(2)
+    );
+
+    // EMPTY AND BURN CDP 3
+    market_remove_collateral(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        3u64,
+        XRD,
+        dec!(5_000),
+        false,
+    )
+    .expect_commit_success();
+
+    market_burn_cdp(&mut helper, borrower_key, borrower_account, vec![3u64])
+        .expect_commit_success();
+
+    assert_eq!(
+        helper
+            .test_runner
+            .get_component_balance(borrower_account, helper.market.cdp_resource_address),
+        dec!(1)
+    );
+}
+
+#[test]
+fn test_list_cdps() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    let usd = helper.faucet.usdc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER WITH 3 CDPS, ONLY THE SECOND ONE HAS A LOAN
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    for _ in 0..3 {
+        market_create_cdp(
+            &mut helper,
+            borrower_key,
+            borrower_account,
+            vec![(XRD, dec!(1_000))],
+        )
+        .expect_commit_success();
+    }
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 2u64, usd, dec!(10))
+        .expect_commit_success();
+
+    let no_filter = (None, None, None, None, None, None);
+
+    // First page stops at the page size and returns the next cursor
+    let receipt = market_list_cdps(&mut helper, no_filter, None, 2);
+    let page: CDPPage = receipt.expect_commit_success().output(1);
+    assert_eq!(2, page.cdps.len());
+    assert_eq!(Some(3), page.next_cursor);
+
+    // Last page does not return a cursor
+    let receipt = market_list_cdps(&mut helper, no_filter, page.next_cursor, 2);
+    let page: CDPPage = receipt.expect_commit_success().output(1);
+    assert_eq!(1, page.cdps.len());
+    assert_eq!(NonFungibleLocalId::integer(3), page.cdps[0].cdp_id);
+    assert_eq!(None, page.next_cursor);
+
+    // Only the CDP with a loan is returned when filtering by loan resource
+    let receipt = market_list_cdps(&mut helper, (None, None, None, Some(usd), None, Some(false)), None, 10);
+    let page: CDPPage = receipt.expect_commit_success().output(1);
+    assert_eq!(1, page.cdps.len());
+    assert_eq!(NonFungibleLocalId::integer(2), page.cdps[0].cdp_id);
+    assert!(page.cdps[0].total_loan_value > dec!(0));
+    assert!(page.cdps[0].loan_to_value_ratio > dec!(0));
+
+    // No CDP is liquidable
+    let receipt = market_list_cdps(&mut helper, (None, None, None, None, None, Some(true)), None, 10);
+    let page: CDPPage = receipt.expect_commit_success().output(1);
+    assert!(page.cdps.is_empty());
+}
+
+#[test]
+fn test_list_cdps_by_pool() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    let usd = helper.faucet.usdc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER WITH 3 CDPS, ONLY THE SECOND ONE HAS A LOAN
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    for _ in 0..3 {
+        market_create_cdp(
+            &mut helper,
+            borrower_key,
+            borrower_account,
+            vec![(XRD, dec!(1_000))],
+        )
+        .expect_commit_success();
+    }
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 2u64, usd, dec!(10))
+        .expect_commit_success();
+
+    let receipt = market_list_cdps_by_pool(&mut helper, usd, None, 10);
+    let page: CDPPage = receipt.expect_commit_success().output(1);
+    assert_eq!(1, page.cdps.len());
+    assert_eq!(NonFungibleLocalId::integer(2), page.cdps[0].cdp_id);
+
+    let receipt = market_list_cdps_by_pool(&mut helper, XRD, None, 2);
+    let page: CDPPage = receipt.expect_commit_success().output(1);
+    assert_eq!(2, page.cdps.len());
+    assert_eq!(Some(2), page.next_cursor);
+
+    let scan_version = page.index_version;
+    assert!(scan_version.is_some());
+
+    // Emptied CDPs are removed from the index
+    market_remove_collateral(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        XRD,
+        dec!(1_000),
+        false,
+    )
+    .expect_commit_success();
+
+    // The removal moved the last CDP into a scanned slot, the version change tells to restart the scan
+    let receipt = market_list_cdps_by_pool(&mut helper, XRD, Some(2), 2);
+    let page: CDPPage = receipt.expect_commit_success().output(1);
+    assert!(page.cdps.is_empty());
+    assert_ne!(scan_version, page.index_version);
+
+    // Merged CDPs are removed from the index
+    market_merge_cdps(&mut helper, borrower_key, borrower_account, 2u64, vec![3u64])
+        .expect_commit_success();
+
+    let receipt = market_list_cdps_by_pool(&mut helper, XRD, None, 10);
+    let page: CDPPage = receipt.expect_commit_success().output(1);
+    assert_eq!(1, page.cdps.len());
+    assert_eq!(NonFungibleLocalId::integer(2), page.cdps[0].cdp_id);
+    assert_eq!(None, page.next_cursor);
+}
+
+#[test]
+fn test_account_summary() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    let usd = helper.faucet.usdc_resource_address;
+    let usd_pool_unit = helper.market.pools.get(&usd).unwrap().1;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A USER SUPPLYING USD AND BORROWING AGAINST XRD
+    let (user_key, _, user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(user_account);
+    helper.test_runner.load_account_from_faucet(user_account);
+    get_resource(&mut helper, user_key, user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, user_key, user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    market_create_cdp(&mut helper, user_key, user_account, vec![(XRD, dec!(5_000))])
+        .expect_commit_success();
+
+    market_borrow(&mut helper, user_key, user_account, 1u64, usd, dec!(100))
+        .expect_commit_success();
+
+    let pool_units = helper
+        .test_runner
+        .get_component_balance(user_account, usd_pool_unit);
+
+    let receipt = market_get_account_summary(&mut helper, vec![1u64], vec![(usd_pool_unit, pool_units)]);
+    let summary: AccountSummary = receipt.expect_commit_success().output(1);
+
+    assert_eq!(dec!(20_000), summary.free_supplied_value);
+    assert_eq!(dec!(5_000), summary.collateral_value);
+    assert_eq!(dec!(25_000), summary.total_supplied_value);
+    assert_eq!(dec!(2_500), summary.total_borrowed_value);
+    assert!(summary.max_loan_to_value_ratio > dec!(0));
+    // 2 500 borrowed against 5 000 XRD with a 0.7 liquidation threshold, between the 0.5 and 0.8 band LTVs
+    assert_eq!(CDPHealthBand::Moderate, summary.health_band);
+
+    // Pool units are only accepted for listed pools
+    market_get_account_summary(&mut helper, vec![], vec![(XRD, dec!(1))]).expect_commit_failure();
+}
+
+#[test]
+fn test_stale_price_degradation() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    const T5_HOURS: i64 = 18000000;
+    let usd = helper.faucet.usdc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(5_000))],
+    )
+    .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(50))
+        .expect_commit_success();
+
+    // THE USD PRICE IS NOT UPDATED FOR LONGER THAN THE EXPIRATION PERIOD
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(2), T2024 + T5_HOURS);
+
+    // Risk-reducing operations proceed with the last known price
+    let receipt = market_repay(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(10));
+    let event: PoolPriceStatusChangedEvent = find_event_in_result(
+        receipt.expect_commit_success(),
+        "PoolPriceStatusChangedEvent",
+    )
+    .expect("PoolPriceStatusChangedEvent not found");
+
+    assert_eq!(event.pool_res_address, usd);
+    assert!(event.stale);
+
+    market_add_collateral(&mut helper, borrower_key, borrower_account, 1u64, XRD, dec!(100))
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(100))
+        .expect_commit_success();
+
+    // Risk-increasing operations are blocked
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(10))
+        .expect_commit_failure();
+
+    market_remove_collateral(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        XRD,
+        dec!(100),
+        false,
+    )
+    .expect_commit_failure();
+
+    // A valid price ends the stale mode
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    let receipt = market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(10));
+    let event: PoolPriceStatusChangedEvent = find_event_in_result(
+        receipt.expect_commit_success(),
+        "PoolPriceStatusChangedEvent",
+    )
+    .expect("PoolPriceStatusChangedEvent not found");
+
+    assert!(!event.stale);
+}
+
+#[test]
+fn test_cdp_positions_storage() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    let usd = helper.faucet.usdc_resource_address;
+    let cdp_res_address = helper.market.cdp_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(5_000))],
+    )
+    .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(100))
+        .expect_commit_success();
+
+    // Only the display fields are written in the NFT data
+    let nft_data: CDPNonFungibleData = helper
+        .test_runner
+        .get_non_fungible_data(cdp_res_address, NonFungibleLocalId::integer(1));
+
+    assert_eq!(nft_data.health_band, CDPHealthBand::Healthy);
+    assert!(nft_data.collaterals.is_empty());
+    assert!(nft_data.loans.is_empty());
+
+    // Positions are loaded from the market storage
+    let cdp_data = market_show_cdp(&mut helper, 1u64)
+        .expect_commit_success()
+        .output::<Option<WrappedCDPData>>(1)
+        .unwrap();
+
+    assert_eq!(cdp_data.get_collateral_units(XRD), pdec!(5_000));
+    assert!(cdp_data.get_loan_units(usd) > pdec!(0));
+    assert!(!cdp_data.legacy_positions);
+
+    // Nothing to migrate
+    let migrated = market_migrate_cdp_positions(&mut helper, vec![1u64])
+        .expect_commit_success()
+        .output::<u64>(1);
+
+    assert_eq!(migrated, 0);
+
+    // The CDP can be emptied and burnt
+    get_resource(&mut helper, borrower_key, borrower_account, dec!(3_000), usd) //
+        .expect_commit_success();
+
+    market_repay(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(110))
+        .expect_commit_success();
+
+    market_remove_collateral(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        XRD,
+        dec!(5_000),
+        false,
+    )
+    .expect_commit_success();
+
+    market_burn_cdp(&mut helper, borrower_key, borrower_account, vec![1u64])
+        .expect_commit_success();
+}
+
+#[test]
+fn test_collateral_usage() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    let usd = helper.faucet.usdc_resource_address;
+    let btc = helper.faucet.btc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER WITH XRD AND USD DEPOSITS
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+    get_resource(&mut helper, borrower_key, borrower_account, dec!(3_000), usd) //
+        .expect_commit_success();
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(5_000)), (usd, dec!(100))],
+    )
+    .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(100))
+        .expect_commit_success();
+
+    // Only deposited assets can be toggled
+    market_update_collateral_usage(&mut helper, borrower_key, borrower_account, 1u64, btc, false)
+        .expect_commit_failure();
+
+    // The XRD deposit is required to keep the CDP healthy
+    market_update_collateral_usage(&mut helper, borrower_key, borrower_account, 1u64, XRD, false)
+        .expect_commit_failure();
+
+    // The USD deposit can be parked
+    market_update_collateral_usage(&mut helper, borrower_key, borrower_account, 1u64, usd, false)
+        .expect_commit_success();
+
+    let cdp_data = market_show_cdp(&mut helper, 1u64)
+        .expect_commit_success()
+        .output::<Option<WrappedCDPData>>(1)
+        .unwrap();
+
+    assert!(!cdp_data.is_used_as_collateral(usd));
+    assert!(cdp_data.is_used_as_collateral(XRD));
+
+    // Parked assets do not count toward the borrowing power: 5000 XRD * 0.7 = 140 USD
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(50))
+        .expect_commit_failure();
+
+    // Parked assets can be withdrawn without health impact
+    market_remove_collateral(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        usd,
+        dec!(50),
+        false,
+    )
+    .expect_commit_success();
+
+    // Enabled again, the remaining deposit counts toward the borrowing power
+    market_update_collateral_usage(&mut helper, borrower_key, borrower_account, 1u64, usd, true)
+        .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(50))
+        .expect_commit_success();
+}
+
+#[test]
+fn test_update_cdp() {
+    let mut helper = TestHelper::new();
+
+    let cdp_res_address = helper.market.cdp_resource_address;
+
+    let (user_key, _, user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(user_account);
+
+    market_create_cdp(&mut helper, user_key, user_account, vec![(XRD, dec!(1_000))])
+        .expect_commit_success();
+
+    market_update_cdp(
+        &mut helper,
+        user_key,
+        user_account,
+        1u64,
+        ManifestUpdateCDPInput::Name("My CDP".to_string()),
+    )
+    .expect_commit_success();
+
+    market_update_cdp(
+        &mut helper,
+        user_key,
+        user_account,
+        1u64,
+        ManifestUpdateCDPInput::Description("Long term position".to_string()),
+    )
+    .expect_commit_success();
+
+    market_update_cdp(
+        &mut helper,
+        user_key,
+        user_account,
+        1u64,
+        ManifestUpdateCDPInput::LiquidationPreference(vec![XRD]),
+    )
+    .expect_commit_success();
+
+    let nft_data: CDPNonFungibleData = helper
+        .test_runner
+        .get_non_fungible_data(cdp_res_address, NonFungibleLocalId::integer(1));
+
+    assert_eq!(nft_data.name, "My CDP");
+    assert_eq!(nft_data.description, "Long term position");
+
+    let cdp_data = market_show_cdp(&mut helper, 1u64)
+        .expect_commit_success()
+        .output::<Option<WrappedCDPData>>(1)
+        .unwrap();
+
+    assert_eq!(cdp_data.liquidation_preference, vec![XRD]);
+}
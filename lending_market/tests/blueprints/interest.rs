@@ -1,4 +1,5 @@
 use crate::helpers::{init::TestHelper, methods::*};
+use lending_market::modules::{pool_history::PoolSnapshot, pool_state::PoolRevenue, utils::PriceInfo};
 use radix_engine_interface::prelude::*;
 
 #[test]
@@ -205,6 +206,18 @@ fn test_interest() {
     let owner_usd_balance = helper
                 .test_runner
                 .get_component_balance(helper.owner_account_address, usd);
+
+    // Nothing is collected below the minimum amount
+    market_collect_pool_reserve(&mut helper, Some(usd), Some(dec!(1_000)))
+        .expect_commit_success();
+
+    assert_eq!(
+        helper
+            .test_runner
+            .get_component_balance(helper.owner_account_address, usd),
+        owner_usd_balance
+    );
+
     market_collect_reserve(&mut helper)
         .expect_commit_success();
 
@@ -214,4 +227,285 @@ fn test_interest() {
             .get_component_balance(helper.owner_account_address, usd) - owner_usd_balance,
         dec!(1.000719181814206157)
     );
+
+    let receipt = market_get_pool_revenue(&mut helper, usd);
+    let revenue: PoolRevenue = receipt.expect_commit_success().output(1);
+
+    assert_eq!(dec!(1.000719181814206157), revenue.collected);
+    assert_eq!(dec!(0), revenue.liquidation);
+    assert!(revenue.pending() >= dec!(0));
+}
+
+#[test]
+fn test_pool_history() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    const T2_HOURS: i64 = 7200000;
+
+    let usd = helper.faucet.usdc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(15)).expect_commit_success();
+
+    let (alice_key, _, alice_account) = helper.test_runner.new_allocated_account();
+
+    helper.test_runner.load_account_from_faucet(alice_account);
+    helper.test_runner.load_account_from_faucet(alice_account);
+
+    get_resource(&mut helper, alice_key, alice_account, dec!(15_001), usd)
+        .expect_commit_success();
+
+    market_contribute(&mut helper, alice_key, alice_account, usd, dec!(1_000))
+        .expect_commit_success();
+
+    // Two hours later, with a new price
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(2), T2024 + T2_HOURS);
+    admin_update_price(&mut helper, 1u64, usd, dec!(16)).expect_commit_success();
+
+    market_update_pool_state(&mut helper, usd);
+
+    let receipt = market_get_pool_history(&mut helper, usd, 0, i64::MAX);
+    let history: Vec<PoolSnapshot> = receipt.expect_commit_success().output(1);
+
+    assert_eq!(2, history.len());
+    assert!(history[0].timestamp < history[1].timestamp);
+    assert_eq!(dec!(15), history[0].price);
+    assert_eq!(dec!(16), history[1].price);
+    assert_eq!(dec!(1_000), history[1].total_deposit);
+
+    // Snapshots out of the requested range are filtered out
+    let receipt = market_get_pool_history(&mut helper, usd, history[1].timestamp, i64::MAX);
+    let history: Vec<PoolSnapshot> = receipt.expect_commit_success().output(1);
+
+    assert_eq!(1, history.len());
+    assert_eq!(dec!(16), history[0].price);
+}
+
+#[test]
+fn test_reserve_policy() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    let usd = helper.faucet.usdc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(15)).expect_commit_success();
+
+    let (alice_key, _, alice_account) = helper.test_runner.new_allocated_account();
+
+    helper.test_runner.load_account_from_faucet(alice_account);
+    helper.test_runner.load_account_from_faucet(alice_account);
+
+    get_resource(&mut helper, alice_key, alice_account, dec!(15_001), usd)
+        .expect_commit_success();
+
+    market_contribute(&mut helper, alice_key, alice_account, usd, dec!(1_000))
+        .expect_commit_success();
+
+    let (bob_key, _, bob_account) = helper.test_runner.new_allocated_account();
+
+    helper.test_runner.load_account_from_faucet(bob_account);
+    helper.test_runner.load_account_from_faucet(bob_account);
+
+    market_create_cdp(&mut helper, bob_key, bob_account, vec![(XRD, dec!(30_000))])
+        .expect_commit_success();
+
+    market_borrow(&mut helper, bob_key, bob_account, 1u64, usd, dec!(500))
+        .expect_commit_success();
+
+    // Keep a buffer of 0.5 USD deposited in the pool
+    admin_update_reserve_policy(&mut helper, usd, dec!(0.5), true).expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(2), T2024 + T6_MONTHS);
+    admin_update_price(&mut helper, 1u64, usd, dec!(15)).expect_commit_success();
+
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    let owner_usd_balance = helper
+        .test_runner
+        .get_component_balance(helper.owner_account_address, usd);
+
+    market_collect_pool_reserve(&mut helper, Some(usd), None).expect_commit_success();
+
+    let collected_amount = helper
+        .test_runner
+        .get_component_balance(helper.owner_account_address, usd) - owner_usd_balance;
+
+    assert!(collected_amount > dec!(0));
+
+    // The buffer is kept in the reserve, and can be used to cover a shortfall
+    let receipt = admin_cover_shortfall(&mut helper, usd, dec!(1_000));
+    let covered_amount: Decimal = receipt.expect_commit_success().output(2);
+
+    assert!(covered_amount > dec!(0.49) && covered_amount <= dec!(0.5));
+
+    let receipt = market_get_pool_revenue(&mut helper, usd);
+    let revenue: PoolRevenue = receipt.expect_commit_success().output(1);
+
+    assert_eq!(collected_amount, revenue.collected);
+    assert_eq!(covered_amount, revenue.covered);
+}
+
+#[test]
+fn test_pool_unit_price() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    let usd = helper.faucet.usdc_resource_address;
+    let usd_pu = helper.market.pools.get(&usd).unwrap().clone().1;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(15)).expect_commit_success();
+
+    let (alice_key, _, alice_account) = helper.test_runner.new_allocated_account();
+
+    helper.test_runner.load_account_from_faucet(alice_account);
+    helper.test_runner.load_account_from_faucet(alice_account);
+
+    get_resource(&mut helper, alice_key, alice_account, dec!(15_001), usd)
+        .expect_commit_success();
+
+    market_contribute(&mut helper, alice_key, alice_account, usd, dec!(1_000))
+        .expect_commit_success();
+
+    // Without accrued interest, a pool unit is worth one unit of asset
+    let receipt = market_get_pool_unit_price(&mut helper, usd_pu);
+    let price_info: Option<PriceInfo> = receipt.expect_commit_success().output(1);
+
+    assert_eq!(dec!(15), price_info.unwrap().price);
+
+    let (bob_key, _, bob_account) = helper.test_runner.new_allocated_account();
+
+    helper.test_runner.load_account_from_faucet(bob_account);
+    helper.test_runner.load_account_from_faucet(bob_account);
+
+    market_create_cdp(&mut helper, bob_key, bob_account, vec![(XRD, dec!(30_000))])
+        .expect_commit_success();
+
+    market_borrow(&mut helper, bob_key, bob_account, 1u64, usd, dec!(500))
+        .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(2), T2024 + T6_MONTHS);
+    admin_update_price(&mut helper, 1u64, usd, dec!(15)).expect_commit_success();
+
+    // The price is read from the stored pool state, its timestamp tells how old it is
+    let receipt = market_get_pool_unit_price(&mut helper, usd_pu);
+    let price_info: Option<PriceInfo> = receipt.expect_commit_success().output(1);
+
+    assert_eq!(T2024 / 1000, price_info.unwrap().timestamp);
+
+    // The accrued interest increases the pool unit price once the pool state is updated
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    let receipt = market_get_pool_unit_price(&mut helper, usd_pu);
+    let price_info = receipt.expect_commit_success().output::<Option<PriceInfo>>(1).unwrap();
+
+    assert!(price_info.price > dec!(15));
+    assert_eq!((T2024 + T6_MONTHS) / 1000, price_info.timestamp);
+
+    // Only pool units of the market are priced
+    let receipt = market_get_pool_unit_price(&mut helper, usd);
+    let price_info: Option<PriceInfo> = receipt.expect_commit_success().output(1);
+
+    assert!(price_info.is_none());
+}
+
+#[test]
+fn test_pool_previews() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    let usd = helper.faucet.usdc_resource_address;
+    let (usd_pool, usd_pu) = *helper.market.pools.get(&usd).unwrap();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(15)).expect_commit_success();
+
+    let (alice_key, _, alice_account) = helper.test_runner.new_allocated_account();
+
+    helper.test_runner.load_account_from_faucet(alice_account);
+    helper.test_runner.load_account_from_faucet(alice_account);
+
+    get_resource(&mut helper, alice_key, alice_account, dec!(15_001), usd)
+        .expect_commit_success();
+
+    market_contribute(&mut helper, alice_key, alice_account, usd, dec!(1_000))
+        .expect_commit_success();
+
+    let (bob_key, _, bob_account) = helper.test_runner.new_allocated_account();
+
+    helper.test_runner.load_account_from_faucet(bob_account);
+    helper.test_runner.load_account_from_faucet(bob_account);
+
+    market_create_cdp(&mut helper, bob_key, bob_account, vec![(XRD, dec!(30_000))])
+        .expect_commit_success();
+
+    market_borrow(&mut helper, bob_key, bob_account, 1u64, usd, dec!(500))
+        .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(2), T2024 + T6_MONTHS);
+    admin_update_price(&mut helper, 1u64, usd, dec!(15)).expect_commit_success();
+
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    // Contribute mints the previewed amount of pool units
+    let receipt = pool_preview(&mut helper, usd_pool, "preview_contribute", dec!(100));
+    let previewed_units: Decimal = receipt.expect_commit_success().output(1);
+
+    let usd_pu_balance = helper.test_runner.get_component_balance(alice_account, usd_pu);
+
+    market_contribute(&mut helper, alice_key, alice_account, usd, dec!(100))
+        .expect_commit_success();
+
+    assert_eq!(
+        previewed_units,
+        helper.test_runner.get_component_balance(alice_account, usd_pu) - usd_pu_balance
+    );
+
+    // Redeem returns the previewed amount of asset
+    let receipt = pool_preview(&mut helper, usd_pool, "preview_redeem", dec!(100));
+    let previewed_amount: Decimal = receipt.expect_commit_success().output(1);
+
+    let usd_balance = helper.test_runner.get_component_balance(alice_account, usd);
+
+    market_redeem(&mut helper, alice_key, alice_account, usd_pu, dec!(100))
+        .expect_commit_success();
+
+    assert_eq!(
+        previewed_amount,
+        helper.test_runner.get_component_balance(alice_account, usd) - usd_balance
+    );
+
+    // The borrowed liquidity cannot be redeemed
+    let receipt = pool_preview(&mut helper, usd_pool, "max_redeemable", dec!(1_000_000));
+    let max_redeemable: Decimal = receipt.expect_commit_success().output(1);
+
+    let receipt = pool_preview(&mut helper, usd_pool, "convert_to_assets", max_redeemable);
+    let max_redeemable_amount: Decimal = receipt.expect_commit_success().output(1);
+
+    assert!(max_redeemable < dec!(1_000_000));
+    assert!(max_redeemable_amount <= dec!(500));
 }
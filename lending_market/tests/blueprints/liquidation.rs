@@ -1,6 +1,25 @@
-use crate::helpers::{init::TestHelper, methods::*};
+use crate::helpers::{init::{find_event_in_result, find_events_in_result, TestHelper}, methods::*};
+use lending_market::modules::cdp_data::{CDPAtRiskEvent, LiquidationEvent, LiquidationQuote};
 use radix_engine_interface::prelude::*;
 
+/// Manifest counterpart of the market `UpdateMarketConfigInput`
+#[derive(ManifestSbor)]
+#[allow(dead_code)]
+enum UpdateMarketConfigInput {
+    MaxCDPPosition(u8),
+    MaxLiquidableValue(Decimal),
+    LiquidationDexSwapRate(Decimal),
+    TargetHealthFactor(Option<Decimal>),
+    FullLiquidationHealthFactor(Decimal),
+    LiquidationDustValue(Decimal),
+    LiquidationGracePeriod(Option<i64>),
+    HardLiquidationHealthFactor(Decimal),
+    MinWithdrawalRequestValue(Decimal),
+    BorrowLTVMargin(Decimal),
+    ModerateHealthBandLTV(Decimal),
+    RiskyHealthBandLTV(Decimal),
+}
+
 #[test]
 fn test_fast_liquidation() {
     let mut helper = TestHelper::new();
@@ -121,5 +140,1138 @@ fn test_fast_liquidation() {
         requested_collaterals,
     );
 
-    receipt.expect_commit_success();
+    let event: LiquidationEvent = find_event_in_result(
+        receipt.expect_commit_success(),
+        "LiquidationEvent",
+    )
+    .unwrap();
+
+    assert_eq!(event.liquidator_badge_id, None);
+}
+
+#[test]
+fn test_fast_liquidation_with_badge() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000))],
+    ) //
+    .expect_commit_success();
+
+    let cdp_id: u64 = 1;
+    market_borrow(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        cdp_id,
+        usd,
+        dec!(420),
+    )
+    .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS);
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+
+    let xrd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, XRD);
+    swap(
+        &mut helper,
+        liquidator_user_account,
+        liquidator_user_key,
+        xrd_balance,
+        XRD,
+        usd,
+    )
+    .expect_commit_success();
+
+    let usd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id).expect_commit_success();
+    let receipt = market_fast_liquidation_with_badge(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance)],
+        vec![XRD],
+    );
+
+    // The liquidator is identified in the event
+    let event: LiquidationEvent = find_event_in_result(
+        receipt.expect_commit_success(),
+        "LiquidationEvent",
+    )
+    .unwrap();
+
+    assert_eq!(event.cdp_id, NonFungibleLocalId::integer(cdp_id));
+    assert_eq!(event.liquidator_badge_id, Some(NonFungibleLocalId::integer(1)));
+}
+
+#[test]
+fn test_fast_liquidation_with_stale_loan_price() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+    // USD prices expire after 240 minutes
+    const T239_MINUTES: i64 = 14_370_000;
+    const T240_MINUTES: i64 = 14_410_000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000))],
+    ) //
+    .expect_commit_success();
+
+    let cdp_id: u64 = 1;
+    market_borrow(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        cdp_id,
+        usd,
+        dec!(420),
+    )
+    .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(2), T2024 + T6_MONTHS);
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+
+    let xrd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, XRD);
+    swap(
+        &mut helper,
+        liquidator_user_account,
+        liquidator_user_key,
+        xrd_balance,
+        XRD,
+        usd,
+    )
+    .expect_commit_success();
+
+    let usd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    // The CDP is found liquidable while the USD price is still valid
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(3), T2024 + T6_MONTHS + T239_MINUTES);
+    check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id).expect_commit_success();
+
+    // Only the price of the loan asset becomes stale, the XRD collateral price stays valid
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(4), T2024 + T6_MONTHS + T240_MINUTES);
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance)],
+        vec![XRD],
+    )
+    .expect_commit_failure();
+
+    // A valid loan price allows the liquidation again
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id).expect_commit_success();
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance)],
+        vec![XRD],
+    )
+    .expect_commit_success();
+}
+#[test]
+fn test_liquidation_preference() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+    let btc = helper.faucet.btc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER WITH XRD AND USD AS COLLATERAL
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+    get_resource(&mut helper, borrower_key, borrower_account, dec!(1_000), usd) //
+        .expect_commit_success();
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000)), (usd, dec!(40))],
+    )
+    .expect_commit_success();
+
+    let cdp_id: u64 = 1;
+
+    market_borrow(&mut helper, borrower_key, borrower_account, cdp_id, usd, dec!(420))
+        .expect_commit_success();
+
+    // Only listed assets, each listed once
+    market_update_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        cdp_id,
+        ManifestUpdateCDPInput::LiquidationPreference(vec![usd, usd]),
+    )
+    .expect_commit_failure();
+
+    let cdp_res_address = helper.market.cdp_resource_address;
+
+    market_update_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        cdp_id,
+        ManifestUpdateCDPInput::LiquidationPreference(vec![cdp_res_address]),
+    )
+    .expect_commit_failure();
+
+    // The XRD stack is protected: USD is seized first
+    market_update_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        cdp_id,
+        ManifestUpdateCDPInput::LiquidationPreference(vec![usd, XRD]),
+    )
+    .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS);
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+
+    let xrd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, XRD);
+
+    swap(
+        &mut helper,
+        liquidator_user_account,
+        liquidator_user_key,
+        xrd_balance,
+        XRD,
+        usd,
+    )
+    .expect_commit_success();
+
+    let usd_balance_after_swap = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id).expect_commit_success();
+
+    // Seizing XRD while USD is not exhausted is refused
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance_after_swap)],
+        vec![XRD],
+    )
+    .expect_commit_failure();
+
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance_after_swap)],
+        vec![usd, XRD],
+    )
+    .expect_commit_success();
+}
+
+#[test]
+fn test_quote_liquidation() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+    let btc = helper.faucet.btc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000))],
+    )
+    .expect_commit_success();
+
+    let cdp_id: u64 = 1;
+
+    market_borrow(&mut helper, borrower_key, borrower_account, cdp_id, usd, dec!(420))
+        .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS);
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+
+    let xrd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, XRD);
+
+    swap(
+        &mut helper,
+        liquidator_user_account,
+        liquidator_user_key,
+        xrd_balance,
+        XRD,
+        usd,
+    )
+    .expect_commit_success();
+
+    check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id).expect_commit_success();
+
+    // The quote does not change the CDP and can be repeated
+    let receipt = market_quote_liquidation(&mut helper, cdp_id, vec![usd], vec![XRD]);
+    let quote: LiquidationQuote = receipt.expect_commit_success().output(1);
+
+    let receipt = market_quote_liquidation(&mut helper, cdp_id, vec![usd], vec![XRD]);
+    let repeated_quote: LiquidationQuote = receipt.expect_commit_success().output(1);
+
+    assert_eq!(quote.total_payment_value, repeated_quote.total_payment_value);
+
+    assert_eq!(quote.repayments.len(), 1);
+    assert_eq!(quote.repayments[0].pool_res_address, usd);
+    assert_eq!(quote.total_payment_value, quote.repayments[0].value);
+
+    assert_eq!(quote.seizures.len(), 1);
+    assert_eq!(quote.seizures[0].pool_res_address, XRD);
+    assert_eq!(quote.uncovered_payment_value, dec!(0));
+    assert!(quote.loan_to_value_ratio > dec!(1));
+    assert!(quote.post_liquidation_loan_to_value_ratio < quote.loan_to_value_ratio);
+
+    // Not a collateral of the CDP: nothing can be seized
+    let receipt = market_quote_liquidation(&mut helper, cdp_id, vec![usd], vec![btc]);
+    let uncovered_quote: LiquidationQuote = receipt.expect_commit_success().output(1);
+
+    assert!(uncovered_quote.seizures.is_empty());
+    assert_eq!(uncovered_quote.uncovered_payment_value, quote.total_payment_value);
+
+    // The quoted repayment is exactly what the liquidation takes
+    let usd_balance_before = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance_before)],
+        vec![XRD],
+    )
+    .expect_commit_success();
+
+    let usd_balance_after = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    assert!(
+        (usd_balance_before - usd_balance_after - quote.repayments[0].amount)
+            .checked_abs()
+            .unwrap()
+            < dec!(0.000001)
+    );
+}
+
+#[test]
+fn test_quote_liquidation_capped_by_liquidable_value() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+    let btc = helper.faucet.btc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000))],
+    )
+    .expect_commit_success();
+
+    let cdp_id: u64 = 1;
+
+    market_borrow(&mut helper, borrower_key, borrower_account, cdp_id, usd, dec!(420))
+        .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS);
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    let (liquidator_user_key, _, _) = helper.test_runner.new_allocated_account();
+
+    check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id).expect_commit_success();
+
+    // The liquidable value of the CDP is the close factor of its loan when it was checked
+    let receipt = market_quote_liquidation(&mut helper, cdp_id, vec![usd], vec![XRD]);
+    let quote: LiquidationQuote = receipt.expect_commit_success().output(1);
+
+    let liquidable_value = quote.total_payment_value;
+
+    // The loan close factor now exceeds the liquidable value, the quote keeps to the latter
+    admin_update_price(&mut helper, 1u64, usd, dec!(35)).expect_commit_success();
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    let receipt = market_quote_liquidation(&mut helper, cdp_id, vec![usd], vec![XRD]);
+    let capped_quote: LiquidationQuote = receipt.expect_commit_success().output(1);
+
+    assert_eq!(capped_quote.total_payment_value, liquidable_value);
+    assert_eq!(capped_quote.repayments[0].value, liquidable_value);
+    assert!(capped_quote.repayments[0].amount < quote.repayments[0].amount);
+}
+
+#[test]
+fn test_target_health_liquidation() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+    let btc = helper.faucet.btc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // Liquidations bring CDPs back to a health factor of 1.05, full close under 0.5
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::TargetHealthFactor(Some(dec!(0.9)))),
+    )
+    .expect_commit_failure();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::TargetHealthFactor(Some(dec!(1.05)))),
+    )
+    .expect_commit_success();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::FullLiquidationHealthFactor(dec!(0.5))),
+    )
+    .expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000))],
+    )
+    .expect_commit_success();
+
+    let cdp_id: u64 = 1;
+
+    market_borrow(&mut helper, borrower_key, borrower_account, cdp_id, usd, dec!(420))
+        .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS);
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+    helper.test_runner.load_account_from_faucet(liquidator_user_account);
+    helper.test_runner.load_account_from_faucet(liquidator_user_account);
+
+    let xrd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, XRD);
+
+    swap(
+        &mut helper,
+        liquidator_user_account,
+        liquidator_user_key,
+        xrd_balance,
+        XRD,
+        usd,
+    )
+    .expect_commit_success();
+
+    let usd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id).expect_commit_success();
+
+    // The repayment is the minimum needed to reach the target health factor
+    let receipt = market_quote_liquidation(&mut helper, cdp_id, vec![usd], vec![XRD]);
+    let quote: LiquidationQuote = receipt.expect_commit_success().output(1);
+
+    let target_loan_to_value_ratio = dec!(1) / dec!(1.05);
+
+    assert!(quote.loan_to_value_ratio > dec!(1));
+    assert!(quote.post_liquidation_loan_to_value_ratio <= target_loan_to_value_ratio);
+    assert!(quote.post_liquidation_loan_to_value_ratio > target_loan_to_value_ratio - dec!(0.0001));
+
+    let receipt = market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance)],
+        vec![XRD],
+    );
+
+    let event: LiquidationEvent = find_event_in_result(
+        receipt.expect_commit_success(),
+        "LiquidationEvent",
+    )
+    .unwrap();
+
+    assert!(
+        (event.total_payment_value - quote.total_payment_value)
+            .checked_abs()
+            .unwrap()
+            < dec!(0.000001)
+    );
+    assert!(event.loan_to_value_ratio <= target_loan_to_value_ratio + dec!(0.000001));
+    assert!(event.loan_to_value_ratio > target_loan_to_value_ratio - dec!(0.0001));
+
+    // The CDP is healthy again
+    check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id).expect_commit_success();
+
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, dec!(1))],
+        vec![XRD],
+    )
+    .expect_commit_failure();
+}
+
+#[test]
+fn test_liquidation_grace_period() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+    let btc = helper.faucet.btc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+    const T30_MINUTES: i64 = 1800000;
+    const T2_HOURS: i64 = 7200000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // CDPs with a health factor between 0.5 and 1 get a one hour grace period
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::LiquidationGracePeriod(Some(3600i64))),
+    )
+    .expect_commit_success();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::HardLiquidationHealthFactor(dec!(0.5))),
+    )
+    .expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000))],
+    )
+    .expect_commit_success();
+
+    let cdp_id: u64 = 1;
+
+    market_borrow(&mut helper, borrower_key, borrower_account, cdp_id, usd, dec!(420))
+        .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS);
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+    helper.test_runner.load_account_from_faucet(liquidator_user_account);
+
+    let xrd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, XRD);
+
+    swap(
+        &mut helper,
+        liquidator_user_account,
+        liquidator_user_key,
+        xrd_balance,
+        XRD,
+        usd,
+    )
+    .expect_commit_success();
+
+    let usd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    // The unhealthy CDP is marked at risk instead of being liquidable
+    let receipt = check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id);
+    let result = receipt.expect_commit_success();
+    let liquidable: bool = result.output(1);
+    assert!(!liquidable);
+
+    let event: CDPAtRiskEvent = find_event_in_result(result, "CDPAtRiskEvent").unwrap();
+    assert_eq!(event.cdp_id, NonFungibleLocalId::integer(cdp_id));
+    assert!(event.loan_to_value_ratio > dec!(1));
+    assert_eq!(event.liquidation_deadline, (T2024 + T6_MONTHS) / 1000 + 3600);
+
+    market_quote_liquidation(&mut helper, cdp_id, vec![usd], vec![XRD]).expect_commit_failure();
+
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance)],
+        vec![XRD],
+    )
+    .expect_commit_failure();
+
+    // Still within the grace period, the deadline is not pushed back
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS + T30_MINUTES);
+
+    let receipt = check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id);
+    let result = receipt.expect_commit_success();
+    let liquidable: bool = result.output(1);
+    assert!(!liquidable);
+    assert!(find_event_in_result::<CDPAtRiskEvent>(result, "CDPAtRiskEvent").is_none());
+
+    // A top-up bringing the CDP back to health clears the deadline
+    market_add_collateral(&mut helper, borrower_key, borrower_account, cdp_id, XRD, dec!(10_000))
+        .expect_commit_success();
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(45)).expect_commit_success();
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    let receipt = check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id);
+    let result = receipt.expect_commit_success();
+    let liquidable: bool = result.output(1);
+    assert!(!liquidable);
+
+    let event: CDPAtRiskEvent = find_event_in_result(result, "CDPAtRiskEvent").unwrap();
+    assert_eq!(
+        event.liquidation_deadline,
+        (T2024 + T6_MONTHS + T30_MINUTES) / 1000 + 3600
+    );
+
+    // Once the grace period is over, the CDP can be liquidated
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS + T2_HOURS);
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(45)).expect_commit_success();
+    market_update_pool_state(&mut helper, usd).expect_commit_success();
+
+    let receipt = check_cdp_for_liquidation(&mut helper, liquidator_user_key, cdp_id);
+    let liquidable: bool = receipt.expect_commit_success().output(1);
+    assert!(liquidable);
+
+    market_quote_liquidation(&mut helper, cdp_id, vec![usd], vec![XRD]).expect_commit_success();
+
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        cdp_id,
+        vec![(usd, usd_balance)],
+        vec![XRD],
+    )
+    .expect_commit_success();
+}
+
+#[test]
+fn test_batch_fast_liquidation() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+    let btc = helper.faucet.btc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(1_000))
+        .expect_commit_success();
+
+    // SET UP TWO BORROWERS, THE SECOND ONE WITH A SAFER CDP
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000))],
+    )
+    .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(420))
+        .expect_commit_success();
+
+    let (safe_borrower_key, _, safe_borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(safe_borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        safe_borrower_key,
+        safe_borrower_account,
+        vec![(XRD, dec!(19_000))],
+    )
+    .expect_commit_success();
+
+    market_borrow(&mut helper, safe_borrower_key, safe_borrower_account, 2u64, usd, dec!(250))
+        .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS);
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+
+    let xrd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, XRD);
+
+    swap(
+        &mut helper,
+        liquidator_user_account,
+        liquidator_user_key,
+        xrd_balance,
+        XRD,
+        usd,
+    )
+    .expect_commit_success();
+
+    let usd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    // No prior check is needed: the healthy CDP is skipped, the other one is liquidated
+    let receipt = market_batch_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        vec![(2u64, vec![XRD]), (1u64, vec![XRD])],
+        vec![(usd, usd_balance)],
+    );
+
+    let events: Vec<LiquidationEvent> = find_events_in_result(
+        receipt.expect_commit_success(),
+        "LiquidationEvent",
+    );
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].cdp_id, NonFungibleLocalId::integer(1));
+
+    // The payment remainder is returned to the liquidator
+    let usd_balance_after = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    assert!(usd_balance_after > dec!(0));
+    assert!(
+        (usd_balance - usd_balance_after - events[0].total_payment_value / dec!(30))
+            .checked_abs()
+            .unwrap()
+            < dec!(0.000001)
+    );
+}
+
+#[test]
+fn test_batch_fast_liquidation_invalid_entries() {
+    let mut helper = TestHelper::new();
+    let usd = helper.faucet.usdc_resource_address;
+    let btc = helper.faucet.btc_resource_address;
+
+    const T2024: i64 = 1704067200;
+    const T6_MONTHS: i64 = 15778476000;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(1_000))
+        .expect_commit_success();
+
+    // SET UP TWO BORROWERS, THE SECOND ONE WITH A SAFER CDP
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(15_000))],
+    )
+    .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(420))
+        .expect_commit_success();
+
+    let (safe_borrower_key, _, safe_borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(safe_borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        safe_borrower_key,
+        safe_borrower_account,
+        vec![(XRD, dec!(19_000))],
+    )
+    .expect_commit_success();
+
+    market_borrow(&mut helper, safe_borrower_key, safe_borrower_account, 2u64, usd, dec!(250))
+        .expect_commit_success();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024 + T6_MONTHS);
+
+    admin_update_price(&mut helper, 1u64, usd, dec!(30)).expect_commit_success();
+    admin_update_price(&mut helper, 1u64, btc, dec!(1300000)).expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+
+    let xrd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, XRD);
+
+    swap(
+        &mut helper,
+        liquidator_user_account,
+        liquidator_user_key,
+        xrd_balance,
+        XRD,
+        usd,
+    )
+    .expect_commit_success();
+
+    let usd_balance = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    // Invalid entries are skipped without failing the batch: an unknown CDP, and a collateral
+    // not held by the CDP. The valid entry is liquidated
+    let receipt = market_batch_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        vec![(99u64, vec![XRD]), (1u64, vec![btc]), (2u64, vec![XRD]), (1u64, vec![XRD])],
+        vec![(usd, usd_balance)],
+    );
+
+    let events: Vec<LiquidationEvent> = find_events_in_result(
+        receipt.expect_commit_success(),
+        "LiquidationEvent",
+    );
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].cdp_id, NonFungibleLocalId::integer(1));
+
+    // The payment remainder is returned to the liquidator
+    let usd_balance_after = helper
+        .test_runner
+        .get_component_balance(liquidator_user_account, usd);
+
+    assert!(usd_balance_after > dec!(0));
+    assert!(
+        (usd_balance - usd_balance_after - events[0].total_payment_value / dec!(30))
+            .checked_abs()
+            .unwrap()
+            < dec!(0.000001)
+    );
 }
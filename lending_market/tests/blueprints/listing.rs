@@ -0,0 +1,612 @@
+use crate::helpers::{init::{find_event_in_result, TestHelper}, methods::*};
+use lending_market::modules::cdp_data::CDPLiquidableEvent;
+use scrypto_test::prelude::*;
+
+#[test]
+fn test_delisting_lifecycle() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    const T2_HOURS: i64 = 7200000;
+
+    let usd = helper.faucet.usdc_resource_address;
+    let usd_pu = helper.market.pools.get(&usd).unwrap().clone().1;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A USER SUPPLYING USD AND USING USD AS COLLATERAL
+    let (user_key, _, user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(user_account);
+    helper.test_runner.load_account_from_faucet(user_account);
+    get_resource(&mut helper, user_key, user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, user_key, user_account, usd, dec!(100))
+        .expect_commit_success();
+
+    market_create_cdp(&mut helper, user_key, user_account, vec![(usd, dec!(100))])
+        .expect_commit_success();
+
+    // Retiring requires a wound down pool
+    admin_update_listing_status(&mut helper, "retire_lending_pool", manifest_args!(usd))
+        .expect_commit_failure();
+
+    // FREEZE: no new contributions or collaterals
+    admin_update_listing_status(&mut helper, "freeze_lending_pool", manifest_args!(usd))
+        .expect_commit_success();
+
+    market_contribute(&mut helper, user_key, user_account, usd, dec!(100))
+        .expect_commit_failure();
+
+    market_create_cdp(&mut helper, user_key, user_account, vec![(usd, dec!(100))])
+        .expect_commit_failure();
+
+    // WIND-DOWN over one hour
+    admin_update_listing_status(
+        &mut helper,
+        "wind_down_lending_pool",
+        manifest_args!(usd, 3600i64),
+    )
+    .expect_commit_success();
+
+    admin_update_listing_status(&mut helper, "retire_lending_pool", manifest_args!(usd))
+        .expect_commit_failure();
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(2), T2024 + T2_HOURS);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // RETIRE: only once the CDP positions are closed
+    admin_update_listing_status(&mut helper, "retire_lending_pool", manifest_args!(usd))
+        .expect_commit_failure();
+
+    market_remove_collateral(&mut helper, user_key, user_account, 1u64, usd, dec!(100), false)
+        .expect_commit_success();
+
+    admin_update_listing_status(&mut helper, "retire_lending_pool", manifest_args!(usd))
+        .expect_commit_success();
+
+    // Remaining pool units can still be redeemed
+    let pool_unit_balance = helper.test_runner.get_component_balance(user_account, usd_pu);
+
+    market_redeem(&mut helper, user_key, user_account, usd_pu, pool_unit_balance)
+        .expect_commit_success();
+
+    assert_eq!(
+        helper.test_runner.get_component_balance(user_account, usd_pu),
+        dec!(0)
+    );
+}
+
+#[test]
+fn test_wound_down_loan_liquidation() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    const T2_HOURS: i64 = 7200000;
+
+    let usd = helper.faucet.usdc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A HEALTHY BORROWER OF USD
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(borrower_account);
+
+    market_create_cdp(&mut helper, borrower_key, borrower_account, vec![(XRD, dec!(5_000))])
+        .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(50))
+        .expect_commit_success();
+
+    // SET UP LIQUIDATOR
+    let auth = rule!(require(NonFungibleGlobalId::from_public_key(&helper.owner_public_key)));
+    let (liquidator_user_key, liquidator_user_account) = (helper.owner_public_key, helper.test_runner.new_account_advanced(OwnerRole::Fixed(auth)));
+    admin_send_liquidator_badge(&mut helper, 1, liquidator_user_account)
+        .expect_commit_success();
+    get_resource(&mut helper, liquidator_user_key, liquidator_user_account, dec!(2_500), usd) //
+        .expect_commit_success();
+
+    // FREEZE AND WIND DOWN THE BORROWED ASSET OVER ONE HOUR
+    admin_update_listing_status(&mut helper, "freeze_lending_pool", manifest_args!(usd))
+        .expect_commit_success();
+
+    admin_update_listing_status(
+        &mut helper,
+        "wind_down_lending_pool",
+        manifest_args!(usd, 3600i64),
+    )
+    .expect_commit_success();
+
+    // The healthy CDP is not liquidable during the wind-down
+    let liquidable = check_cdp_for_liquidation(&mut helper, liquidator_user_key, 1u64)
+        .expect_commit_success()
+        .output::<bool>(1);
+
+    assert!(!liquidable);
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(2), T2024 + T2_HOURS);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // Once the wind-down is over, the loan can be fully liquidated whatever the CDP health
+    let liquidable = check_cdp_for_liquidation(&mut helper, liquidator_user_key, 1u64)
+        .expect_commit_success()
+        .output::<bool>(1);
+
+    assert!(liquidable);
+
+    market_fast_liquidation(
+        &mut helper,
+        liquidator_user_key,
+        liquidator_user_account,
+        1,
+        1u64,
+        vec![(usd, dec!(100))],
+        vec![XRD],
+    )
+    .expect_commit_success();
+
+    let liquidable = check_cdp_for_liquidation(&mut helper, liquidator_user_key, 1u64)
+        .expect_commit_success()
+        .output::<bool>(1);
+
+    assert!(!liquidable);
+}
+
+#[test]
+fn test_replace_pool_component() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+
+    let usd = helper.faucet.usdc_resource_address;
+    let usd_pu = helper.market.pools.get(&usd).unwrap().clone().1;
+    let price_feed_component_address = helper.price_feed.price_feed_component_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(100))
+        .expect_commit_success();
+
+    // PRICE FEED MIGRATION, checked against the last known price
+    admin_update_listing_status(
+        &mut helper,
+        "migrate_pool_price_feed",
+        manifest_args!(usd, price_feed_component_address, dec!(-0.01)),
+    )
+    .expect_commit_failure();
+
+    admin_update_listing_status(
+        &mut helper,
+        "migrate_pool_price_feed",
+        manifest_args!(usd, price_feed_component_address, dec!(0.01)),
+    )
+    .expect_commit_success();
+
+    // Only a frozen pool can be replaced
+    admin_update_listing_status(&mut helper, "replace_pool_component", manifest_args!(usd))
+        .expect_commit_failure();
+
+    admin_update_listing_status(&mut helper, "freeze_lending_pool", manifest_args!(usd))
+        .expect_commit_success();
+
+    let new_usd_pu = admin_update_listing_status(&mut helper, "replace_pool_component", manifest_args!(usd))
+        .expect_commit_success()
+        .output::<ResourceAddress>(1);
+
+    assert_ne!(new_usd_pu, usd_pu);
+
+    // The replaced pool units cannot be redeemed anymore, they are migrated to the new pool
+    let pool_unit_balance = helper.test_runner.get_component_balance(lp_user_account, usd_pu);
+
+    market_redeem(&mut helper, lp_user_key, lp_user_account, usd_pu, pool_unit_balance)
+        .expect_commit_failure();
+
+    market_migrate_pool_units(&mut helper, lp_user_key, lp_user_account, usd_pu, pool_unit_balance)
+        .expect_commit_success();
+
+    assert_eq!(helper.test_runner.get_component_balance(lp_user_account, usd_pu), dec!(0));
+
+    let new_pool_unit_balance = helper.test_runner.get_component_balance(lp_user_account, new_usd_pu);
+
+    assert!(new_pool_unit_balance > dec!(0));
+
+    let usd_balance = helper.test_runner.get_component_balance(lp_user_account, usd);
+
+    market_redeem(&mut helper, lp_user_key, lp_user_account, new_usd_pu, new_pool_unit_balance)
+        .expect_commit_success();
+
+    let redeemed_amount = helper.test_runner.get_component_balance(lp_user_account, usd) - usd_balance;
+
+    assert!(redeemed_amount > dec!(99.99) && redeemed_amount <= dec!(100));
+}
+
+#[test]
+fn test_audit_listed_asset() {
+    let mut helper = TestHelper::new();
+
+    // CREATE AN ASSET WITH AN UPDATABLE FREEZER ROLE
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_fungible_resource(
+            OwnerRole::None,
+            true,
+            18,
+            FungibleResourceRoles {
+                freeze_roles: freeze_roles! {
+                    freezer => rule!(deny_all);
+                    freezer_updater => rule!(allow_all);
+                },
+                ..Default::default()
+            },
+            metadata!(init {
+                "symbol" => "TST", locked;
+            }),
+            Some(dec!(1_000)),
+        )
+        .deposit_batch(helper.owner_account_address)
+        .build();
+
+    let tst = helper
+        .test_runner
+        .execute_manifest(manifest, vec![])
+        .expect_commit_success()
+        .new_resource_addresses()[0];
+
+    admin_update_price(&mut helper, 1u64, tst, dec!(1)).expect_commit_success();
+
+    let price_feed_component_address = helper.price_feed.price_feed_component_address;
+
+    admin_update_listing_status(
+        &mut helper,
+        "create_lending_pool",
+        manifest_args!(
+            price_feed_component_address,
+            tst,
+            (
+                dec!("0.35"),
+                dec!("0.15"),
+                dec!("0.08"),
+                dec!("0.001"),
+                0u8,
+                dec!("0"),
+                dec!("1"),
+                None::<Decimal>,
+                None::<Decimal>,
+                Some(dec!("0.99")),
+                5i64,
+                15i64,
+                240i64,
+                dec!("0.45"),
+            ),
+            (dec!(0), dec!(0.04), dec!(3.00)),
+            (
+                Some(dec!("0.7")),
+                Some(dec!("0.7")),
+                IndexMap::<ResourceAddress, Decimal>::new(),
+                IndexMap::<u8, Decimal>::new(),
+                dec!("0.7")
+            )
+        ),
+    )
+    .expect_commit_success();
+
+    // Unchanged roles
+    let changed_roles = market_audit_listed_asset(&mut helper, tst)
+        .expect_commit_success()
+        .output::<Vec<String>>(1);
+
+    assert!(changed_roles.is_empty());
+
+    // The freezer role is updated by the asset owner
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .set_role(tst, ModuleId::Main, "freezer", rule!(allow_all))
+        .build();
+
+    helper
+        .test_runner
+        .execute_manifest(manifest, vec![])
+        .expect_commit_success();
+
+    let changed_roles = market_audit_listed_asset(&mut helper, tst)
+        .expect_commit_success()
+        .output::<Vec<String>>(1);
+
+    assert_eq!(changed_roles, vec!["freezer".to_string()]);
+
+    // Add collateral is disabled for the asset
+    let owner_public_key = helper.owner_public_key;
+    let owner_account_address = helper.owner_account_address;
+
+    market_create_cdp(
+        &mut helper,
+        owner_public_key,
+        owner_account_address,
+        vec![(tst, dec!(100))],
+    )
+    .expect_commit_failure();
+
+    // The change is reported until an admin confirms the new roles
+    let changed_roles = market_audit_listed_asset(&mut helper, tst)
+        .expect_commit_success()
+        .output::<Vec<String>>(1);
+
+    assert_eq!(changed_roles, vec!["freezer".to_string()]);
+
+    let confirmed_roles =
+        admin_update_listing_status(&mut helper, "confirm_asset_roles", manifest_args!(tst))
+            .expect_commit_success()
+            .output::<Vec<String>>(2);
+
+    assert_eq!(confirmed_roles, vec!["freezer".to_string()]);
+
+    let changed_roles = market_audit_listed_asset(&mut helper, tst)
+        .expect_commit_success()
+        .output::<Vec<String>>(1);
+
+    assert!(changed_roles.is_empty());
+
+    // The confirmation does not enable add collateral again
+    market_create_cdp(
+        &mut helper,
+        owner_public_key,
+        owner_account_address,
+        vec![(tst, dec!(100))],
+    )
+    .expect_commit_failure();
+
+    // Unlisted assets cannot be audited
+    let cdp_res_address = helper.market.cdp_resource_address;
+
+    market_audit_listed_asset(&mut helper, cdp_res_address).expect_commit_failure();
+
+    admin_update_listing_status(
+        &mut helper,
+        "confirm_asset_roles",
+        manifest_args!(cdp_res_address),
+    )
+    .expect_commit_failure();
+}
+
+#[derive(ManifestSbor)]
+enum UpdateLiquidationThresholdInput {
+    DefaultValue(Decimal),
+}
+
+/// Manifest counterpart of the market `UpdateMarketConfigInput`
+#[derive(ManifestSbor)]
+#[allow(dead_code)]
+enum UpdateMarketConfigInput {
+    MaxCDPPosition(u8),
+    MaxLiquidableValue(Decimal),
+    LiquidationDexSwapRate(Decimal),
+    TargetHealthFactor(Option<Decimal>),
+    FullLiquidationHealthFactor(Decimal),
+    LiquidationDustValue(Decimal),
+    LiquidationGracePeriod(Option<i64>),
+    HardLiquidationHealthFactor(Decimal),
+    MinWithdrawalRequestValue(Decimal),
+    BorrowLTVMargin(Decimal),
+    ModerateHealthBandLTV(Decimal),
+    RiskyHealthBandLTV(Decimal),
+}
+
+#[test]
+fn test_borrow_ltv() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+    let usd = helper.faucet.usdc_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(800))
+        .expect_commit_success();
+
+    // SET UP A BORROWER
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper
+        .test_runner
+        .load_account_from_faucet(borrower_account);
+
+    market_create_cdp(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        vec![(XRD, dec!(5_000))],
+    )
+    .expect_commit_success();
+
+    // The borrow LTV must be lower than the liquidation threshold of 0.7
+    admin_update_listing_status(
+        &mut helper,
+        "set_borrow_ltv",
+        manifest_args!(
+            XRD,
+            (
+                Some(dec!("0.7")),
+                Some(dec!("0.7")),
+                IndexMap::<ResourceAddress, Decimal>::new(),
+                IndexMap::<u8, Decimal>::new(),
+                dec!("0.7")
+            )
+        ),
+    )
+    .expect_commit_failure();
+
+    admin_update_listing_status(
+        &mut helper,
+        "set_borrow_ltv",
+        manifest_args!(
+            XRD,
+            (
+                Some(dec!("0.4")),
+                Some(dec!("0.4")),
+                IndexMap::<ResourceAddress, Decimal>::new(),
+                IndexMap::<u8, Decimal>::new(),
+                dec!("0.4")
+            )
+        ),
+    )
+    .expect_commit_success();
+
+    // Borrowing is limited by the borrow LTV: 5000 XRD * 0.4 = 80 USD
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(100))
+        .expect_commit_failure();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(70))
+        .expect_commit_success();
+
+    // A price drop above the borrow LTV blocks risk-increasing actions without allowing liquidation
+    admin_update_price(&mut helper, 1u64, usd, dec!(40)).expect_commit_success();
+
+    market_remove_collateral(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        XRD,
+        dec!(1),
+        false,
+    )
+    .expect_commit_failure();
+
+    let receipt = market_list_liquidable_cdps(&mut helper);
+    let event: CDPLiquidableEvent = find_event_in_result(receipt.expect_commit_success(), "CDPLiquidableEvent").expect("CDPLiquidableEvent not found");
+
+    assert!(event.cdps.is_empty());
+
+    // The liquidation threshold cannot be lowered under the borrow LTV
+    admin_update_listing_status(
+        &mut helper,
+        "update_liquidation_threshold",
+        manifest_args!(XRD, UpdateLiquidationThresholdInput::DefaultValue(dec!("0.3"))),
+    )
+    .expect_commit_failure();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_borrow_ltv",
+        manifest_args!(XRD, UpdateLiquidationThresholdInput::DefaultValue(dec!("0.3"))),
+    )
+    .expect_commit_success();
+
+    // Raised again, collateral can be removed
+    admin_update_listing_status(
+        &mut helper,
+        "update_borrow_ltv",
+        manifest_args!(XRD, UpdateLiquidationThresholdInput::DefaultValue(dec!("0.6"))),
+    )
+    .expect_commit_success();
+
+    market_remove_collateral(
+        &mut helper,
+        borrower_key,
+        borrower_account,
+        1u64,
+        XRD,
+        dec!(1),
+        false,
+    )
+    .expect_commit_success();
+
+    // Without market margin, the USD pool has no borrow LTV
+    admin_update_listing_status(
+        &mut helper,
+        "update_borrow_ltv",
+        manifest_args!(usd, UpdateLiquidationThresholdInput::DefaultValue(dec!("0.6"))),
+    )
+    .expect_commit_failure();
+
+    // With a market margin of 10%, the borrow LTV must stay under 0.7 * 0.9 = 0.63
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::BorrowLTVMargin(dec!(0.1))),
+    )
+    .expect_commit_success();
+
+    // The USD pool got a borrow LTV derived from its liquidation threshold: 0.75 * 0.9 = 0.675
+    admin_update_listing_status(
+        &mut helper,
+        "update_borrow_ltv",
+        manifest_args!(usd, UpdateLiquidationThresholdInput::DefaultValue(dec!("0.7"))),
+    )
+    .expect_commit_failure();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_borrow_ltv",
+        manifest_args!(usd, UpdateLiquidationThresholdInput::DefaultValue(dec!("0.6"))),
+    )
+    .expect_commit_success();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_borrow_ltv",
+        manifest_args!(XRD, UpdateLiquidationThresholdInput::DefaultValue(dec!("0.65"))),
+    )
+    .expect_commit_failure();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_borrow_ltv",
+        manifest_args!(XRD, UpdateLiquidationThresholdInput::DefaultValue(dec!("0.63"))),
+    )
+    .expect_commit_success();
+
+    // The margin cannot be raised over the one kept by the XRD borrow LTV: 0.7 * 0.8 < 0.63
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::BorrowLTVMargin(dec!(0.2))),
+    )
+    .expect_commit_failure();
+
+    admin_update_listing_status(
+        &mut helper,
+        "update_market_config",
+        manifest_args!(UpdateMarketConfigInput::BorrowLTVMargin(dec!(0.05))),
+    )
+    .expect_commit_success();
+}
@@ -1,5 +1,8 @@
 pub mod basic;
+pub mod cdp;
 pub mod flashloan;
 pub mod instantiate;
 pub mod interest;
 pub mod liquidation;
+pub mod listing;
+pub mod withdrawal;
@@ -0,0 +1,171 @@
+use crate::helpers::{init::TestHelper, methods::*};
+use radix_engine_interface::prelude::*;
+
+#[test]
+fn test_partial_redeem_and_withdrawal_queue() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+
+    let usd = helper.faucet.usdc_resource_address;
+    let usd_pu = helper.market.pools.get(&usd).unwrap().clone().1;
+    let withdrawal_request = helper.market.withdrawal_request_resource_address;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(1_000))
+        .expect_commit_success();
+
+    // SET UP A BORROWER USING 70% OF THE LIQUIDITY
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(borrower_account);
+    helper.test_runner.load_account_from_faucet(borrower_account);
+
+    market_create_cdp(&mut helper, borrower_key, borrower_account, vec![(XRD, dec!(30_000))])
+        .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(700))
+        .expect_commit_success();
+
+    // A full redeem fails for lack of liquidity
+    market_redeem(&mut helper, lp_user_key, lp_user_account, usd_pu, dec!(500))
+        .expect_commit_failure();
+
+    // A partial redeem returns the available liquidity and the remaining pool units
+    let usd_balance = helper.test_runner.get_component_balance(lp_user_account, usd);
+
+    market_redeem_partial(&mut helper, lp_user_key, lp_user_account, usd_pu, dec!(500))
+        .expect_commit_success();
+
+    let redeemed_amount =
+        helper.test_runner.get_component_balance(lp_user_account, usd) - usd_balance;
+
+    assert!(redeemed_amount > dec!(299.99) && redeemed_amount <= dec!(300));
+    assert!(helper.test_runner.get_component_balance(lp_user_account, usd_pu) > dec!(699.99));
+
+    // The remaining pool units wait in the withdrawal queue
+    market_request_withdrawal(&mut helper, lp_user_key, lp_user_account, usd_pu, dec!(200))
+        .expect_commit_success();
+
+    assert_eq!(
+        helper.test_runner.get_component_balance(lp_user_account, withdrawal_request),
+        dec!(1)
+    );
+
+    // The queue is paid with the repayment
+    market_repay(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(100))
+        .expect_commit_success();
+
+    let usd_balance = helper.test_runner.get_component_balance(lp_user_account, usd);
+
+    market_settle_withdrawal(&mut helper, lp_user_key, lp_user_account, "claim_withdrawal", 1u64)
+        .expect_commit_success();
+
+    let claimed_amount =
+        helper.test_runner.get_component_balance(lp_user_account, usd) - usd_balance;
+
+    assert!(claimed_amount > dec!(99.99) && claimed_amount <= dec!(100));
+
+    // The request is kept until fully paid
+    assert_eq!(
+        helper.test_runner.get_component_balance(lp_user_account, withdrawal_request),
+        dec!(1)
+    );
+
+    // Cancelling returns the pool units not redeemed yet
+    let usd_pu_balance = helper.test_runner.get_component_balance(lp_user_account, usd_pu);
+
+    market_settle_withdrawal(&mut helper, lp_user_key, lp_user_account, "cancel_withdrawal", 1u64)
+        .expect_commit_success();
+
+    let returned_units =
+        helper.test_runner.get_component_balance(lp_user_account, usd_pu) - usd_pu_balance;
+
+    assert!(returned_units > dec!(99.99) && returned_units <= dec!(100));
+
+    assert_eq!(
+        helper.test_runner.get_component_balance(lp_user_account, withdrawal_request),
+        dec!(0)
+    );
+}
+
+#[test]
+fn test_withdrawal_queue_dust_and_cancelled_requests() {
+    let mut helper = TestHelper::new();
+
+    const T2024: i64 = 1704067200;
+
+    let usd = helper.faucet.usdc_resource_address;
+    let usd_pu = helper.market.pools.get(&usd).unwrap().clone().1;
+
+    helper
+        .test_runner
+        .advance_to_round_at_timestamp(Round::of(1), T2024);
+    admin_update_price(&mut helper, 1u64, usd, dec!(25)).expect_commit_success();
+
+    // SET UP A LP PROVIDER
+    let (lp_user_key, _, lp_user_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    helper.test_runner.load_account_from_faucet(lp_user_account);
+    get_resource(&mut helper, lp_user_key, lp_user_account, dec!(25_000), usd) //
+        .expect_commit_success();
+
+    market_contribute(&mut helper, lp_user_key, lp_user_account, usd, dec!(1_000))
+        .expect_commit_success();
+
+    // SET UP A BORROWER AND DRAIN THE LIQUIDITY
+    let (borrower_key, _, borrower_account) = helper.test_runner.new_allocated_account();
+    helper.test_runner.load_account_from_faucet(borrower_account);
+    helper.test_runner.load_account_from_faucet(borrower_account);
+
+    market_create_cdp(&mut helper, borrower_key, borrower_account, vec![(XRD, dec!(30_000))])
+        .expect_commit_success();
+
+    market_borrow(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(700))
+        .expect_commit_success();
+
+    market_redeem_partial(&mut helper, lp_user_key, lp_user_account, usd_pu, dec!(500))
+        .expect_commit_success();
+
+    // Dust requests are refused: 0.1 USD is worth 2.5 XRD, under the 10 XRD minimum
+    market_request_withdrawal(&mut helper, lp_user_key, lp_user_account, usd_pu, dec!(0.1))
+        .expect_commit_failure();
+
+    // Queue then cancel more requests than a repayment can skip
+    for _ in 0..25 {
+        market_request_withdrawal(&mut helper, lp_user_key, lp_user_account, usd_pu, dec!(1))
+            .expect_commit_success();
+    }
+
+    for request_id in 1..=25u64 {
+        market_settle_withdrawal(&mut helper, lp_user_key, lp_user_account, "cancel_withdrawal", request_id)
+            .expect_commit_success();
+    }
+
+    market_request_withdrawal(&mut helper, lp_user_key, lp_user_account, usd_pu, dec!(100))
+        .expect_commit_success();
+
+    // Repayments stay available and the cancelled slots are skipped over several calls
+    market_repay(&mut helper, borrower_key, borrower_account, 1u64, usd, dec!(100))
+        .expect_commit_success();
+
+    let usd_balance = helper.test_runner.get_component_balance(lp_user_account, usd);
+
+    market_settle_withdrawal(&mut helper, lp_user_key, lp_user_account, "claim_withdrawal", 26u64)
+        .expect_commit_success();
+
+    let claimed_amount =
+        helper.test_runner.get_component_balance(lp_user_account, usd) - usd_balance;
+
+    assert!(claimed_amount > dec!(99.99) && claimed_amount <= dec!(100));
+}
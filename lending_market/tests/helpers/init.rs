@@ -85,4 +85,14 @@ pub fn find_event_in_result<T: ScryptoDecode>(result: &CommitResult, event_type:
             None
         }
     })
-}
\ No newline at end of file
+}
+
+pub fn find_events_in_result<T: ScryptoDecode>(result: &CommitResult, event_type: &str) -> Vec<T> {
+    result.application_events.iter().filter_map(|(EventTypeIdentifier(_, id), event)|{
+        if id == &event_type {
+            Some(scrypto_decode::<T>(&event).unwrap())
+        } else {
+            None
+        }
+    }).collect()
+}
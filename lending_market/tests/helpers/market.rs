@@ -16,6 +16,7 @@ pub struct MarketTestHelper {
     pub market_reserve_collector_badge: ResourceAddress,
     pub liquidation_term_resource_address: ResourceAddress,
     pub market_liquidator_badge: ResourceAddress,
+    pub withdrawal_request_resource_address: ResourceAddress,
     pub pools: IndexMap<ResourceAddress, (ComponentAddress, ResourceAddress)>,
 }
 
@@ -58,7 +59,7 @@ impl MarketTestHelper {
                 market_package_address,
                 "LendingMarket",
                 "instantiate",
-                manifest_args!((10u8, dec!(0.4), dec!(0.98))),
+                manifest_args!((10u8, dec!(0.4), dec!(0.98), None::<Decimal>, dec!(0.95), dec!(0), None::<i64>, dec!(0.9), dec!(10), dec!(0), dec!(0.5), dec!(0.8))),
             )
             .deposit_batch(owner_account_address);
 
@@ -80,6 +81,7 @@ impl MarketTestHelper {
         let batch_flashloan_resource_address = resource_addresses_created[3];
         let liquidation_term_resource_address = resource_addresses_created[3];
         let market_liquidator_badge = resource_addresses_created[4];
+        let withdrawal_request_resource_address = resource_addresses_created[5];
 
         // // Pools
 
@@ -425,6 +427,7 @@ impl MarketTestHelper {
             market_reserve_collector_badge,
             liquidation_term_resource_address,
             market_liquidator_badge,
+            withdrawal_request_resource_address,
             pools,
         }
     }
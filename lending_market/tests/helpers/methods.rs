@@ -220,6 +220,44 @@ pub fn market_update_pool_state(
     receipt
 }
 
+pub fn market_get_pool_history(
+    helper: &mut TestHelper,
+    res_address: ResourceAddress,
+    from: i64,
+    to: i64,
+) -> TransactionReceiptV1 {
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        helper.market.market_component_address,
+        "get_pool_history",
+        manifest_args!(res_address, from, to),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "get_pool_history".into()),
+        vec![NonFungibleGlobalId::from_public_key(
+            &helper.owner_public_key,
+        )],
+    )
+}
+
+pub fn market_get_pool_revenue(
+    helper: &mut TestHelper,
+    res_address: ResourceAddress,
+) -> TransactionReceiptV1 {
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        helper.market.market_component_address,
+        "get_pool_revenue",
+        manifest_args!(res_address),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "get_pool_revenue".into()),
+        vec![NonFungibleGlobalId::from_public_key(
+            &helper.owner_public_key,
+        )],
+    )
+}
+
 pub fn market_list_liquidable_cdps(
     helper: &mut TestHelper,
 ) -> TransactionReceiptV1 {
@@ -243,6 +281,133 @@ pub fn market_list_liquidable_cdps(
     receipt
 }
 
+pub fn market_list_cdps(
+    helper: &mut TestHelper,
+    filter: (
+        Option<Decimal>,
+        Option<Decimal>,
+        Option<ResourceAddress>,
+        Option<ResourceAddress>,
+        Option<Decimal>,
+        Option<bool>,
+    ),
+    cursor: Option<u64>,
+    page_size: u64,
+) -> TransactionReceiptV1 {
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        helper.market.market_component_address,
+        "list_cdps",
+        manifest_args!(filter, cursor, page_size),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "list_cdps".into()),
+        vec![NonFungibleGlobalId::from_public_key(
+            &helper.owner_public_key,
+        )],
+    )
+}
+
+pub fn market_list_cdps_by_pool(
+    helper: &mut TestHelper,
+    pool_res_address: ResourceAddress,
+    cursor: Option<u64>,
+    limit: u64,
+) -> TransactionReceiptV1 {
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        helper.market.market_component_address,
+        "list_cdps_by_pool",
+        manifest_args!(pool_res_address, cursor, limit),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "list_cdps_by_pool".into()),
+        vec![NonFungibleGlobalId::from_public_key(
+            &helper.owner_public_key,
+        )],
+    )
+}
+
+pub fn market_audit_listed_asset(
+    helper: &mut TestHelper,
+    pool_res_address: ResourceAddress,
+) -> TransactionReceiptV1 {
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        helper.market.market_component_address,
+        "audit_listed_asset",
+        manifest_args!(pool_res_address),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "audit_listed_asset".into()),
+        vec![NonFungibleGlobalId::from_public_key(
+            &helper.owner_public_key,
+        )],
+    )
+}
+
+pub fn market_get_pool_unit_price(
+    helper: &mut TestHelper,
+    pool_unit_res_address: ResourceAddress,
+) -> TransactionReceiptV1 {
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        helper.market.market_component_address,
+        "get_pool_unit_price",
+        manifest_args!(pool_unit_res_address),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "get_pool_unit_price".into()),
+        vec![NonFungibleGlobalId::from_public_key(
+            &helper.owner_public_key,
+        )],
+    )
+}
+
+pub fn pool_preview(
+    helper: &mut TestHelper,
+    pool_component_address: ComponentAddress,
+    method_name: &str,
+    amount: Decimal,
+) -> TransactionReceiptV1 {
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        pool_component_address,
+        method_name,
+        manifest_args!(amount),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, method_name.into()),
+        vec![NonFungibleGlobalId::from_public_key(
+            &helper.owner_public_key,
+        )],
+    )
+}
+
+pub fn market_get_account_summary(
+    helper: &mut TestHelper,
+    cdp_ids: Vec<u64>,
+    pool_unit_holdings: Vec<(ResourceAddress, Decimal)>,
+) -> TransactionReceiptV1 {
+    let cdp_ids: Vec<NonFungibleLocalId> = cdp_ids
+        .into_iter()
+        .map(NonFungibleLocalId::integer)
+        .collect();
+
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        helper.market.market_component_address,
+        "get_account_summary",
+        manifest_args!(cdp_ids, pool_unit_holdings),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "get_account_summary".into()),
+        vec![NonFungibleGlobalId::from_public_key(
+            &helper.owner_public_key,
+        )],
+    )
+}
+
 pub fn market_show_cdp(
     helper: &mut TestHelper,
     cdp_id: u64,
@@ -268,6 +433,132 @@ pub fn market_show_cdp(
 }
 
 
+pub fn market_burn_cdp(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    cdp_ids: Vec<u64>,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(
+            user_account_address,
+            helper.market.cdp_resource_address,
+            cdp_ids.into_iter().map(NonFungibleLocalId::integer),
+        )
+        .take_all_from_worktop(helper.market.cdp_resource_address, "cdp_bucket")
+        .with_name_lookup(|builder, lookup| {
+            let bucket = lookup.bucket("cdp_bucket");
+
+            builder.call_method(
+                helper.market.market_component_address,
+                "burn_cdp",
+                manifest_args!(bucket),
+            )
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "burn_cdp".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
+pub fn market_migrate_cdp_positions(
+    helper: &mut TestHelper,
+    cdp_ids: Vec<u64>,
+) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new().lock_fee_from_faucet().call_method(
+        helper.market.market_component_address,
+        "migrate_cdp_positions",
+        manifest_args!(cdp_ids
+            .into_iter()
+            .map(NonFungibleLocalId::integer)
+            .collect::<Vec<NonFungibleLocalId>>()),
+    );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "migrate_cdp_positions".into()),
+        vec![],
+    )
+}
+
+pub fn market_merge_cdps(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    cdp_id: u64,
+    merged_cdp_ids: Vec<u64>,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungible(
+            user_account_address,
+            NonFungibleGlobalId::new(
+                helper.market.cdp_resource_address,
+                NonFungibleLocalId::Integer(cdp_id.into()),
+            ),
+        )
+        .pop_from_auth_zone("cdp_proof")
+        .withdraw_non_fungibles_from_account(
+            user_account_address,
+            helper.market.cdp_resource_address,
+            merged_cdp_ids.into_iter().map(NonFungibleLocalId::integer),
+        )
+        .take_all_from_worktop(helper.market.cdp_resource_address, "cdp_bucket")
+        .with_name_lookup(|builder, lookup| {
+            let proof = lookup.proof("cdp_proof");
+            let bucket = lookup.bucket("cdp_bucket");
+
+            builder.call_method(
+                helper.market.market_component_address,
+                "merge_cdps",
+                manifest_args!(proof, bucket),
+            )
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "merge_cdps".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
+pub fn market_split_cdp(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    cdp_id: u64,
+    collaterals: Vec<(ResourceAddress, Decimal)>,
+    loans: Vec<(ResourceAddress, Decimal)>,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungible(
+            user_account_address,
+            NonFungibleGlobalId::new(
+                helper.market.cdp_resource_address,
+                NonFungibleLocalId::Integer(cdp_id.into()),
+            ),
+        )
+        .pop_from_auth_zone("cdp_proof")
+        .with_name_lookup(|builder, lookup| {
+            let proof = lookup.proof("cdp_proof");
+
+            builder.call_method(
+                helper.market.market_component_address,
+                "split_cdp",
+                manifest_args!(proof, collaterals, loans),
+            )
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "split_cdp".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
 pub fn market_contribute(
     helper: &mut TestHelper,
     user_public_key: Secp256k1PublicKey,
@@ -305,21 +596,136 @@ pub fn market_redeem(
 ) -> TransactionReceipt {
     let manifest_builder = ManifestBuilder::new()
         .lock_fee_from_faucet()
-        .withdraw_from_account(user_account_address, res_address, amount)
-        .take_all_from_worktop(res_address, "res_bucket")
+        .withdraw_from_account(user_account_address, res_address, amount)
+        .take_all_from_worktop(res_address, "res_bucket")
+        .with_name_lookup(|builder, lookup| {
+            let bucket = lookup.bucket("res_bucket");
+
+            builder.call_method(
+                helper.market.market_component_address,
+                "redeem",
+                manifest_args!(bucket),
+            )
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "redeem".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
+pub fn market_migrate_pool_units(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    pool_unit_res_address: ResourceAddress,
+    amount: Decimal,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account_address, pool_unit_res_address, amount)
+        .take_all_from_worktop(pool_unit_res_address, "pool_unit_bucket")
+        .with_name_lookup(|builder, lookup| {
+            let bucket = lookup.bucket("pool_unit_bucket");
+
+            builder.call_method(
+                helper.market.market_component_address,
+                "migrate_pool_units",
+                manifest_args!(bucket),
+            )
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "migrate_pool_units".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
+pub fn market_redeem_partial(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    res_address: ResourceAddress,
+    amount: Decimal,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account_address, res_address, amount)
+        .take_all_from_worktop(res_address, "res_bucket")
+        .with_name_lookup(|builder, lookup| {
+            let bucket = lookup.bucket("res_bucket");
+            builder.call_method(
+                helper.market.market_component_address,
+                "redeem_partial",
+                manifest_args!(bucket),
+            )
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "redeem_partial".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
+pub fn market_request_withdrawal(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    res_address: ResourceAddress,
+    amount: Decimal,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account_address, res_address, amount)
+        .take_all_from_worktop(res_address, "res_bucket")
+        .with_name_lookup(|builder, lookup| {
+            let bucket = lookup.bucket("res_bucket");
+            builder.call_method(
+                helper.market.market_component_address,
+                "request_withdrawal",
+                manifest_args!(bucket),
+            )
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "request_withdrawal".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
+pub fn market_settle_withdrawal(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    method_name: &str,
+    request_id: u64,
+) -> TransactionReceipt {
+    let request_res_address = helper.market.withdrawal_request_resource_address;
+
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(
+            user_account_address,
+            request_res_address,
+            vec![NonFungibleLocalId::integer(request_id)],
+        )
+        .take_all_from_worktop(request_res_address, "request_bucket")
         .with_name_lookup(|builder, lookup| {
-            let bucket = lookup.bucket("res_bucket");
-
+            let bucket = lookup.bucket("request_bucket");
             builder.call_method(
                 helper.market.market_component_address,
-                "redeem",
+                method_name,
                 manifest_args!(bucket),
             )
         })
         .deposit_batch(user_account_address);
 
     helper.test_runner.execute_manifest(
-        build_and_dump_to_fs(manifest_builder, "redeem".into()),
+        build_and_dump_to_fs(manifest_builder, method_name.into()),
         vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
     )
 }
@@ -477,6 +883,83 @@ pub fn market_borrow(
     )
 }
 
+pub fn market_update_collateral_usage(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    cdp_id: u64,
+    res_address: ResourceAddress,
+    use_as_collateral: bool,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungible(
+            user_account_address,
+            NonFungibleGlobalId::new(
+                helper.market.cdp_resource_address,
+                NonFungibleLocalId::Integer(cdp_id.into()),
+            ),
+        )
+        .pop_from_auth_zone("cdp_proof")
+        .with_name_lookup(|builder, lookup| {
+            let proof = lookup.proof("cdp_proof");
+
+            builder.call_method(
+                helper.market.market_component_address,
+                "update_collateral_usage",
+                manifest_args!(proof, res_address, use_as_collateral),
+            )
+        });
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "update_collateral_usage".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
+/// Manifest counterpart of the market `UpdateCDPInput`
+#[derive(ManifestSbor)]
+#[allow(dead_code)]
+pub enum ManifestUpdateCDPInput {
+    KeyImageURL(String),
+    Name(String),
+    Description(String),
+    LiquidationPreference(Vec<ResourceAddress>),
+}
+
+pub fn market_update_cdp(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    cdp_id: u64,
+    value: ManifestUpdateCDPInput,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungible(
+            user_account_address,
+            NonFungibleGlobalId::new(
+                helper.market.cdp_resource_address,
+                NonFungibleLocalId::Integer(cdp_id.into()),
+            ),
+        )
+        .pop_from_auth_zone("cdp_proof")
+        .with_name_lookup(|builder, lookup| {
+            let proof = lookup.proof("cdp_proof");
+
+            builder.call_method(
+                helper.market.market_component_address,
+                "update_cdp",
+                manifest_args!(proof, value),
+            )
+        });
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "update_cdp".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
 pub fn market_repay(
     helper: &mut TestHelper,
     user_public_key: Secp256k1PublicKey,
@@ -533,6 +1016,25 @@ pub fn check_cdp_for_liquidation(
     )
 }
 
+pub fn market_quote_liquidation(
+    helper: &mut TestHelper,
+    cdp_id: u64,
+    repay_assets: Vec<ResourceAddress>,
+    seize_order: Vec<ResourceAddress>,
+) -> TransactionReceipt {
+    let manifest_builder = ManifestBuilder::new()
+    .lock_fee_from_faucet()
+    .call_method(
+        helper.market.market_component_address,
+        "quote_liquidation",
+        manifest_args!(NonFungibleLocalId::integer(cdp_id), repay_assets, seize_order),
+    );
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "quote_liquidation".into()),
+        vec![NonFungibleGlobalId::from_public_key(&helper.owner_public_key)],
+    )
+}
+
 pub fn market_liquidation(
     helper: &mut TestHelper,
     user_public_key: Secp256k1PublicKey,
@@ -659,6 +1161,134 @@ pub fn market_fast_liquidation(
     )
 }
 
+pub fn market_fast_liquidation_with_badge(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    liquidator_badge_id: u64,
+    cdp_id: u64,
+    payments: Vec<(ResourceAddress, Decimal)>,
+    requested_collaterals: Vec<ResourceAddress>,
+) -> TransactionReceipt {
+    let mut payment_buckets = Vec::<ManifestBucket>::new();
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungible(
+            user_account_address,
+            NonFungibleGlobalId::new(
+                helper.market.market_liquidator_badge,
+                NonFungibleLocalId::integer(liquidator_badge_id),
+            ),
+        )
+        .create_proof_from_auth_zone_of_non_fungibles(
+            helper.market.market_liquidator_badge,
+            vec![NonFungibleLocalId::integer(liquidator_badge_id)],
+            "liquidator_badge_proof",
+        )
+        .with_name_lookup(|builder, _lookup| {
+            let (_, newbuilder) =
+                payments
+                    .iter()
+                    .fold((0, builder), |(i, builder), (res_address, amount)| {
+                        (
+                            i + 1,
+                            builder
+                                .withdraw_from_account(user_account_address, *res_address, *amount)
+                                .take_all_from_worktop(
+                                    *res_address,
+                                    format!("payment_bucket_{}", i),
+                                )
+                                .with_name_lookup(|builder, lookup| {
+                                    payment_buckets
+                                        .push(lookup.bucket(format!("payment_bucket_{}", i)));
+                                    builder
+                                }),
+                        )
+                    });
+
+            newbuilder.with_name_lookup(|builder, lookup| {
+                let proof = lookup.proof("liquidator_badge_proof");
+
+                builder.call_method(
+                    helper.market.market_component_address,
+                    "fast_liquidation_with_badge",
+                    manifest_args!(
+                        NonFungibleLocalId::integer(cdp_id),
+                        payment_buckets,
+                        requested_collaterals,
+                        proof
+                    ),
+                )
+            })
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "fast_liquidation_with_badge".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
+pub fn market_batch_fast_liquidation(
+    helper: &mut TestHelper,
+    user_public_key: Secp256k1PublicKey,
+    user_account_address: ComponentAddress,
+    liquidator_badge_id: u64,
+    cdps: Vec<(u64, Vec<ResourceAddress>)>,
+    payments: Vec<(ResourceAddress, Decimal)>,
+) -> TransactionReceipt {
+    let cdps: Vec<(NonFungibleLocalId, Vec<ResourceAddress>)> = cdps
+        .into_iter()
+        .map(|(cdp_id, requested_collaterals)| {
+            (NonFungibleLocalId::integer(cdp_id), requested_collaterals)
+        })
+        .collect();
+
+    let mut payment_buckets = Vec::<ManifestBucket>::new();
+    let manifest_builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungible(
+            user_account_address,
+            NonFungibleGlobalId::new(
+                helper.market.market_liquidator_badge,
+                NonFungibleLocalId::integer(liquidator_badge_id),
+            ),
+        )
+        .with_name_lookup(|builder, _lookup| {
+            let (_, newbuilder) =
+                payments
+                    .iter()
+                    .fold((0, builder), |(i, builder), (res_address, amount)| {
+                        (
+                            i + 1,
+                            builder
+                                .withdraw_from_account(user_account_address, *res_address, *amount)
+                                .take_all_from_worktop(
+                                    *res_address,
+                                    format!("payment_bucket_{}", i),
+                                )
+                                .with_name_lookup(|builder, lookup| {
+                                    payment_buckets
+                                        .push(lookup.bucket(format!("payment_bucket_{}", i)));
+                                    builder
+                                }),
+                        )
+                    });
+
+            newbuilder.call_method(
+                helper.market.market_component_address,
+                "batch_fast_liquidation",
+                manifest_args!(cdps, payment_buckets),
+            )
+        })
+        .deposit_batch(user_account_address);
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest_builder, "batch_fast_liquidation".into()),
+        vec![NonFungibleGlobalId::from_public_key(&user_public_key)],
+    )
+}
+
 pub fn market_take_batch_flashloan(
     helper: &mut TestHelper,
     _user_public_key: Secp256k1PublicKey,
@@ -675,6 +1305,14 @@ pub fn market_take_batch_flashloan(
 
 pub fn market_collect_reserve(
     helper: &mut TestHelper,
+) -> TransactionReceipt {
+    market_collect_pool_reserve(helper, None, None)
+}
+
+pub fn market_collect_pool_reserve(
+    helper: &mut TestHelper,
+    pool_res_address: Option<ResourceAddress>,
+    min_amount: Option<Decimal>,
 ) -> TransactionReceipt {
     let manifest_builder = ManifestBuilder::new()
         .lock_fee_from_faucet()
@@ -689,7 +1327,7 @@ pub fn market_collect_reserve(
             builder.call_method(
                 helper.market.market_component_address,
                 "collect_reserve",
-                manifest_args!(),
+                manifest_args!(pool_res_address, min_amount),
             )
         })
         .deposit_batch(helper.owner_account_address);
@@ -700,6 +1338,94 @@ pub fn market_collect_reserve(
     )
 }
 
+pub fn admin_update_listing_status(
+    helper: &mut TestHelper,
+    method_name: &str,
+    arguments: ManifestArgs,
+) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(
+            helper.owner_account_address,
+            helper.market.market_admin_badge,
+            vec![
+                NonFungibleLocalId::integer(1),
+                NonFungibleLocalId::integer(2),
+                NonFungibleLocalId::integer(3),
+                NonFungibleLocalId::integer(4),
+            ],
+        )
+        .call_method(
+            helper.market.market_component_address,
+            method_name,
+            arguments,
+        );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, method_name.into()),
+        vec![NonFungibleGlobalId::from_public_key(&helper.owner_public_key)],
+    )
+}
+
+pub fn admin_update_reserve_policy(
+    helper: &mut TestHelper,
+    pool_res_address: ResourceAddress,
+    min_buffer: Decimal,
+    auto_deposit: bool,
+) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(
+            helper.owner_account_address,
+            helper.market.market_admin_badge,
+            vec![
+                NonFungibleLocalId::integer(1),
+                NonFungibleLocalId::integer(2),
+                NonFungibleLocalId::integer(3),
+                NonFungibleLocalId::integer(4),
+            ],
+        )
+        .call_method(
+            helper.market.market_component_address,
+            "update_reserve_policy",
+            manifest_args!(pool_res_address, min_buffer, auto_deposit),
+        );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "update_reserve_policy".into()),
+        vec![NonFungibleGlobalId::from_public_key(&helper.owner_public_key)],
+    )
+}
+
+pub fn admin_cover_shortfall(
+    helper: &mut TestHelper,
+    pool_res_address: ResourceAddress,
+    amount: Decimal,
+) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(
+            helper.owner_account_address,
+            helper.market.market_admin_badge,
+            vec![
+                NonFungibleLocalId::integer(1),
+                NonFungibleLocalId::integer(2),
+                NonFungibleLocalId::integer(3),
+                NonFungibleLocalId::integer(4),
+            ],
+        )
+        .call_method(
+            helper.market.market_component_address,
+            "cover_shortfall",
+            manifest_args!(pool_res_address, amount),
+        );
+
+    helper.test_runner.execute_manifest(
+        build_and_dump_to_fs(manifest, "cover_shortfall".into()),
+        vec![NonFungibleGlobalId::from_public_key(&helper.owner_public_key)],
+    )
+}
+
 pub fn market_update_config(
     helper: &mut TestHelper,
     user_public_key: Secp256k1PublicKey,
@@ -1,5 +1,5 @@
 use indexmap::IndexMap;
-use lending_market::modules::{cdp_data::*, cdp_health_checker::{CDPHealthChecker, ExtendedCollateralPositionData, ExtendedLoanPositionData, PositionData}, liquidation_threshold::LiquidationThreshold};
+use lending_market::modules::{cdp_data::*, cdp_health_checker::{CDPHealthChecker, ExtendedCollateralPositionData, ExtendedLoanPositionData, PositionData}, liquidation_threshold::LiquidationThreshold, market_config::MarketConfig};
 use scrypto_test::prelude::*;
 
 use crate::helpers::init::TestHelper;
@@ -20,12 +20,19 @@ fn test_get_collateral_units() {
         minted_at: 0,
         updated_at: 0,
         liquidable: None,
+        health_band: CDPHealthBand::Healthy,
     };
     let wrapped_cdp_data = WrappedCDPData {
         cdp_data,
         cdp_id: 1u64.into(),
         collateral_updated: false,
         loan_updated: false,
+        display_updated: false,
+        updated_pools: IndexSet::new(),
+        legacy_positions: false,
+        disabled_collaterals: IndexSet::new(),
+        liquidation_preference: vec![],
+        liquidation_deadline: None,
     };
     assert_eq!(wrapped_cdp_data.get_collateral_units(res_address), pdec!(10));
 }
@@ -46,12 +53,19 @@ fn test_get_loan_unit() {
         minted_at: 0,
         updated_at: 0,
         liquidable: None,
+        health_band: CDPHealthBand::Healthy,
     };
     let wrapped_cdp_data = WrappedCDPData {
         cdp_data,
         cdp_id: 1u64.into(),
         collateral_updated: false,
         loan_updated: false,
+        display_updated: false,
+        updated_pools: IndexSet::new(),
+        legacy_positions: false,
+        disabled_collaterals: IndexSet::new(),
+        liquidation_preference: vec![],
+        liquidation_deadline: None,
     };
     assert_eq!(wrapped_cdp_data.get_loan_units(res_address), pdec!(10));
 }
@@ -70,10 +84,17 @@ fn test_update_collateral() {
             minted_at: 0,
             updated_at: 0,
             liquidable: None,
+            health_band: CDPHealthBand::Healthy,
         },
         cdp_id: 1u64.into(),
         collateral_updated: false,
         loan_updated: false,
+        display_updated: false,
+        updated_pools: IndexSet::new(),
+        legacy_positions: false,
+        disabled_collaterals: IndexSet::new(),
+        liquidation_preference: vec![],
+        liquidation_deadline: None,
     };
     wrapped_cdp_data
         .update_collateral(res_address.clone(), pdec!(10))
@@ -84,6 +105,38 @@ fn test_update_collateral() {
     assert_eq!(wrapped_cdp_data.cdp_data.collaterals, collaterals);
 }
 
+#[test]
+fn test_update_collateral_negative_new_position() {
+    let mut wrapped_cdp_data = WrappedCDPData {
+        cdp_data: CollaterizedDebtPositionData {
+            key_image_url: "url".to_string(),
+            name: "name".to_string(),
+            description: "description".to_string(),
+            cdp_type: CDPType::Standard,
+            collaterals: IndexMap::new(),
+            loans: IndexMap::new(),
+            minted_at: 0,
+            updated_at: 0,
+            liquidable: None,
+            health_band: CDPHealthBand::Healthy,
+        },
+        cdp_id: 1u64.into(),
+        collateral_updated: false,
+        loan_updated: false,
+        display_updated: false,
+        updated_pools: IndexSet::new(),
+        legacy_positions: false,
+        disabled_collaterals: IndexSet::new(),
+        liquidation_preference: vec![],
+        liquidation_deadline: None,
+    };
+
+    assert!(wrapped_cdp_data.update_collateral(XRD, pdec!(-10)).is_err());
+    assert!(wrapped_cdp_data.update_loan(XRD, pdec!(-10)).is_err());
+    assert!(wrapped_cdp_data.cdp_data.collaterals.is_empty());
+    assert!(wrapped_cdp_data.cdp_data.loans.is_empty());
+}
+
 #[test]
 fn test_update_loan() {
     let res_address = XRD;
@@ -98,10 +151,17 @@ fn test_update_loan() {
             minted_at: 0,
             updated_at: 0,
             liquidable: None,
+            health_band: CDPHealthBand::Healthy,
         },
         cdp_id: 1u64.into(),
         collateral_updated: false,
         loan_updated: false,
+        display_updated: false,
+        updated_pools: IndexSet::new(),
+        legacy_positions: false,
+        disabled_collaterals: IndexSet::new(),
+        liquidation_preference: vec![],
+        liquidation_deadline: None,
     };
     wrapped_cdp_data
         .update_loan(res_address.clone(), pdec!(10))
@@ -111,6 +171,58 @@ fn test_update_loan() {
     assert_eq!(wrapped_cdp_data.cdp_data.loans, loans);
 }
 
+#[test]
+fn test_merge() {
+    let helper = TestHelper::new();
+    let btc = helper.faucet.btc_resource_address;
+
+    let new_cdp = |collaterals: IndexMap<ResourceAddress, PreciseDecimal>, loans: IndexMap<ResourceAddress, PreciseDecimal>| WrappedCDPData {
+        cdp_data: CollaterizedDebtPositionData {
+            key_image_url: "url".to_string(),
+            name: "name".to_string(),
+            description: "description".to_string(),
+            cdp_type: CDPType::Standard,
+            collaterals,
+            loans,
+            minted_at: 0,
+            updated_at: 0,
+            liquidable: None,
+            health_band: CDPHealthBand::Healthy,
+        },
+        cdp_id: 1u64.into(),
+        collateral_updated: false,
+        loan_updated: false,
+        display_updated: false,
+        updated_pools: IndexSet::new(),
+        legacy_positions: false,
+        disabled_collaterals: IndexSet::new(),
+        liquidation_preference: vec![],
+        liquidation_deadline: None,
+    };
+
+    let mut collaterals = IndexMap::new();
+    collaterals.insert(XRD, pdec!(10));
+    let mut wrapped_cdp_data = new_cdp(collaterals, IndexMap::new());
+
+    let mut collaterals = IndexMap::new();
+    collaterals.insert(XRD, pdec!(5));
+    collaterals.insert(btc, pdec!(1));
+    let mut loans = IndexMap::new();
+    loans.insert(btc, pdec!(0.5));
+    let other = new_cdp(collaterals, loans);
+
+    assert!(!other.is_empty());
+    assert!(new_cdp(IndexMap::new(), IndexMap::new()).is_empty());
+
+    wrapped_cdp_data.merge(&other).unwrap();
+
+    assert_eq!(wrapped_cdp_data.get_collateral_units(XRD), pdec!(15));
+    assert_eq!(wrapped_cdp_data.get_collateral_units(btc), pdec!(1));
+    assert_eq!(wrapped_cdp_data.get_loan_units(btc), pdec!(0.5));
+    assert!(wrapped_cdp_data.collateral_updated);
+    assert!(wrapped_cdp_data.loan_updated);
+}
+
 #[test]
 fn test_ltv() {
     let helper = TestHelper::new();
@@ -131,6 +243,12 @@ fn test_ltv() {
             default_value: dec!(0.7),
             ..LiquidationThreshold::default()
         },
+        borrow_ltv: LiquidationThreshold {
+            identical_resource: Some(dec!(0.7)),
+            identical_asset_type: Some(dec!(0.7)),
+            default_value: dec!(0.7),
+            ..LiquidationThreshold::default()
+        },
         pool_res_address: helper.faucet.btc_resource_address,
         price: dec!(2538907.8490715)
     });
@@ -149,6 +267,12 @@ fn test_ltv() {
             default_value: dec!(0.7),
             ..LiquidationThreshold::default()
         },
+        borrow_ltv: LiquidationThreshold {
+            identical_resource: Some(dec!(0.7)),
+            identical_asset_type: Some(dec!(0.7)),
+            default_value: dec!(0.7),
+            ..LiquidationThreshold::default()
+        },
         pool_res_address: XRD,
         price: dec!(1)
     });
@@ -162,8 +286,10 @@ fn test_ltv() {
             value: dec!(0)
         },
         discounted_collateral_value: dec!(0),
+        discounted_borrow_collateral_value: dec!(0),
         pool_res_address: helper.faucet.eth_resource_address,
         loan_close_factor: dec!(0),
+        wound_down: false,
         price: dec!(107211.80214007)
     });
 
@@ -173,10 +299,145 @@ fn test_ltv() {
         loan_positions,
         self_closable_loan_value: dec!(0),
         total_loan_to_value_ratio: dec!(0),
-        total_loan_value: dec!(0)
+        total_borrow_loan_to_value_ratio: dec!(0),
+        total_loan_value: dec!(0),
+        stale_price_pools: vec![]
     };
 
     health_check.check_cdp().unwrap();
 
-    assert_eq!(dec!(0.899431648740609894), health_check.total_loan_to_value_ratio)
+    assert_eq!(dec!(0.899431648740609894), health_check.total_loan_to_value_ratio);
+    assert_eq!(
+        health_check.total_loan_to_value_ratio,
+        health_check.total_borrow_loan_to_value_ratio
+    );
+
+    // A lower borrow LTV blocks risk-increasing actions without making the CDP liquidable
+    health_check.collateral_positions.iter_mut().for_each(|(_, collateral_position)| {
+        collateral_position.borrow_ltv = LiquidationThreshold {
+            default_value: dec!(0.6),
+            ..LiquidationThreshold::default()
+        };
+    });
+
+    assert!(health_check.check_cdp().is_err());
+    assert!(health_check.total_borrow_loan_to_value_ratio > Decimal::ONE);
+    assert!(health_check.can_liquidate().is_err());
+}
+
+#[test]
+fn test_health_band() {
+    let mut market_config = MarketConfig {
+        max_cdp_position: 10,
+        max_liquidable_value: dec!(0.4),
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
+    };
+
+    assert_eq!(CDPHealthBand::from_ltv(dec!(0), &market_config), CDPHealthBand::Healthy);
+    assert_eq!(CDPHealthBand::from_ltv(dec!(0.5), &market_config), CDPHealthBand::Moderate);
+    assert_eq!(CDPHealthBand::from_ltv(dec!(0.8), &market_config), CDPHealthBand::Risky);
+    assert_eq!(CDPHealthBand::from_ltv(dec!(1), &market_config), CDPHealthBand::Risky);
+    assert_eq!(CDPHealthBand::from_ltv(dec!(1.01), &market_config), CDPHealthBand::Liquidable);
+
+    // The bands follow the market configuration
+    market_config.moderate_health_band_ltv = dec!(0.6);
+    market_config.risky_health_band_ltv = dec!(0.9);
+
+    assert_eq!(CDPHealthBand::from_ltv(dec!(0.5), &market_config), CDPHealthBand::Healthy);
+    assert_eq!(CDPHealthBand::from_ltv(dec!(0.8), &market_config), CDPHealthBand::Moderate);
+    assert_eq!(CDPHealthBand::from_ltv(dec!(0.9), &market_config), CDPHealthBand::Risky);
+}
+
+#[test]
+fn test_render_cdp_key_image_url() {
+    assert_eq!(
+        render_cdp_key_image_url(
+            "https://example.com/cdp/{cdp_id}.svg?band={health_band}",
+            &NonFungibleLocalId::integer(42),
+            &CDPHealthBand::Risky
+        ),
+        "https://example.com/cdp/42.svg?band=risky"
+    );
+}
+
+#[test]
+fn test_cdp_filter() {
+    let mut collaterals = IndexMap::new();
+    collaterals.insert(XRD, pdec!(10));
+    let cdp_health_data = CDPHealthData {
+        cdp_id: 1u64.into(),
+        cdp_data: CollaterizedDebtPositionData {
+            key_image_url: "url".to_string(),
+            name: "name".to_string(),
+            description: "description".to_string(),
+            cdp_type: CDPType::Standard,
+            collaterals,
+            loans: IndexMap::new(),
+            minted_at: 0,
+            updated_at: 0,
+            liquidable: None,
+            health_band: CDPHealthBand::Healthy,
+        },
+        total_collateral_value: dec!(10),
+        total_loan_value: dec!(6),
+        loan_to_value_ratio: dec!(0.6),
+        health_band: CDPHealthBand::Moderate,
+        liquidable: false,
+    };
+
+    assert!(CDPFilter::default().matches(&cdp_health_data));
+    assert!(CDPFilter {
+        min_loan_to_value_ratio: Some(dec!(0.5)),
+        max_loan_to_value_ratio: Some(dec!(0.6)),
+        collateral: Some(XRD),
+        min_loan_value: Some(dec!(6)),
+        liquidable: Some(false),
+        ..CDPFilter::default()
+    }
+    .matches(&cdp_health_data));
+    assert!(!CDPFilter { max_loan_to_value_ratio: Some(dec!(0.5)), ..CDPFilter::default() }.matches(&cdp_health_data));
+    assert!(!CDPFilter { loan: Some(XRD), ..CDPFilter::default() }.matches(&cdp_health_data));
+    assert!(!CDPFilter { min_loan_value: Some(dec!(7)), ..CDPFilter::default() }.matches(&cdp_health_data));
+    assert!(!CDPFilter { liquidable: Some(true), ..CDPFilter::default() }.matches(&cdp_health_data));
+}
+
+#[test]
+fn test_legacy_positions() {
+    let mut collaterals = IndexMap::new();
+    collaterals.insert(XRD, pdec!(1_000));
+
+    // Positions only stored in the NFT data of a CDP not migrated yet
+    let nft_data = CDPNonFungibleData {
+        key_image_url: "url".to_string(),
+        name: "name".to_string(),
+        description: "description".to_string(),
+        minted_at: 100,
+        cdp_type: CDPType::Standard,
+        health_band: CDPHealthBand::Healthy,
+        collaterals,
+        loans: IndexMap::new(),
+    };
+
+    let positions = nft_data.legacy_positions().unwrap();
+
+    assert_eq!(positions.collaterals.get(&XRD), Some(&pdec!(1_000)));
+    assert!(positions.loans.is_empty());
+    assert_eq!(positions.updated_at, 100);
+
+    // Nothing to migrate once the NFT positions are emptied
+    let migrated_nft_data = CDPNonFungibleData {
+        collaterals: IndexMap::new(),
+        ..nft_data
+    };
+
+    assert!(migrated_nft_data.legacy_positions().is_none());
 }
@@ -125,3 +125,57 @@ fn test_get_ratio_asset_type_1() {
     );
     assert_eq!(ratio, dec!(0.5));
 }
+
+#[test]
+fn test_scale() {
+    let liquidation_threshold = create_sample_liquidation_threshold().scale(dec!(0.5));
+
+    let res_a_1 = SECP256K1_SIGNATURE_VIRTUAL_BADGE;
+
+    assert_eq!(liquidation_threshold.identical_resource, Some(dec!(0.4)));
+    assert_eq!(liquidation_threshold.identical_asset_type, Some(dec!(0.05)));
+    assert_eq!(*liquidation_threshold.resource.get(&res_a_1).unwrap(), dec!(0.1));
+    assert_eq!(*liquidation_threshold.asset_type.get(&0).unwrap(), dec!(0.25));
+    assert_eq!(liquidation_threshold.default_value, dec!(0));
+    assert!(liquidation_threshold.check().is_ok());
+}
+
+#[test]
+fn test_check_below() {
+    let liquidation_threshold = create_sample_liquidation_threshold();
+
+    let res_a_1 = SECP256K1_SIGNATURE_VIRTUAL_BADGE;
+    let collateral_res = XRD;
+
+    let borrow_ltv = liquidation_threshold.scale(dec!(0.9));
+    assert!(borrow_ltv
+        .check_below(&liquidation_threshold, dec!(0), collateral_res, 3)
+        .is_ok());
+
+    // Ratios within the margin are rejected
+    assert!(borrow_ltv
+        .check_below(&liquidation_threshold, dec!(0.05), collateral_res, 3)
+        .is_ok());
+    assert!(borrow_ltv
+        .check_below(&liquidation_threshold, dec!(0.2), collateral_res, 3)
+        .is_err());
+
+    // Equal ratios are rejected
+    assert!(liquidation_threshold
+        .check_below(&liquidation_threshold, dec!(0), collateral_res, 3)
+        .is_err());
+
+    // A single entry above the liquidation threshold is rejected
+    let mut borrow_ltv = liquidation_threshold.scale(dec!(0.9));
+    borrow_ltv.resource.insert(res_a_1, dec!(0.25));
+    assert!(borrow_ltv
+        .check_below(&liquidation_threshold, dec!(0), collateral_res, 3)
+        .is_err());
+
+    // A default value applying where the liquidation threshold is zero is rejected
+    let mut borrow_ltv = liquidation_threshold.scale(dec!(0.9));
+    borrow_ltv.default_value = dec!(0.1);
+    assert!(borrow_ltv
+        .check_below(&liquidation_threshold, dec!(0), collateral_res, 3)
+        .is_err());
+}
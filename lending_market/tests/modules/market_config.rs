@@ -6,7 +6,16 @@ fn test_check_valid_max_cdp_position() {
     let market_config = MarketConfig {
         max_cdp_position: 10,
         max_liquidable_value: dec!(0.4),
-        liquidation_dex_swap_rate: dec!(1)
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
     };
 
     assert_eq!(market_config.check(), Ok(()));
@@ -17,7 +26,16 @@ fn test_check_invalid_max_cdp_position() {
     let market_config = MarketConfig {
         max_cdp_position: 0,
         max_liquidable_value: dec!(0.4),
-        liquidation_dex_swap_rate: dec!(1)
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
     };
 
     assert_eq!(
@@ -31,7 +49,16 @@ fn test_update_max_cdp_position_valid() {
     let mut market_config = MarketConfig {
         max_cdp_position: 10,
         max_liquidable_value: dec!(0.4),
-        liquidation_dex_swap_rate: dec!(1)
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
     };
 
     assert_eq!(
@@ -47,7 +74,16 @@ fn test_update_max_cdp_position_invalid() {
     let mut market_config = MarketConfig {
         max_cdp_position: 10,
         max_liquidable_value: dec!(0.4),
-        liquidation_dex_swap_rate: dec!(1)
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
     };
 
     assert_eq!(
@@ -56,13 +92,21 @@ fn test_update_max_cdp_position_invalid() {
     );
 }
 
-
 #[test]
 fn test_check_valid_max_liquidable_value() {
     let market_config = MarketConfig {
         max_cdp_position: 10,
         max_liquidable_value: dec!(0.4),
-        liquidation_dex_swap_rate: dec!(1)
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
     };
 
     assert_eq!(market_config.check(), Ok(()));
@@ -73,7 +117,16 @@ fn test_check_invalid_max_liquidable_value() {
     let market_config = MarketConfig {
         max_cdp_position: 10,
         max_liquidable_value: dec!(100),
-        liquidation_dex_swap_rate: dec!(1)
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
     };
 
     assert_eq!(
@@ -87,7 +140,16 @@ fn test_update_max_liquidable_value() {
     let mut market_config = MarketConfig {
         max_cdp_position: 10,
         max_liquidable_value: dec!(0.4),
-        liquidation_dex_swap_rate: dec!(1)
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
     };
 
     assert_eq!(
@@ -103,7 +165,16 @@ fn test_update_max_liquidable_value_invalid() {
     let mut market_config = MarketConfig {
         max_cdp_position: 10,
         max_liquidable_value: dec!(0.4),
-        liquidation_dex_swap_rate: dec!(1)
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
     };
 
     assert_eq!(
@@ -111,3 +182,209 @@ fn test_update_max_liquidable_value_invalid() {
         Err("Max liquidable value must be in range 0..1".into())
     );
 }
+
+#[test]
+fn test_update_target_health_factor() {
+    let market_config = MarketConfig {
+        max_cdp_position: 10,
+        max_liquidable_value: dec!(0.4),
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
+    };
+
+    let mut updated_market_config = market_config.clone();
+
+    assert_eq!(
+        updated_market_config.update(UpdateMarketConfigInput::TargetHealthFactor(Some(dec!(1.05)))),
+        Ok(())
+    );
+
+    assert_eq!(updated_market_config.target_health_factor, Some(dec!(1.05)));
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::TargetHealthFactor(Some(dec!(0.9)))),
+        Err("Target health factor must be greater than 1".into())
+    );
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::FullLiquidationHealthFactor(dec!(1.2))),
+        Err("Full liquidation health factor must be in range 0..1".into())
+    );
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::LiquidationDustValue(dec!(-1))),
+        Err("Liquidation dust value must be non-negative".into())
+    );
+}
+
+#[test]
+fn test_update_liquidation_grace_period() {
+    let market_config = MarketConfig {
+        max_cdp_position: 10,
+        max_liquidable_value: dec!(0.4),
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
+    };
+
+    let mut updated_market_config = market_config.clone();
+
+    assert_eq!(
+        updated_market_config.update(UpdateMarketConfigInput::LiquidationGracePeriod(Some(3600))),
+        Ok(())
+    );
+
+    assert_eq!(updated_market_config.liquidation_grace_period, Some(3600));
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::LiquidationGracePeriod(Some(0))),
+        Err("Liquidation grace period must be greater than 0".into())
+    );
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::HardLiquidationHealthFactor(dec!(1.1))),
+        Err("Hard liquidation health factor must be in range 0..1".into())
+    );
+}
+
+#[test]
+fn test_update_min_withdrawal_request_value_invalid() {
+    let mut market_config = MarketConfig {
+        max_cdp_position: 10,
+        max_liquidable_value: dec!(0.4),
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
+    };
+
+    assert_eq!(
+        market_config.update(UpdateMarketConfigInput::MinWithdrawalRequestValue(dec!(-1))),
+        Err("Min withdrawal request value must be non-negative".into())
+    );
+}
+
+#[test]
+fn test_update_borrow_ltv_margin() {
+    let market_config = MarketConfig {
+        max_cdp_position: 10,
+        max_liquidable_value: dec!(0.4),
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
+    };
+
+    let mut updated_market_config = market_config.clone();
+
+    assert_eq!(
+        updated_market_config.update(UpdateMarketConfigInput::BorrowLTVMargin(dec!(0.05))),
+        Ok(())
+    );
+
+    assert_eq!(updated_market_config.borrow_ltv_margin, dec!(0.05));
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::BorrowLTVMargin(dec!(-0.1))),
+        Err("Borrow LTV margin must be in range 0..1, excluding 1".into())
+    );
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::BorrowLTVMargin(dec!(1))),
+        Err("Borrow LTV margin must be in range 0..1, excluding 1".into())
+    );
+}
+
+#[test]
+fn test_update_health_band_ltvs() {
+    let market_config = MarketConfig {
+        max_cdp_position: 10,
+        max_liquidable_value: dec!(0.4),
+        liquidation_dex_swap_rate: dec!(1),
+        target_health_factor: None,
+        full_liquidation_health_factor: dec!(0.95),
+        liquidation_dust_value: dec!(0),
+        liquidation_grace_period: None,
+        hard_liquidation_health_factor: dec!(0.9),
+        min_withdrawal_request_value: dec!(10),
+        borrow_ltv_margin: dec!(0.1),
+        moderate_health_band_ltv: dec!(0.5),
+        risky_health_band_ltv: dec!(0.8),
+    };
+
+    let mut updated_market_config = market_config.clone();
+
+    assert_eq!(
+        updated_market_config.update(UpdateMarketConfigInput::ModerateHealthBandLTV(dec!(0.6))),
+        Ok(())
+    );
+    assert_eq!(
+        updated_market_config.update(UpdateMarketConfigInput::RiskyHealthBandLTV(dec!(0.9))),
+        Ok(())
+    );
+
+    assert_eq!(updated_market_config.moderate_health_band_ltv, dec!(0.6));
+    assert_eq!(updated_market_config.risky_health_band_ltv, dec!(0.9));
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::ModerateHealthBandLTV(dec!(0))),
+        Err("Health band LTVs must verify 0 < moderate < risky <= 1".into())
+    );
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::ModerateHealthBandLTV(dec!(0.8))),
+        Err("Health band LTVs must verify 0 < moderate < risky <= 1".into())
+    );
+
+    assert_eq!(
+        market_config
+            .clone()
+            .update(UpdateMarketConfigInput::RiskyHealthBandLTV(dec!(1.1))),
+        Err("Health band LTVs must verify 0 < moderate < risky <= 1".into())
+    );
+}
@@ -2,6 +2,15 @@
 // MIT License
 // Copyright (c) 2023 @WeftFinance
 
+//! Single resource pool with external liquidity support.
+//!
+//! The pool value is tracked with an internal accounting (`pooled_amount` and
+//! `external_liquidity_amount`) instead of the vault balance: assets can only enter the pool
+//! through its methods, so a transfer to the vault cannot move the unit to asset ratio, and the
+//! liquidity lent outside the pool is still counted in the pool value. Deposits made through
+//! `protected_deposit` do move the ratio, and the locked virtual liquidity bounds their effect,
+//! see `get_virtual_liquidity`.
+
 use scrypto::prelude::*;
 
 #[derive(ScryptoSbor, NonFungibleData)]
@@ -22,6 +31,19 @@ pub enum DepositType {
     LiquiditySupply,
 }
 
+// Max number of decimals of the virtual liquidity, see `get_virtual_liquidity`
+pub const VIRTUAL_LIQUIDITY_DECIMALS: u8 = 6;
+
+// Amount of pool units minted and locked in the pool at instantiation, backed by the same virtual amount
+// of asset: 10^-6, or the smallest amount of the asset if its divisibility is lower.
+// Most of a donation made to inflate the unit value goes to the locked units, making a first-depositor
+// attack unprofitable, and the unit value cannot be inflated enough to round a contribution to zero units
+pub fn get_virtual_liquidity(divisibility: u8) -> Decimal {
+    let decimals = divisibility.min(VIRTUAL_LIQUIDITY_DECIMALS);
+
+    Decimal::ONE / Decimal::from(10u64.pow(decimals as u32))
+}
+
 pub fn assert_fungible_res_address(address: ResourceAddress, message: Option<String>) {
     assert!(
         ResourceManager::from_address(address)
@@ -61,23 +83,39 @@ pub mod single_resource_pool {
 
             contribute => restrict_to :[can_contribute];
             redeem  => restrict_to :[can_redeem];
+            redeem_with_min_out => restrict_to :[can_redeem];
 
             get_pool_unit_ratio => PUBLIC;
             get_pool_unit_supply => PUBLIC;
             get_pooled_amount => PUBLIC;
 
+            preview_contribute => PUBLIC;
+            preview_redeem => PUBLIC;
+            max_redeemable => PUBLIC;
+            convert_to_assets => PUBLIC;
+            convert_to_units => PUBLIC;
+
         }
     }
 
     pub struct SingleResourcePool {
         /// Vault containing the pooled token
         liquidity: Vault,        
+        /// Amount of pooled token, tracked independently of the vault balance so that transfers
+        /// to the vault do not change the unit to asset ratio
+        pooled_amount: Decimal,
         /// Amount taken from the pool and not yet returned
         external_liquidity_amount: Decimal,
 
         /// Pool unit fungible resource manager
         pool_unit_res_manager: ResourceManager,
 
+        /// Pool units locked at instantiation, see `get_virtual_liquidity`
+        locked_units: Vault,
+
+        /// Virtual amount of asset backing the locked units
+        virtual_liquidity: Decimal,
+
         /// Ratio between the pool unit and the pooled token
         unit_to_asset_ratio: PreciseDecimal,
     }
@@ -91,7 +129,14 @@ pub mod single_resource_pool {
             /* CHECK INPUTS */
             assert_fungible_res_address(pool_resource_address, None);
 
-            let pool_unit_res_manager = ResourceBuilder::new_fungible(owner_role)
+            let divisibility = ResourceManager::from_address(pool_resource_address)
+                .resource_type()
+                .divisibility()
+                .expect("Pool resource must be fungible");
+
+            let virtual_liquidity = get_virtual_liquidity(divisibility);
+
+            let locked_units: Bucket = ResourceBuilder::new_fungible(owner_role)
                 .mint_roles(mint_roles! {
                     minter => component_rule.clone();
                     minter_updater => rule!(deny_all);
@@ -100,11 +145,17 @@ pub mod single_resource_pool {
                     burner => component_rule;
                     burner_updater => rule!(deny_all);
                 })
-                .create_with_no_initial_supply();
+                .mint_initial_supply(virtual_liquidity)
+                .into();
+
+            let pool_unit_res_manager = locked_units.resource_manager();
 
             let pool_component = Self {
                 liquidity: Vault::new(pool_resource_address),
+                pooled_amount: 0.into(),
                 pool_unit_res_manager,
+                locked_units: Vault::with_bucket(locked_units),
+                virtual_liquidity,
                 external_liquidity_amount: 0.into(),
                 unit_to_asset_ratio: 1.into(),
             }
@@ -159,11 +210,11 @@ pub mod single_resource_pool {
         }
 
         pub fn get_pool_unit_supply(&self) -> Decimal {
-            self.pool_unit_res_manager.total_supply().unwrap_or(dec!(0))
+            self.pool_unit_res_manager.total_supply().unwrap_or(dec!(0)) - self.locked_units.amount()
         }
 
         pub fn get_pooled_amount(&self) -> (Decimal, Decimal) {
-            (self.liquidity.amount(), self.external_liquidity_amount)
+            (self.pooled_amount, self.external_liquidity_amount)
         }
 
         // Handle request to increase liquidity.
@@ -175,11 +226,9 @@ pub mod single_resource_pool {
                 "Pool resource address mismatch"
             );
 
-            let unit_amount =
-                assets.amount() * self.unit_to_asset_ratio
-                    .checked_truncate(RoundingMode::AwayFromZero)
-                    .expect("Error while calculating unit amount to mint");
+            let unit_amount = self.convert_to_units(assets.amount());
 
+            self.pooled_amount += assets.amount();
             self.liquidity.put(assets);
 
             self.pool_unit_res_manager.mint(unit_amount)
@@ -194,21 +243,81 @@ pub mod single_resource_pool {
                 "Pool unit resource address mismatch"
             );
 
-            let amount = pool_units.amount() / self.unit_to_asset_ratio
-                .checked_truncate(RoundingMode::AwayFromZero)
-                .expect("Error while calculating amount to withdraw");
+            let amount = self.convert_to_assets(pool_units.amount());
 
             self.pool_unit_res_manager.burn(pool_units);
 
             assert!(
-                amount <= self.liquidity.amount(),
-                "Not enough liquidity to withdraw {}, liquidity is {}", amount, self.liquidity.amount()
+                amount <= self.pooled_amount,
+                "Not enough liquidity to withdraw {}, liquidity is {}", amount, self.pooled_amount
             );
 
-            self.liquidity.take_advanced(
+            let assets = self.liquidity.take_advanced(
                 amount,
                 WithdrawStrategy::Rounded(RoundingMode::ToNearestMidpointToEven),
-            )
+            );
+
+            self.pooled_amount -= assets.amount();
+
+            assets
+        }
+
+        // Redeem pool units, failing if the redeemed amount is lower than the expected minimum
+        pub fn redeem_with_min_out(&mut self, pool_units: Bucket, min_amount: Decimal) -> Bucket {
+            let assets = self.redeem(pool_units);
+
+            assert!(
+                assets.amount() >= min_amount,
+                "Redeemed amount {} is lower than the minimum {}", assets.amount(), min_amount
+            );
+
+            assets
+        }
+
+        // Amount of pool units minted by `contribute` for the given amount of asset
+        pub fn preview_contribute(&self, amount: Decimal) -> Decimal {
+            self.convert_to_units(amount)
+        }
+
+        // Amount of asset returned by `redeem` for the given amount of pool units,
+        // including the rounding to the asset divisibility
+        pub fn preview_redeem(&self, units: Decimal) -> Decimal {
+            let divisibility = self
+                .liquidity
+                .resource_manager()
+                .resource_type()
+                .divisibility()
+                .expect("Pool resource must be fungible");
+
+            self.convert_to_assets(units)
+                .checked_round(divisibility, RoundingMode::ToNearestMidpointToEven)
+                .expect("Error while calculating amount to withdraw")
+        }
+
+        // Maximum amount of the given pool units that can currently be redeemed,
+        // liquidity taken outside the pool cannot be redeemed until it is returned
+        pub fn max_redeemable(&self, units: Decimal) -> Decimal {
+            let max_units = self.convert_to_units(self.pooled_amount);
+
+            if units > max_units {
+                max_units
+            } else {
+                units
+            }
+        }
+
+        // Conversion of pool units to asset, with the same rounding as `redeem`
+        pub fn convert_to_assets(&self, units: Decimal) -> Decimal {
+            units / self.unit_to_asset_ratio
+                .checked_truncate(RoundingMode::AwayFromZero)
+                .expect("Error while calculating amount to withdraw")
+        }
+
+        // Conversion of asset to pool units, with the same rounding as `contribute`
+        pub fn convert_to_units(&self, amount: Decimal) -> Decimal {
+            amount * self.unit_to_asset_ratio
+                .checked_truncate(RoundingMode::AwayFromZero)
+                .expect("Error while calculating unit amount to mint")
         }
 
         pub fn protected_withdraw(
@@ -222,6 +331,8 @@ pub mod single_resource_pool {
 
             let assets = self.liquidity.take_advanced(amount, withdraw_strategy);
 
+            self.pooled_amount -= assets.amount();
+
             if withdraw_type == WithdrawType::ForTemporaryUse {
                 self.external_liquidity_amount += amount;
             } else {
@@ -236,6 +347,7 @@ pub mod single_resource_pool {
             assert_fungible_res_address(assets.resource_address(), None);
 
             let amount = assets.amount();
+            self.pooled_amount += amount;
             self.liquidity.put(assets);
 
             if deposit_type == DepositType::FromTemporaryUse {
@@ -263,15 +375,14 @@ pub mod single_resource_pool {
         /* PRIVATE UTILITY METHODS */
 
         fn _update_unit_to_asset_ratios(&mut self) {
-            let total_liquidity_amount = self.liquidity.amount() + self.external_liquidity_amount;
+            // Internal accounting and locked units are used, the vault balance is never read
+            let total_liquidity_amount =
+                self.pooled_amount + self.external_liquidity_amount + self.virtual_liquidity;
 
             let total_supply = self.pool_unit_res_manager.total_supply().unwrap_or(dec!(0));
 
-            self.unit_to_asset_ratio = if total_liquidity_amount != 0.into() {
-                PreciseDecimal::from(total_supply) / PreciseDecimal::from(total_liquidity_amount)
-            } else {
-                1.into()
-            };
+            self.unit_to_asset_ratio =
+                PreciseDecimal::from(total_supply) / PreciseDecimal::from(total_liquidity_amount);
         }
     }
 }
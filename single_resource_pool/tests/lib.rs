@@ -1 +1,305 @@
+use radix_engine::vm::NoExtension;
+use scrypto_test::prelude::*;
+use scrypto_unit::*;
 
+struct PoolTestHelper {
+    test_runner: TestRunner<NoExtension, InMemorySubstateDatabase>,
+    pool_component_address: ComponentAddress,
+    pool_unit_res_address: ResourceAddress,
+}
+
+impl PoolTestHelper {
+    fn new() -> PoolTestHelper {
+        let mut test_runner = TestRunnerBuilder::new().build();
+
+        let package_address = test_runner.compile_and_publish(this_package!());
+
+        // All the roles are public so that any account can act as the lending market
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "SingleResourcePool",
+                "instantiate",
+                manifest_args!(
+                    XRD,
+                    OwnerRole::None,
+                    rule!(allow_all),
+                    rule!(allow_all),
+                    rule!(allow_all),
+                    rule!(allow_all)
+                ),
+            )
+            .build();
+
+        let result = test_runner
+            .execute_manifest(manifest, vec![])
+            .expect_commit_success()
+            .clone();
+
+        PoolTestHelper {
+            test_runner,
+            pool_component_address: result.new_component_addresses()[0],
+            pool_unit_res_address: result.new_resource_addresses()[0],
+        }
+    }
+
+    fn contribute(
+        &mut self,
+        public_key: Secp256k1PublicKey,
+        account_address: ComponentAddress,
+        amount: Decimal,
+    ) -> TransactionReceipt {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(account_address, XRD, amount)
+            .take_all_from_worktop(XRD, "assets")
+            .with_name_lookup(|builder, lookup| {
+                let bucket = lookup.bucket("assets");
+                builder.call_method(self.pool_component_address, "contribute", manifest_args!(bucket))
+            })
+            .deposit_batch(account_address)
+            .build();
+
+        self.test_runner.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&public_key)],
+        )
+    }
+
+    fn donate(
+        &mut self,
+        public_key: Secp256k1PublicKey,
+        account_address: ComponentAddress,
+        amount: Decimal,
+    ) -> TransactionReceipt {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(account_address, XRD, amount)
+            .take_all_from_worktop(XRD, "assets")
+            .with_name_lookup(|builder, lookup| {
+                let bucket = lookup.bucket("assets");
+                builder.call_method(
+                    self.pool_component_address,
+                    "protected_deposit",
+                    manifest_args!(bucket, DepositType::LiquiditySupply),
+                )
+            })
+            .build();
+
+        self.test_runner.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&public_key)],
+        )
+    }
+
+    fn redeem(
+        &mut self,
+        public_key: Secp256k1PublicKey,
+        account_address: ComponentAddress,
+        units: Decimal,
+    ) -> TransactionReceipt {
+        let pool_unit_res_address = self.pool_unit_res_address;
+
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(account_address, pool_unit_res_address, units)
+            .take_all_from_worktop(pool_unit_res_address, "pool_units")
+            .with_name_lookup(|builder, lookup| {
+                let bucket = lookup.bucket("pool_units");
+                builder.call_method(self.pool_component_address, "redeem", manifest_args!(bucket))
+            })
+            .deposit_batch(account_address)
+            .build();
+
+        self.test_runner.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&public_key)],
+        )
+    }
+
+    fn call_getter(&mut self, method_name: &str) -> TransactionReceipt {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(self.pool_component_address, method_name, manifest_args!())
+            .build();
+
+        self.test_runner.execute_manifest(manifest, vec![])
+    }
+}
+
+#[derive(ManifestSbor)]
+enum DepositType {
+    #[allow(dead_code)]
+    FromTemporaryUse,
+    LiquiditySupply,
+}
+
+#[test]
+fn test_first_depositor_donation_attack() {
+    let mut helper = PoolTestHelper::new();
+
+    let (attacker_key, _, attacker_account) = helper.test_runner.new_allocated_account();
+    let (victim_key, _, victim_account) = helper.test_runner.new_allocated_account();
+
+    let attacker_initial_balance = helper.test_runner.get_component_balance(attacker_account, XRD);
+
+    // The attacker contributes the smallest possible amount, then donates to inflate the unit value
+    helper
+        .contribute(attacker_key, attacker_account, Decimal::ONE_ATTO)
+        .expect_commit_success();
+
+    helper
+        .donate(attacker_key, attacker_account, dec!(1_000))
+        .expect_commit_success();
+
+    // The victim still receives pool units for the contribution
+    helper
+        .contribute(victim_key, victim_account, dec!(1_000))
+        .expect_commit_success();
+
+    let victim_units = helper
+        .test_runner
+        .get_component_balance(victim_account, helper.pool_unit_res_address);
+
+    assert!(victim_units > Decimal::ZERO);
+
+    // The victim gets the contribution back
+    let victim_balance = helper.test_runner.get_component_balance(victim_account, XRD);
+
+    helper
+        .redeem(victim_key, victim_account, victim_units)
+        .expect_commit_success();
+
+    assert!(
+        helper.test_runner.get_component_balance(victim_account, XRD) - victim_balance
+            >= dec!(999)
+    );
+
+    // The attack is not profitable
+    let attacker_units = helper
+        .test_runner
+        .get_component_balance(attacker_account, helper.pool_unit_res_address);
+
+    helper
+        .redeem(attacker_key, attacker_account, attacker_units)
+        .expect_commit_success();
+
+    assert!(
+        helper.test_runner.get_component_balance(attacker_account, XRD)
+            < attacker_initial_balance - dec!(900)
+    );
+}
+
+#[test]
+fn test_donation_before_first_deposit() {
+    let mut helper = PoolTestHelper::new();
+
+    let (attacker_key, _, attacker_account) = helper.test_runner.new_allocated_account();
+    let (victim_key, _, victim_account) = helper.test_runner.new_allocated_account();
+
+    // The attacker donates to the empty pool, then contributes the smallest possible amount
+    helper
+        .donate(attacker_key, attacker_account, dec!(1_000))
+        .expect_commit_success();
+
+    helper
+        .contribute(attacker_key, attacker_account, Decimal::ONE_ATTO)
+        .expect_commit_success();
+
+    // A small contribution of the victim is not rounded to zero pool units
+    helper
+        .contribute(victim_key, victim_account, dec!(0.5))
+        .expect_commit_success();
+
+    let victim_units = helper
+        .test_runner
+        .get_component_balance(victim_account, helper.pool_unit_res_address);
+
+    assert!(victim_units > Decimal::ZERO);
+
+    let victim_balance = helper.test_runner.get_component_balance(victim_account, XRD);
+
+    helper
+        .redeem(victim_key, victim_account, victim_units)
+        .expect_commit_success();
+
+    assert!(
+        helper.test_runner.get_component_balance(victim_account, XRD) - victim_balance
+            >= dec!(0.49)
+    );
+}
+
+#[test]
+fn test_internal_accounting() {
+    let mut helper = PoolTestHelper::new();
+
+    let (user_key, _, user_account) = helper.test_runner.new_allocated_account();
+
+    // Locked units are not part of the circulating supply
+    let supply: Decimal = helper
+        .call_getter("get_pool_unit_supply")
+        .expect_commit_success()
+        .output(1);
+
+    assert_eq!(supply, Decimal::ZERO);
+
+    helper
+        .contribute(user_key, user_account, dec!(100))
+        .expect_commit_success();
+
+    helper
+        .redeem(user_key, user_account, dec!(40))
+        .expect_commit_success();
+
+    let pooled_amount: (Decimal, Decimal) = helper
+        .call_getter("get_pooled_amount")
+        .expect_commit_success()
+        .output(1);
+
+    assert_eq!(pooled_amount, (dec!(60), Decimal::ZERO));
+
+    let supply: Decimal = helper
+        .call_getter("get_pool_unit_supply")
+        .expect_commit_success()
+        .output(1);
+
+    assert_eq!(supply, dec!(60));
+
+    // The ratio recomputed on an emptied pool is based on the locked units only
+    helper
+        .redeem(user_key, user_account, dec!(60))
+        .expect_commit_success();
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(
+            helper.pool_component_address,
+            "increase_external_liquidity",
+            manifest_args!(Decimal::ZERO),
+        )
+        .build();
+
+    helper
+        .test_runner
+        .execute_manifest(manifest, vec![])
+        .expect_commit_success();
+
+    let ratio: PreciseDecimal = helper
+        .call_getter("get_pool_unit_ratio")
+        .expect_commit_success()
+        .output(1);
+
+    assert_eq!(ratio, PreciseDecimal::ONE);
+
+    helper
+        .contribute(user_key, user_account, dec!(10))
+        .expect_commit_success();
+
+    assert_eq!(
+        helper
+            .test_runner
+            .get_component_balance(user_account, helper.pool_unit_res_address),
+        dec!(10)
+    );
+}